@@ -2,15 +2,7 @@
 
 set -eo pipefail
 
-echo "Compiling benchmarks in the release mode."
-
-pushd "benches"
-eval "cargo build --release"
-
-echo
-echo "Compiling and running the benchmark runner."
-
-popd > /dev/null
+echo "Compiling and running the memory benchmarks."
 eval "cargo run --release"
 
 echo
@@ -0,0 +1,96 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Wraps the system allocator, keeping track of the number of
+/// bytes allocated at the moment, the peak of that number, and
+/// the number of allocations.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn allocated(size: usize) {
+        let live_bytes = LIVE_BYTES.fetch_add(size, Ordering::SeqCst) + size;
+
+        PEAK_BYTES.fetch_max(live_bytes, Ordering::SeqCst);
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn deallocated(size: usize) {
+        LIVE_BYTES.fetch_sub(size, Ordering::SeqCst);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+
+        if !ptr.is_null() {
+            CountingAllocator::allocated(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+
+        if !ptr.is_null() {
+            CountingAllocator::allocated(layout.size());
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CountingAllocator::deallocated(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+
+        if !new_ptr.is_null() {
+            CountingAllocator::deallocated(layout.size());
+            CountingAllocator::allocated(new_size);
+        }
+
+        new_ptr
+    }
+}
+
+/// Memory used by a benchmark scenario, not counting
+/// the memory allocated before the scenario started.
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    /// Bytes held by the value produced by the scenario.
+    pub live_bytes: usize,
+    /// The largest number of bytes held at once while running the scenario.
+    pub peak_bytes: usize,
+    /// The number of allocations and reallocations made by the scenario.
+    pub allocations: usize,
+}
+
+/// Runs the scenario, and measures the memory it uses. The value produced by
+/// the scenario is dropped after measuring, to find out what it holds on to.
+pub fn measure<T, F: FnOnce() -> T>(scenario: F) -> Measurement {
+    let baseline = LIVE_BYTES.load(Ordering::SeqCst);
+
+    PEAK_BYTES.store(baseline, Ordering::SeqCst);
+    ALLOCATIONS.store(0, Ordering::SeqCst);
+
+    let value = scenario();
+
+    let live_bytes = LIVE_BYTES.load(Ordering::SeqCst).saturating_sub(baseline);
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst);
+
+    drop(value);
+
+    Measurement {
+        live_bytes,
+        peak_bytes: PEAK_BYTES.load(Ordering::SeqCst).saturating_sub(baseline),
+        allocations,
+    }
+}
@@ -1,125 +1,43 @@
 extern crate csv;
+extern crate im_rc;
+extern crate pvec;
+extern crate serde_json;
 
+mod alloc;
+mod scenarios;
+
+use alloc::CountingAllocator;
 use csv::Writer;
+use scenarios::TYPES;
+use serde_json::{json, Value};
 use std::env;
 use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-
-const STD_VEC: &str = "std-vec";
-
-const IM_RS_VECTOR_BALANCED: &str = "im-rs-vector-balanced";
-const IM_RS_VECTOR_RELAXED: &str = "im-rs-vector-relaxed";
 
-const PVEC_RRBVEC_BALANCED: &str = "pvec-rrbvec-balanced";
-const PVEC_RRBVEC_RELAXED: &str = "pvec-rrbvec-relaxed";
-const PVEC_STD: &str = "pvec-std";
-
-const RRBVEC: &str = "rrbvec";
-const RBVEC: &str = "rbvec";
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 struct Bench<'a> {
     name: &'a str,
-    types: Vec<&'a str>,
     sizes: Vec<usize>,
 }
 
-struct BenchRunner {
-    path: String,
-}
-
-impl BenchRunner {
-    fn new(exe_path: PathBuf) -> Self {
-        let benches_path = exe_path.join("benches").to_str().map(|it| it.to_string());
-
-        BenchRunner {
-            path: benches_path.unwrap(),
-        }
-    }
-
-    fn execute(&self, bench: Option<&str>, vec: Option<&str>, n: Option<&usize>) -> usize {
-        let mut command = Command::new("/usr/bin/time");
-        command.arg("-l").arg(self.path.clone());
-
-        if let Some(bench) = bench {
-            command.arg(bench);
-        }
-
-        if let Some(vec) = vec {
-            command.arg(vec);
-        }
-
-        if let Some(n) = n {
-            command.arg(n.to_string());
-        }
-
-        // The output of the benches process is piped into /dev/null
-        // to simplify parsing of the 'time' command output
-        command.arg("> /dev/null");
-
-        let output = command.output().expect("Failed to run benches.");
-
-        // For some reason, even when executed successfully, results of the
-        // child process are piped into the error stream.
-        let output_str = std::str::from_utf8(&output.stderr)
-            .expect("Couldn't read the output from a child process.");
-
-        // The maximum resident set size is located at line 2.
-        // Hence, we skip the first one.
-        let maximum_resident_set_size_report_line = output_str.lines().skip(1).next().unwrap();
-
-        // Tokenizing the line by splitting it using space as delimiter.
-        let maximum_resident_set_size_tokens = maximum_resident_set_size_report_line
-            .split_whitespace()
-            .collect::<Vec<&str>>();
-
-        // The first token is the actual size, then we take
-        // it and parse to a number type.
-        let maximum_resident_set_size = maximum_resident_set_size_tokens
-            .first()
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
-
-        maximum_resident_set_size
-    }
-
-    // Runs the benchmark process without any arguments
-    // to measure the memory footprint of the process.
-    fn baseline(&self) -> usize {
-        self.execute(None, None, None)
-    }
-
-    fn run(&self, bench: &str, vec: &str, n: &usize) -> usize {
-        self.execute(Some(bench), Some(vec), Some(n))
-    }
-}
-
 fn execute() -> Result<(), Box<dyn Error>> {
     let exe_path = env::current_exe().map(|mut path| {
         path.pop();
         path
     })?;
 
-    let report_path = exe_path.clone().join("report");
-    let bench_runner = BenchRunner::new(exe_path.clone());
+    let report_path = exe_path.join("report");
 
-    // Baseline is the memory footprint of the process
-    // without running any benchmarks. Its value is subtracted
-    // from the result of a benchmark to get a sense of
-    // how much memory vector consumes
-    let baseline = bench_runner.baseline();
+    // Optionally, the benchmarks can be narrowed
+    // down to a single bench and vector type.
+    let mut args = env::args().skip(1);
+    let bench_filter = args.next();
+    let vec_filter = args.next();
 
     let update_clone = Bench {
         name: "update_clone",
-        types: vec![
-            STD_VEC,
-            RBVEC,
-            PVEC_STD,
-            PVEC_RRBVEC_BALANCED,
-            IM_RS_VECTOR_BALANCED,
-        ],
         sizes: vec![
             1_000, 2_000, 4_000, 6_000, 8_000, 10_000, 20_000, 30_000, 40_000, 50_000, 60_000,
         ],
@@ -127,13 +45,6 @@ fn execute() -> Result<(), Box<dyn Error>> {
 
     let push = Bench {
         name: "push",
-        types: vec![
-            STD_VEC,
-            RBVEC,
-            PVEC_STD,
-            PVEC_RRBVEC_BALANCED,
-            IM_RS_VECTOR_BALANCED,
-        ],
         sizes: vec![
             6_000, 8_000, 10_000, 20_000, 40_000, 60_000, 80_000, 100_000, 200_000, 400_000,
             600_000, 800_000, 1_000_000, 2_000_000,
@@ -142,49 +53,62 @@ fn execute() -> Result<(), Box<dyn Error>> {
 
     let append = Bench {
         name: "append",
-        types: vec![
-            STD_VEC,
-            RBVEC,
-            RRBVEC,
-            PVEC_STD,
-            PVEC_RRBVEC_RELAXED,
-            IM_RS_VECTOR_RELAXED,
-        ],
         sizes: vec![
             6_000, 8_000, 10_000, 20_000, 40_000, 60_000, 80_000, 100_000, 200_000, 400_000,
             600_000, 800_000, 1_000_000, 2_000_000,
         ],
     };
 
-    let benchmarks = vec![update_clone, push, append];
+    let benchmarks = [update_clone, push, append];
+    let mut summary = serde_json::Map::new();
 
     for bench in benchmarks.iter() {
+        if bench_filter.iter().any(|it| it != bench.name) {
+            continue;
+        }
+
         println!("Running \"{}\" bench", bench.name);
 
-        for vec in bench.types.iter() {
+        let mut bench_summary = serde_json::Map::new();
+
+        for vec in TYPES.iter() {
+            if vec_filter.iter().any(|it| it != vec) {
+                continue;
+            }
+
             // Make sure that the directory for the report exists.
-            let bench_report_dir = report_path.clone().join(bench.name);
+            let bench_report_dir = report_path.join(bench.name);
             fs::create_dir_all(bench_report_dir.clone())?;
 
             let bench_report_path = bench_report_dir.join(format!("{}.csv", vec));
             let mut wtr = Writer::from_path(bench_report_path)?;
+            let mut vec_summary = Vec::new();
 
             for n in bench.sizes.iter() {
-                let maximum_resident_set_size = bench_runner.run(&bench.name, vec, n);
-                let maximum_resident_set_size_diff =
-                    maximum_resident_set_size.checked_sub(baseline).unwrap_or(0);
-
-                let n_str: &str = &n.to_string();
-                let maximum_resident_set_size_diff_str =
-                    &maximum_resident_set_size_diff.to_string();
-
-                wtr.write_record(&[n_str, maximum_resident_set_size_diff_str])?;
+                let measurement = scenarios::run(bench.name, vec, *n);
+
+                wtr.write_record(&[n.to_string(), measurement.peak_bytes.to_string()])?;
+                vec_summary.push(json!({
+                    "n": n,
+                    "live_bytes": measurement.live_bytes,
+                    "peak_bytes": measurement.peak_bytes,
+                    "allocations": measurement.allocations,
+                }));
             }
 
             wtr.flush()?;
+            bench_summary.insert(vec.to_string(), Value::Array(vec_summary));
         }
+
+        summary.insert(bench.name.to_string(), Value::Object(bench_summary));
     }
 
+    fs::create_dir_all(report_path.clone())?;
+    fs::write(
+        report_path.join("summary.json"),
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+
     Ok(())
 }
 
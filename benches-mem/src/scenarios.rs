@@ -0,0 +1,220 @@
+use super::alloc::{measure, Measurement};
+use im_rc::Vector as IVec;
+use pvec::core::{RbVec, RrbVec};
+use pvec::PVec;
+
+pub const STD_VEC: &str = "std-vec";
+
+pub const IM_RS_VECTOR_BALANCED: &str = "im-rs-vector-balanced";
+pub const IM_RS_VECTOR_RELAXED: &str = "im-rs-vector-relaxed";
+
+pub const PVEC_RRBVEC_BALANCED: &str = "pvec-rrbvec-balanced";
+pub const PVEC_RRBVEC_RELAXED: &str = "pvec-rrbvec-relaxed";
+pub const PVEC_STD: &str = "pvec-std";
+
+pub const RRBVEC: &str = "rrbvec";
+pub const RBVEC: &str = "rbvec";
+
+pub const TYPES: [&str; 8] = [
+    STD_VEC,
+    IM_RS_VECTOR_BALANCED,
+    IM_RS_VECTOR_RELAXED,
+    PVEC_RRBVEC_BALANCED,
+    PVEC_RRBVEC_RELAXED,
+    PVEC_STD,
+    RRBVEC,
+    RBVEC,
+];
+
+// The size of chunks appended to balanced vectors. It is
+// a multiple of branching factors of all vectors, which
+// keeps the trees free of relaxed nodes.
+const BALANCED_CHUNK_SIZE: usize = 4096;
+
+/// The shape of the tree a scenario should build, if the type has one.
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Any,
+    Balanced,
+    Relaxed,
+}
+
+mod append {
+    use super::*;
+
+    pub fn vec(vec: &mut Vec<usize>, mut data: Vec<usize>) {
+        vec.append(&mut data);
+    }
+
+    pub fn ivec(vec: &mut IVec<usize>, data: IVec<usize>) {
+        vec.append(data);
+    }
+
+    pub fn rbvec(vec: &mut RbVec<usize>, mut data: RbVec<usize>) {
+        vec.append(&mut data);
+    }
+
+    pub fn rrbvec(vec: &mut RrbVec<usize>, mut data: RrbVec<usize>) {
+        vec.append(&mut data);
+    }
+
+    pub fn pvec(vec: &mut PVec<usize>, mut data: PVec<usize>) {
+        vec.append(&mut data);
+    }
+}
+
+// Builds a vector of n elements by appending vectors of
+// growing sizes, which leaves relaxed nodes in the tree.
+macro_rules! build_relaxed {
+    ($init:expr, $push:ident, $append:path, $n:expr) => {{
+        let n = $n;
+        let mut vec = $init();
+        let mut i = 1;
+
+        while i < n && (vec.len() + i) <= n {
+            let mut vec_t = $init();
+
+            for j in 0..i {
+                vec_t.$push(j);
+            }
+
+            $append(&mut vec, vec_t);
+            i *= 2;
+        }
+
+        let mut vec_t = $init();
+        for j in vec.len()..n {
+            vec_t.$push(j);
+        }
+
+        $append(&mut vec, vec_t);
+        vec
+    }};
+}
+
+// Builds a vector of n elements by appending vectors of the
+// same size, aligned to the branching factor of the tree.
+macro_rules! build_balanced {
+    ($init:expr, $push:ident, $append:path, $n:expr) => {{
+        let n = $n;
+        let mut vec = $init();
+
+        while vec.len() < n {
+            let mut vec_t = $init();
+
+            for j in vec.len()..n.min(vec.len() + BALANCED_CHUNK_SIZE) {
+                vec_t.$push(j);
+            }
+
+            $append(&mut vec, vec_t);
+        }
+
+        vec
+    }};
+}
+
+// Building a different vectors from
+// scratch to demonstrate its memory
+// footprint. Relaxed vectors are built
+// by pushing onto a relaxed tree.
+macro_rules! push {
+    ($init:expr, $push:ident, $append:path, $shape:expr, $n:expr) => {
+        measure(|| {
+            let n = $n;
+            let mut vec = if $shape == Shape::Relaxed {
+                build_relaxed!($init, $push, $append, n / 2)
+            } else {
+                $init()
+            };
+
+            for i in vec.len()..n {
+                vec.$push(i);
+            }
+
+            vec
+        })
+    };
+}
+
+// This benchmark demonstrates how structural
+// sharing can save memory.
+macro_rules! update_clone {
+    ($init:expr, $push:ident, $append:path, $shape:expr, $n:expr) => {
+        measure(|| {
+            let n = $n;
+            let vec = if $shape == Shape::Relaxed {
+                build_relaxed!($init, $push, $append, n)
+            } else {
+                let mut vec = $init();
+
+                for i in 0..n {
+                    vec.$push(i);
+                }
+
+                vec
+            };
+
+            let cln = vec.clone();
+            let mut bag = Vec::with_capacity(n);
+
+            for i in 0..n {
+                let mut tmp = cln.clone();
+                (*tmp.get_mut(i).unwrap()) += 1;
+
+                bag.push(tmp);
+            }
+
+            (vec, cln, bag)
+        })
+    };
+}
+
+// Evaluates the memory overhead induced by
+// appending vectors. This should reveal the
+// overhead of relaxed nodes if any.
+macro_rules! append {
+    ($init:expr, $push:ident, $append:path, $shape:expr, $n:expr) => {
+        measure(|| {
+            if $shape == Shape::Balanced {
+                build_balanced!($init, $push, $append, $n)
+            } else {
+                build_relaxed!($init, $push, $append, $n)
+            }
+        })
+    };
+}
+
+macro_rules! run {
+    ($scenario:ident, $vec:expr, $n:expr) => {
+        match $vec {
+            STD_VEC => $scenario!(Vec::new, push, append::vec, Shape::Any, $n),
+            IM_RS_VECTOR_BALANCED => {
+                $scenario!(IVec::new, push_back, append::ivec, Shape::Balanced, $n)
+            }
+            IM_RS_VECTOR_RELAXED => {
+                $scenario!(IVec::new, push_back, append::ivec, Shape::Relaxed, $n)
+            }
+            PVEC_RRBVEC_BALANCED => {
+                $scenario!(PVec::new_with_tree, push, append::pvec, Shape::Balanced, $n)
+            }
+            PVEC_RRBVEC_RELAXED => {
+                $scenario!(PVec::new_with_tree, push, append::pvec, Shape::Relaxed, $n)
+            }
+            PVEC_STD => $scenario!(PVec::new, push, append::pvec, Shape::Any, $n),
+            RRBVEC => $scenario!(RrbVec::new, push, append::rrbvec, Shape::Any, $n),
+            RBVEC => $scenario!(RbVec::new, push, append::rbvec, Shape::Any, $n),
+            &_ => panic!("Unsupported vec type={}", $vec),
+        }
+    };
+}
+
+/// Runs the scenario with a vector of the given
+/// type and size, and measures the memory it uses.
+pub fn run(bench: &str, vec: &str, n: usize) -> Measurement {
+    match bench {
+        "push" => run!(push, vec, n),
+        "update_clone" => run!(update_clone, vec, n),
+        "append" => run!(append, vec, n),
+        &_ => panic!("Unsupported bench={}", bench),
+    }
+}
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pvec::PVec;
+
+fuzz_target!(|data: &[u8]| pvec::fuzz::replay::<PVec<u32>>(data));
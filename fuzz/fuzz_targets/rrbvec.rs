@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pvec::core::RrbVec;
+
+fuzz_target!(|data: &[u8]| pvec::fuzz::replay::<RrbVec<u32>>(data));
@@ -0,0 +1,64 @@
+extern crate arbitrary;
+
+use self::arbitrary::{Arbitrary, Result, Unstructured};
+use super::PVec;
+use super::BRANCH_FACTOR;
+use crate::core::{RbVec, RrbVec};
+use std::fmt::Debug;
+use std::ops::ControlFlow;
+
+// The upper bound of steps taken to build a vector, as well as the
+// number of elements above which the vector is no longer doubled.
+const MAX_STEPS: u32 = 64;
+const MAX_DOUBLING_LEN: usize = BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR;
+
+// Vectors are not generated by pushing elements one by one, as that would
+// only ever produce balanced trees. Instead, chunks of random lengths are
+// appended, the vectors are split at random indices and appended to their
+// own clones, which leaves relaxed nodes at random places in the tree and
+// shares the nodes between the halves.
+macro_rules! impl_arbitrary {
+    ($vec:ident) => {
+        impl<'a, T: Arbitrary<'a> + Clone + Debug> Arbitrary<'a> for $vec<T> {
+            fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                let mut vec = $vec::new();
+
+                u.arbitrary_loop(None, Some(MAX_STEPS), |u| {
+                    match u.choose_index(3)? {
+                        0 => {
+                            let mut that = $vec::new();
+
+                            for _ in 0..u.int_in_range(0..=BRANCH_FACTOR * 2)? {
+                                that.push(T::arbitrary(u)?);
+                            }
+
+                            vec.append(&mut that);
+                        }
+                        1 => {
+                            let mid = u.int_in_range(0..=vec.len())?;
+                            let that = vec.split_off(mid);
+
+                            if bool::arbitrary(u)? {
+                                vec = that;
+                            }
+                        }
+                        _ => {
+                            if vec.len() < MAX_DOUBLING_LEN {
+                                let mut that = vec.clone();
+                                vec.append(&mut that);
+                            }
+                        }
+                    }
+
+                    Ok(ControlFlow::Continue(()))
+                })?;
+
+                Ok(vec)
+            }
+        }
+    };
+}
+
+impl_arbitrary!(RbVec);
+impl_arbitrary!(RrbVec);
+impl_arbitrary!(PVec);
@@ -0,0 +1,464 @@
+//! A module providing a persistent bit vector based on RrbTree.
+
+use crate::core::measure::{self, Measure, MeasuredRrbVec};
+use core::fmt;
+use core::iter::FromIterator;
+
+const WORD_BITS: u32 = 64;
+
+/// A word of up to 64 bits, where the unused high bits are always zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Word {
+    bits: u64,
+    len: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct BitSummary {
+    bits: usize,
+    ones: usize,
+}
+
+#[derive(Clone, Debug)]
+struct BitMeasure;
+
+#[inline(always)]
+fn low_bits(bits: u64, len: u32) -> u64 {
+    if len >= WORD_BITS {
+        bits
+    } else {
+        bits & ((1 << len) - 1)
+    }
+}
+
+impl Word {
+    #[inline(always)]
+    fn get(self, offset: u32) -> bool {
+        (self.bits >> offset) & 1 == 1
+    }
+
+    #[inline(always)]
+    fn set(&mut self, offset: u32, bit: bool) {
+        if bit {
+            self.bits |= 1 << offset;
+        } else {
+            self.bits &= !(1 << offset);
+        }
+    }
+
+    #[inline(always)]
+    fn is_full(self) -> bool {
+        self.len == WORD_BITS
+    }
+
+    /// Splits off the bits starting from the offset into a new word.
+    #[inline(always)]
+    fn split_off(&mut self, offset: u32) -> Word {
+        let right = Word {
+            bits: self.bits.checked_shr(offset).unwrap_or(0),
+            len: self.len - offset,
+        };
+
+        self.bits = low_bits(self.bits, offset);
+        self.len = offset;
+
+        right
+    }
+
+    /// Returns the offset of the set bit with the given rank.
+    #[inline(always)]
+    fn select(self, rank: usize) -> u32 {
+        let mut bits = self.bits;
+
+        for _ in 0..rank {
+            bits &= bits - 1;
+        }
+
+        bits.trailing_zeros()
+    }
+}
+
+impl Measure<Word> for BitMeasure {
+    type Summary = BitSummary;
+
+    #[inline(always)]
+    fn identity() -> BitSummary {
+        BitSummary::default()
+    }
+
+    #[inline(always)]
+    fn measure(word: &Word) -> BitSummary {
+        BitSummary {
+            bits: word.len as usize,
+            ones: word.bits.count_ones() as usize,
+        }
+    }
+
+    #[inline(always)]
+    fn combine(left: &BitSummary, right: &BitSummary) -> BitSummary {
+        BitSummary {
+            bits: left.bits + right.bits,
+            ones: left.ones + right.ones,
+        }
+    }
+}
+
+/// A persistent bit vector, which packs bits into 64-bit words stored in the
+/// leaves of a relaxed RrbTree. Every node of the tree caches the number of
+/// bits and set bits in its subtree, hence [count_ones](PBitVec::count_ones)
+/// takes constant time, while random access, [rank](PBitVec::rank) and
+/// [select](PBitVec::select) take logarithmic time.
+///
+/// Splitting at a position which is not a multiple of 64 leaves partially
+/// filled words behind, which are kept as they are when vectors are appended.
+#[derive(Clone, Default)]
+pub struct PBitVec {
+    words: MeasuredRrbVec<Word, BitMeasure>,
+}
+
+/// An iterator over the bits of a [PBitVec](crate::bitvec::PBitVec).
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    words: measure::Iter<'a, Word, BitMeasure>,
+    word: Word,
+    offset: u32,
+    len: usize,
+}
+
+/// An iterator over the bits of a vector, packed into words of 64
+/// bits regardless of the lengths of the words stored in the tree.
+struct Chunks<'a> {
+    words: measure::Iter<'a, Word, BitMeasure>,
+    word: Word,
+}
+
+impl PBitVec {
+    /// Constructs a new, empty bit vector.
+    pub fn new() -> Self {
+        PBitVec {
+            words: MeasuredRrbVec::new(),
+        }
+    }
+
+    /// Returns the number of bits in the vector.
+    pub fn len(&self) -> usize {
+        self.words.summary().bits
+    }
+
+    /// Returns true if the vector has a length of 0.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns the number of set bits in the vector.
+    pub fn count_ones(&self) -> usize {
+        self.words.summary().ones
+    }
+
+    /// Returns the number of unset bits in the vector.
+    pub fn count_zeros(&self) -> usize {
+        let summary = self.words.summary();
+        summary.bits - summary.ones
+    }
+
+    /// Returns the index of the word holding the given bit, along with
+    /// the offset of the bit in the word and the summary of the preceding
+    /// words, or None if the index is out of bounds.
+    #[inline(always)]
+    fn locate(&self, index: usize) -> Option<(usize, u32, BitSummary)> {
+        let word = self.words.find_by_measure(|summary| summary.bits > index)?;
+        let prefix = self.words.range_summary(0..word);
+
+        Some((word, (index - prefix.bits) as u32, prefix))
+    }
+
+    /// Returns the bit at the given position or None if out of bounds.
+    pub fn get(&self, index: usize) -> Option<bool> {
+        self.locate(index)
+            .map(|(word, offset, _)| self.words[word].get(offset))
+    }
+
+    /// Sets the bit at the given position. Panics if the index is out of bounds.
+    pub fn set(&mut self, index: usize, bit: bool) {
+        let (word, offset, _) = self.locate(index).unwrap_or_else(|| {
+            panic!(
+                "index `{}` out of bounds in PBitVec of length `{}`",
+                index,
+                self.len()
+            )
+        });
+
+        if self.words[word].get(offset) != bit {
+            self.words.get_mut(word).unwrap().set(offset, bit);
+        }
+    }
+
+    /// Adds a bit to the back of the vector.
+    pub fn push(&mut self, bit: bool) {
+        match self.words.len().checked_sub(1) {
+            Some(last) if !self.words[last].is_full() => {
+                let mut word = self.words.get_mut(last).unwrap();
+
+                let offset = word.len;
+                word.len += 1;
+                word.set(offset, bit);
+            }
+            _ => self.words.push(Word {
+                bits: bit as u64,
+                len: 1,
+            }),
+        }
+    }
+
+    /// Removes the last bit from the vector and
+    /// returns it, or None if it is empty.
+    pub fn pop(&mut self) -> Option<bool> {
+        let last = self.words.len().checked_sub(1)?;
+
+        if self.words[last].len == 1 {
+            return self.words.pop().map(|word| word.get(0));
+        }
+
+        let mut word = self.words.get_mut(last).unwrap();
+
+        word.len -= 1;
+
+        let offset = word.len;
+        let bit = word.get(offset);
+
+        word.set(offset, false);
+        Some(bit)
+    }
+
+    /// Splits the vector into two at the given index. Returns a vector
+    /// containing the bits in the range [at, len), while the original
+    /// vector is left containing the bits [0, at).
+    pub fn split_off(&mut self, at: usize) -> PBitVec {
+        let len = self.len();
+
+        assert!(
+            at <= len,
+            "index `{}` out of bounds in PBitVec of length `{}`",
+            at,
+            len
+        );
+
+        let (word, offset, _) = match self.locate(at) {
+            Some(location) => location,
+            None => return PBitVec::new(),
+        };
+
+        let mut words = self.words.split_off(word);
+
+        if offset != 0 {
+            let mut left = words[0];
+            let right = left.split_off(offset);
+
+            *words.get_mut(0).unwrap() = right;
+            self.words.push(left);
+        }
+
+        PBitVec { words }
+    }
+
+    /// Moves all the bits of `that` to the end of `Self`, leaving `that` empty.
+    pub fn append(&mut self, that: &mut PBitVec) {
+        self.words.append(&mut that.words);
+    }
+
+    /// Returns the number of set bits in the range [0, index).
+    /// Panics if the index is out of bounds.
+    pub fn rank(&self, index: usize) -> usize {
+        match self.locate(index) {
+            Some((word, offset, prefix)) => {
+                prefix.ones + low_bits(self.words[word].bits, offset).count_ones() as usize
+            }
+            None => {
+                assert!(
+                    index == self.len(),
+                    "index `{}` out of bounds in PBitVec of length `{}`",
+                    index,
+                    self.len()
+                );
+
+                self.count_ones()
+            }
+        }
+    }
+
+    /// Returns the index of the set bit with the given rank, in other
+    /// words the position of the `rank + 1`-th set bit, or None if the
+    /// vector contains no more than `rank` set bits.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        let word = self.words.find_by_measure(|summary| summary.ones > rank)?;
+        let prefix = self.words.range_summary(0..word);
+
+        Some(prefix.bits + self.words[word].select(rank - prefix.ones) as usize)
+    }
+
+    /// Returns the bitwise and of two vectors of the same length.
+    /// Subtrees shared between the vectors are reused in the result.
+    pub fn and(&self, that: &PBitVec) -> PBitVec {
+        self.combine(that, true, |a, b| a & b)
+    }
+
+    /// Returns the bitwise or of two vectors of the same length.
+    /// Subtrees shared between the vectors are reused in the result.
+    pub fn or(&self, that: &PBitVec) -> PBitVec {
+        self.combine(that, true, |a, b| a | b)
+    }
+
+    /// Returns the bitwise xor of two vectors of the same length. Unlike
+    /// the other operations, shared subtrees can not be reused, as they
+    /// cancel out into zeroes.
+    pub fn xor(&self, that: &PBitVec) -> PBitVec {
+        self.combine(that, false, |a, b| a ^ b)
+    }
+
+    /// Combines two vectors word by word. If both vectors have the same shape,
+    /// the trees are zipped, so that the shared subtrees can be reused when
+    /// `reuse_shared` is set. Otherwise, the bits are combined in chunks.
+    fn combine<F>(&self, that: &PBitVec, reuse_shared: bool, f: F) -> PBitVec
+    where
+        F: Fn(u64, u64) -> u64,
+    {
+        assert_eq!(
+            self.len(),
+            that.len(),
+            "bitwise operation on PBitVecs of different lengths"
+        );
+
+        let zipped = self.words.zip_with(&that.words, reuse_shared, |a, b| {
+            if a.len == b.len {
+                Some(Word {
+                    bits: f(a.bits, b.bits),
+                    len: a.len,
+                })
+            } else {
+                None
+            }
+        });
+
+        if let Some(words) = zipped {
+            return PBitVec { words };
+        }
+
+        let mut words = MeasuredRrbVec::new();
+
+        for (a, b) in self.chunks().zip(that.chunks()) {
+            words.push(Word {
+                bits: f(a.bits, b.bits),
+                len: a.len,
+            });
+        }
+
+        PBitVec { words }
+    }
+
+    fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            words: self.words.iter(),
+            word: Word { bits: 0, len: 0 },
+        }
+    }
+
+    /// Returns an iterator over the bits of the vector.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: self.words.iter(),
+            word: Word { bits: 0, len: 0 },
+            offset: 0,
+            len: self.len(),
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.offset == self.word.len {
+            self.word = *self.words.next()?;
+            self.offset = 0;
+        }
+
+        let bit = self.word.get(self.offset);
+
+        self.offset += 1;
+        self.len -= 1;
+
+        Some(bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Word;
+
+    fn next(&mut self) -> Option<Word> {
+        let mut chunk = Word { bits: 0, len: 0 };
+
+        while !chunk.is_full() {
+            if self.word.len == 0 {
+                match self.words.next() {
+                    Some(word) => self.word = *word,
+                    None => break,
+                }
+            }
+
+            let len = (WORD_BITS - chunk.len).min(self.word.len);
+            let rest = self.word.split_off(len);
+
+            chunk.bits |= self.word.bits << chunk.len;
+            chunk.len += len;
+
+            self.word = rest;
+        }
+
+        if chunk.len == 0 {
+            None
+        } else {
+            Some(chunk)
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a PBitVec {
+    type Item = bool;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<bool> for PBitVec {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut vec = PBitVec::new();
+
+        for bit in iter {
+            vec.push(bit);
+        }
+
+        vec
+    }
+}
+
+impl PartialEq for PBitVec {
+    fn eq(&self, other: &PBitVec) -> bool {
+        self.len() == other.len() && self.chunks().eq(other.chunks())
+    }
+}
+
+impl Eq for PBitVec {}
+
+impl fmt::Debug for PBitVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
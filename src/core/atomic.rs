@@ -0,0 +1,185 @@
+//! A module providing a cell, which allows to share a changing
+//! [RrbVec](crate::core::RrbVec) between threads, without locking readers.
+
+use super::RrbVec;
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A cell holding a vector, which is read and updated concurrently by
+/// many threads. Readers obtain snapshots of the vector through
+/// [load](crate::core::atomic::AtomicRrbVec::load), which never blocks on
+/// writers, as a snapshot is a clone sharing all nodes with the vector in
+/// the cell. Writers apply their changes to a private copy of the vector
+/// and publish it with a compare-and-swap, retrying on conflicts.
+///
+/// The cell keeps the vector behind an [Arc](std::sync::Arc). A reader
+/// registers itself in the counter of the current generation for the short
+/// time it takes to read the pointer and bump its reference count. A writer
+/// that has just replaced the vector starts a new generation, and waits for
+/// the counter of the previous one to drop to zero before releasing its
+/// reference to the old vector. New readers join the new generation, so
+/// the writer only waits for the readers that might have seen the old
+/// vector. Writers take turns in waiting, one generation at a time.
+pub struct AtomicRrbVec<T> {
+    ptr: AtomicPtr<RrbVec<T>>,
+    generation: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    retiring: Mutex<()>,
+    marker: PhantomData<Arc<RrbVec<T>>>,
+}
+
+unsafe impl<T: Send + Sync> Send for AtomicRrbVec<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicRrbVec<T> {}
+
+impl<T: Clone + Debug> AtomicRrbVec<T> {
+    /// Constructs a new cell holding the given vector.
+    pub fn new(vec: RrbVec<T>) -> Self {
+        AtomicRrbVec {
+            ptr: AtomicPtr::new(Arc::into_raw(Arc::new(vec)) as *mut RrbVec<T>),
+            generation: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            retiring: Mutex::new(()),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a snapshot of the vector, which is not
+    /// affected by the updates happening afterwards.
+    pub fn load(&self) -> RrbVec<T> {
+        (*self.acquire()).clone()
+    }
+
+    /// Replaces the vector in the cell.
+    pub fn store(&self, vec: RrbVec<T>) {
+        self.swap(vec);
+    }
+
+    /// Replaces the vector in the cell, returning the previous one.
+    pub fn swap(&self, vec: RrbVec<T>) -> RrbVec<T> {
+        let new = Arc::into_raw(Arc::new(vec)) as *mut RrbVec<T>;
+        let old = self.ptr.swap(new, Ordering::SeqCst);
+
+        Arc::try_unwrap(self.retire(old)).unwrap_or_else(|old| (*old).clone())
+    }
+
+    /// Applies the function to a copy of the current vector and publishes
+    /// the result, unless the vector has been replaced by another writer in
+    /// the meantime. In that case, the function is applied again to the new
+    /// vector, hence it might be called several times. Returns the result
+    /// of the last call.
+    pub fn update<F, R>(&self, mut f: F) -> R
+    where
+        F: FnMut(&mut RrbVec<T>) -> R,
+    {
+        loop {
+            let current = self.acquire();
+
+            let mut vec = (*current).clone();
+            let result = f(&mut vec);
+
+            let expected = Arc::as_ptr(&current) as *mut RrbVec<T>;
+            let new = Arc::into_raw(Arc::new(vec)) as *mut RrbVec<T>;
+
+            // The snapshot keeps the current vector alive, hence
+            // its address can not be reused by a new vector.
+            match self
+                .ptr
+                .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(old) => {
+                    self.retire(old);
+                    return result;
+                }
+                Err(..) => unsafe {
+                    drop(Arc::from_raw(new));
+                },
+            }
+        }
+    }
+
+    /// Consumes the cell, returning the vector.
+    pub fn into_inner(self) -> RrbVec<T> {
+        let ptr = self.ptr.swap(ptr::null_mut(), Ordering::SeqCst);
+        let vec = unsafe { Arc::from_raw(ptr) };
+
+        Arc::try_unwrap(vec).unwrap_or_else(|vec| (*vec).clone())
+    }
+
+    fn acquire(&self) -> Arc<RrbVec<T>> {
+        // A reader which registers after the generation has changed might
+        // be missed by the writer waiting for it, hence it moves on to the
+        // new generation.
+        let readers = loop {
+            let generation = self.generation.load(Ordering::SeqCst);
+            let readers = &self.readers[generation % 2];
+
+            readers.fetch_add(1, Ordering::SeqCst);
+
+            if self.generation.load(Ordering::SeqCst) == generation {
+                break readers;
+            }
+
+            readers.fetch_sub(1, Ordering::SeqCst);
+        };
+
+        let vec = unsafe {
+            let ptr = self.ptr.load(Ordering::SeqCst);
+
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+
+        readers.fetch_sub(1, Ordering::SeqCst);
+        vec
+    }
+
+    /// Takes over the reference held by the cell to a vector which has just
+    /// been replaced, once no reader can be in the middle of acquiring it.
+    fn retire(&self, ptr: *mut RrbVec<T>) -> Arc<RrbVec<T>> {
+        let _turn = self.retiring.lock().unwrap_or_else(|err| err.into_inner());
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst);
+        let readers = &self.readers[generation % 2];
+
+        while readers.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+
+        unsafe { Arc::from_raw(ptr) }
+    }
+}
+
+impl<T> Drop for AtomicRrbVec<T> {
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+
+        if !ptr.is_null() {
+            unsafe {
+                drop(Arc::from_raw(ptr));
+            }
+        }
+    }
+}
+
+impl<T: Clone + Debug> Default for AtomicRrbVec<T> {
+    fn default() -> Self {
+        AtomicRrbVec::new(RrbVec::new())
+    }
+}
+
+impl<T: Clone + Debug> From<RrbVec<T>> for AtomicRrbVec<T> {
+    fn from(vec: RrbVec<T>) -> Self {
+        AtomicRrbVec::new(vec)
+    }
+}
+
+impl<T: Clone + Debug> Debug for AtomicRrbVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicRrbVec").field(&self.load()).finish()
+    }
+}
@@ -0,0 +1,109 @@
+//! A module providing deduplication of structurally equal nodes,
+//! which lets [RrbVec](crate::core::RrbVec)s that were built
+//! independently share the memory of their common content.
+
+use super::rrbtree::dedup::NodeInterner;
+use super::RrbVec;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops;
+
+/// Counters of the work done by the [Deduplicator](crate::core::dedup::Deduplicator).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    /// The number of nodes walked.
+    pub visited_nodes: usize,
+    /// The number of nodes replaced by an equal shared instance.
+    pub replaced_nodes: usize,
+    /// The memory taken by the replaced nodes, which is released once no
+    /// other vector refers to them. Memory owned by elements is not counted.
+    pub bytes_saved: usize,
+}
+
+/// An interner of tree nodes. Leaves with equal elements, and branches
+/// with the same children, are replaced by a single shared instance.
+///
+/// The deduplicator keeps the canonical instances, hence vectors passed to
+/// it later are deduplicated against the ones seen before. The vectors copy
+/// the shared nodes on write, like they do with their clones.
+#[derive(Debug)]
+pub struct Deduplicator<T> {
+    interner: NodeInterner<T>,
+    total: DedupStats,
+}
+
+impl ops::AddAssign for DedupStats {
+    fn add_assign(&mut self, that: DedupStats) {
+        self.visited_nodes += that.visited_nodes;
+        self.replaced_nodes += that.replaced_nodes;
+        self.bytes_saved += that.bytes_saved;
+    }
+}
+
+impl<T: Clone + Debug + Hash + Eq> Default for Deduplicator<T> {
+    fn default() -> Self {
+        Deduplicator::new()
+    }
+}
+
+impl<T: Clone + Debug + Hash + Eq> Deduplicator<T> {
+    /// Constructs a new deduplicator without any nodes.
+    pub fn new() -> Self {
+        Deduplicator {
+            interner: NodeInterner::new(),
+            total: DedupStats::default(),
+        }
+    }
+
+    /// Returns the number of canonical nodes held by the deduplicator.
+    pub fn len(&self) -> usize {
+        self.interner.len()
+    }
+
+    /// Returns true if the deduplicator holds no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the counters accumulated over all calls.
+    pub fn stats(&self) -> DedupStats {
+        self.total
+    }
+
+    /// Replaces nodes of the vector with the equal nodes seen so far,
+    /// and returns the counters of this call. Subtrees that are already
+    /// shared with the deduplicator are skipped.
+    pub fn dedup(&mut self, vec: &mut RrbVec<T>) -> DedupStats {
+        let mut stats = DedupStats::default();
+
+        self.interner.dedup(&mut vec.tree, &mut stats);
+        self.total += stats;
+
+        stats
+    }
+
+    /// Deduplicates all of the vectors, including the nodes they have in common.
+    pub fn dedup_all<'a, I>(&mut self, vecs: I) -> DedupStats
+    where
+        I: IntoIterator<Item = &'a mut RrbVec<T>>,
+        T: 'a,
+    {
+        let mut stats = DedupStats::default();
+
+        for vec in vecs {
+            stats += self.dedup(vec);
+        }
+
+        stats
+    }
+
+    /// Releases the nodes which are no longer referenced by any vector.
+    pub fn purge(&mut self) {
+        self.interner.purge();
+    }
+
+    /// Releases all nodes held by the deduplicator.
+    pub fn clear(&mut self) {
+        self.interner = NodeInterner::new();
+    }
+}
@@ -0,0 +1,1638 @@
+//! A module providing a persistent vector, whose nodes are kept
+//! in a pluggable store of records and loaded on demand.
+
+use super::merkle::Element;
+use super::rrbtree::BRANCH_FACTOR;
+use super::sharedptr::{SharedPtr, Take};
+use super::split_ranges;
+use std::env;
+use std::fmt;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::iter::{self, FromIterator, Sum};
+use std::mem;
+use std::ops;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::vec;
+
+/// The number of leaves a [DiskRrbVec](crate::core::disk::DiskRrbVec)
+/// keeps in memory, unless its store asks for another budget.
+pub const DEFAULT_CACHE_BUDGET: usize = 1024;
+
+const LEAF: u8 = 0;
+const BRANCH: u8 = 1;
+
+const EMPTY: u8 = 0;
+const ROOTED: u8 = 1;
+
+/// Identifies a record in a [NodeStore](crate::core::disk::NodeStore).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordId(pub u64);
+
+/// A storage of immutable records holding the nodes of
+/// [DiskRrbVec](crate::core::disk::DiskRrbVec), along with the headers
+/// written by [flush](crate::core::disk::DiskRrbVec::flush). Records are
+/// never modified, a changed node is written into a new record instead.
+/// Clones of a store refer to the same records.
+pub trait NodeStore: Clone {
+    /// Appends a record and returns its id.
+    fn write(&self, record: &[u8]) -> io::Result<RecordId>;
+
+    /// Reads the record with the given id.
+    fn read(&self, id: RecordId) -> io::Result<Vec<u8>>;
+
+    /// Makes the records written so far durable.
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns true if both stores refer to the same records.
+    fn same_store(&self, other: &Self) -> bool;
+
+    /// Returns the number of leaves the vectors using
+    /// this store keep in memory by default.
+    fn cache_budget(&self) -> usize {
+        DEFAULT_CACHE_BUDGET
+    }
+}
+
+/// A store keeping records in memory, which is mostly useful for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    records: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+/// A store appending records to a file, each prefixed with its length.
+/// The id of a record is its offset in the file.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    inner: Arc<Mutex<StoreFile>>,
+}
+
+#[derive(Debug)]
+struct StoreFile {
+    path: PathBuf,
+    file: Option<File>,
+    len: u64,
+    temporary: bool,
+}
+
+/// A node of the vector. All leaves are at the same depth, and
+/// nodes hold from one up to BRANCH_FACTOR children or elements.
+#[derive(Debug)]
+enum Node<T> {
+    Leaf(SharedPtr<Vec<T>>),
+    Branch(SharedPtr<Vec<Link<T>>>),
+}
+
+/// A reference to a node, which is loaded into memory, written into the
+/// store, or both. A link without a record points to a node which has
+/// not been written yet, while a dirty one might have been modified
+/// since it was loaded or written. The state sits behind a lock, as
+/// reads load and evict nodes through shared references.
+#[derive(Debug)]
+struct Link<T> {
+    len: usize,
+    state: Mutex<LinkState<T>>,
+}
+
+#[derive(Debug)]
+struct LinkState<T> {
+    record: Option<RecordId>,
+    dirty: bool,
+    node: Option<Node<T>>,
+    last_used: u64,
+}
+
+/// The store of a vector along with the state of its leaf cache.
+/// The number of loaded leaves is tracked approximately, as loads
+/// through shared nodes are visible to all vectors sharing them,
+/// and it is recounted by every eviction.
+#[derive(Debug)]
+struct Cache<S> {
+    store: S,
+    budget: usize,
+    loaded: AtomicUsize,
+    clock: AtomicU64,
+}
+
+/// A persistent vector, whose nodes are loaded from a
+/// [NodeStore](crate::core::disk::NodeStore) when they are accessed,
+/// and written back when the number of loaded leaves exceeds the cache
+/// budget, in which case the least recently used leaves are dropped
+/// from memory, along with the branches left without loaded children.
+/// Modified nodes are written into new records, hence clones of the
+/// vector and the vectors reopened from earlier
+/// [flushes](crate::core::disk::DiskRrbVec::flush) are never affected.
+///
+/// Both reads and modifications evict nodes. Elements are therefore
+/// returned through [DiskRef](crate::core::disk::DiskRef), which keeps
+/// the leaf of the element alive after it is evicted.
+///
+/// The errors of the store are returned by
+/// [try_get](crate::core::disk::DiskRrbVec::try_get) and
+/// [try_iter](crate::core::disk::DiskRrbVec::try_iter). The other operations
+/// which load nodes panic if a node can not be read from the store.
+pub struct DiskRrbVec<T, S> {
+    root: Option<Link<T>>,
+    height: usize,
+    cache: Cache<S>,
+}
+
+/// A reference to an element of the [DiskRrbVec](crate::core::disk::DiskRrbVec),
+/// which keeps the leaf of the element in memory until it is dropped.
+pub struct DiskRef<T> {
+    leaf: SharedPtr<Vec<T>>,
+    offset: usize,
+}
+
+/// An iterator over references to elements of the
+/// [DiskRrbVec](crate::core::disk::DiskRrbVec),
+/// which holds a single leaf at a time.
+pub struct Iter<'a, T, S> {
+    inner: TryIter<'a, T, S>,
+}
+
+/// An iterator over references to elements of the
+/// [DiskRrbVec](crate::core::disk::DiskRrbVec), which returns
+/// an error for every element of a leaf that can not be read.
+pub struct TryIter<'a, T, S> {
+    vec: &'a DiskRrbVec<T, S>,
+    leaf: Option<SharedPtr<Vec<T>>>,
+    offset: usize,
+    index: usize,
+}
+
+/// An iterator over the elements of the
+/// [DiskRrbVec](crate::core::disk::DiskRrbVec), which
+/// takes the ownership of the vector.
+pub struct IntoIter<T, S> {
+    vec: DiskRrbVec<T, S>,
+    head_index: usize,
+    head_chunk: vec::IntoIter<T>,
+    tail_index: usize,
+    tail_chunk: vec::IntoIter<T>,
+    len: usize,
+}
+
+fn malformed(id: RecordId) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed record {:?}", id),
+    )
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Unwraps the result of loading nodes in
+/// the operations which do not return errors.
+fn or_panic<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Returns the child of the branch of the given height and length
+/// holding the element at the given index, along with the index
+/// of the element in the child.
+fn find_child<T>(
+    children: &[Link<T>],
+    len: usize,
+    height: usize,
+    mut index: usize,
+) -> (usize, usize) {
+    // All children but the last one are full unless the branch has been
+    // split or appended, otherwise the child is found by its length.
+    let last = children.len() - 1;
+
+    if let Some(capacity) = BRANCH_FACTOR.checked_pow(height as u32) {
+        if len - children[last].len == last * capacity {
+            return (index / capacity, index % capacity);
+        }
+    }
+
+    for (i, child) in children.iter().enumerate() {
+        if index < child.len {
+            return (i, index);
+        }
+
+        index -= child.len;
+    }
+
+    unreachable!("index is out of the bounds of the node")
+}
+
+impl MemoryStore {
+    /// Constructs a new, empty store.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Returns the number of records in the store.
+    pub fn len(&self) -> usize {
+        self.records().len()
+    }
+
+    /// Returns true if the store has no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn records(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        self.records.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl NodeStore for MemoryStore {
+    fn write(&self, record: &[u8]) -> io::Result<RecordId> {
+        let mut records = self.records();
+        records.push(record.to_vec());
+
+        Ok(RecordId(records.len() as u64 - 1))
+    }
+
+    fn read(&self, id: RecordId) -> io::Result<Vec<u8>> {
+        self.records()
+            .get(id.0 as usize)
+            .cloned()
+            .ok_or_else(|| malformed(id))
+    }
+
+    fn same_store(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.records, &other.records)
+    }
+}
+
+impl FileStore {
+    /// Opens the store kept in the given file, creating the file if it does
+    /// not exist. Records written to the file before remain readable.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref())?;
+
+        Ok(FileStore::with_file(StoreFile {
+            path: path.as_ref().to_path_buf(),
+            len: file.metadata()?.len(),
+            file: Some(file),
+            temporary: false,
+        }))
+    }
+
+    /// Constructs a store in a new file in the temporary directory, which
+    /// is created on the first write and removed once the last clone of
+    /// the store is dropped.
+    pub fn temporary() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "pvec-{}-{}.store",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+
+        FileStore::with_file(StoreFile {
+            path: env::temp_dir().join(name),
+            file: None,
+            len: 0,
+            temporary: true,
+        })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> PathBuf {
+        self.file().path.clone()
+    }
+
+    fn with_file(file: StoreFile) -> Self {
+        FileStore {
+            inner: Arc::new(Mutex::new(file)),
+        }
+    }
+
+    fn file(&self) -> MutexGuard<'_, StoreFile> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl StoreFile {
+    fn handle(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            self.file = Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        Ok(self.file.as_mut().unwrap())
+    }
+}
+
+impl Drop for StoreFile {
+    fn drop(&mut self) {
+        if self.temporary && self.file.take().is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Default for FileStore {
+    fn default() -> Self {
+        FileStore::temporary()
+    }
+}
+
+impl NodeStore for FileStore {
+    fn write(&self, record: &[u8]) -> io::Result<RecordId> {
+        let mut store = self.file();
+        let offset = store.len;
+
+        let mut bytes = Vec::with_capacity(record.len() + 8);
+        (record.len() as u64).encode(&mut bytes);
+        bytes.extend_from_slice(record);
+
+        let file = store.handle()?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&bytes)?;
+
+        store.len += bytes.len() as u64;
+        Ok(RecordId(offset))
+    }
+
+    fn read(&self, id: RecordId) -> io::Result<Vec<u8>> {
+        let mut store = self.file();
+        let len = store.len;
+
+        if id.0.checked_add(8).map_or(true, |end| end > len) {
+            return Err(malformed(id));
+        }
+
+        let file = store.handle()?;
+        file.seek(SeekFrom::Start(id.0))?;
+
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+
+        let record_len = u64::from_le_bytes(header);
+        if record_len > len - id.0 - 8 {
+            return Err(malformed(id));
+        }
+
+        let mut record = vec![0; record_len as usize];
+        file.read_exact(&mut record)?;
+
+        Ok(record)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        match self.file().file {
+            Some(ref file) => file.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    fn same_store(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        match *self {
+            Node::Leaf(ref items) => Node::Leaf(items.clone()),
+            Node::Branch(ref children) => Node::Branch(children.clone()),
+        }
+    }
+}
+
+impl<T> Link<T> {
+    fn new(len: usize, node: Node<T>, last_used: u64) -> Self {
+        Link {
+            len,
+            state: Mutex::new(LinkState {
+                record: None,
+                dirty: true,
+                node: Some(node),
+                last_used,
+            }),
+        }
+    }
+
+    fn stored(record: RecordId, len: usize) -> Self {
+        Link {
+            len,
+            state: Mutex::new(LinkState {
+                record: Some(record),
+                dirty: false,
+                node: None,
+                last_used: 0,
+            }),
+        }
+    }
+
+    fn state_mut(&mut self) -> &mut LinkState<T> {
+        self.state.get_mut().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn node(&self) -> Option<Node<T>> {
+        lock(&self.state).node.clone()
+    }
+
+    /// Collects the times the loaded leaves of the subtree were last used.
+    fn collect_stamps(&self, stamps: &mut Vec<u64>) {
+        let (node, last_used) = {
+            let state = lock(&self.state);
+            (state.node.clone(), state.last_used)
+        };
+
+        match node {
+            Some(Node::Leaf(..)) => stamps.push(last_used),
+            Some(Node::Branch(children)) => {
+                for child in children.iter() {
+                    child.collect_stamps(stamps);
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn loaded_leaves(&self) -> usize {
+        match self.node() {
+            Some(Node::Leaf(..)) => 1,
+            Some(Node::Branch(children)) => children.iter().map(Link::loaded_leaves).sum(),
+            None => 0,
+        }
+    }
+}
+
+impl<T> Clone for Link<T> {
+    fn clone(&self) -> Self {
+        let state = lock(&self.state);
+
+        Link {
+            len: self.len,
+            state: Mutex::new(LinkState {
+                record: state.record,
+                dirty: state.dirty,
+                node: state.node.clone(),
+                last_used: state.last_used,
+            }),
+        }
+    }
+}
+
+impl<S: NodeStore> Cache<S> {
+    fn new(store: S) -> Self {
+        Cache {
+            budget: store.cache_budget(),
+            store,
+            loaded: AtomicUsize::new(0),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Advances the clock ordering the uses of leaves. Readers racing
+    /// with each other may observe the same time, which only makes
+    /// the order of evictions less precise.
+    #[inline(always)]
+    fn tick(&self) -> u64 {
+        let now = self.clock.load(Ordering::Relaxed) + 1;
+        self.clock.store(now, Ordering::Relaxed);
+        now
+    }
+
+    /// Reads the node of the given height and length from the store.
+    fn read<T: Element>(&self, record: RecordId, height: usize, len: usize) -> io::Result<Node<T>> {
+        let bytes = self.store.read(record)?;
+        let mut input = &bytes[..];
+
+        let tag = u8::decode(&mut input);
+        let count = usize::decode(&mut input)
+            .filter(|count| *count > 0 && *count <= BRANCH_FACTOR)
+            .ok_or_else(|| malformed(record))?;
+
+        let node = match tag {
+            Some(LEAF) if height == 0 && count == len => {
+                let mut items = Vec::with_capacity(count);
+                for _ in 0..count {
+                    items.push(T::decode(&mut input).ok_or_else(|| malformed(record))?);
+                }
+
+                Node::Leaf(SharedPtr::new(items))
+            }
+            Some(BRANCH) if height > 0 => {
+                let mut children = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let child = u64::decode(&mut input).ok_or_else(|| malformed(record))?;
+                    let child_len = usize::decode(&mut input)
+                        .filter(|len| *len > 0)
+                        .ok_or_else(|| malformed(record))?;
+
+                    children.push(Link::stored(RecordId(child), child_len));
+                }
+
+                let total = children
+                    .iter()
+                    .try_fold(0usize, |total, child| total.checked_add(child.len));
+
+                if total != Some(len) {
+                    return Err(malformed(record));
+                }
+
+                Node::Branch(SharedPtr::new(children))
+            }
+            _ => return Err(malformed(record)),
+        };
+
+        if !input.is_empty() {
+            return Err(malformed(record));
+        }
+
+        Ok(node)
+    }
+
+    /// Reads the node of the link from the store unless it is loaded,
+    /// and marks the leaves as used.
+    fn fetch<T: Element>(
+        &self,
+        state: &mut LinkState<T>,
+        height: usize,
+        len: usize,
+    ) -> io::Result<()> {
+        if state.node.is_none() {
+            let record = state
+                .record
+                .expect("node has neither contents nor a record");
+
+            let node = self.read(record, height, len).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("failed to read the node {:?}: {}", record, err),
+                )
+            })?;
+
+            if let Node::Leaf(..) = node {
+                self.loaded.fetch_add(1, Ordering::Relaxed);
+            }
+
+            state.node = Some(node);
+        }
+
+        if let Some(Node::Leaf(..)) = state.node {
+            state.last_used = self.tick();
+        }
+
+        Ok(())
+    }
+
+    fn load<T: Element>(&self, link: &Link<T>, height: usize) -> io::Result<Node<T>> {
+        let mut state = lock(&link.state);
+        self.fetch(&mut state, height, link.len)?;
+        Ok(state.node.clone().unwrap())
+    }
+
+    fn load_branch<T: Element>(
+        &self,
+        link: &Link<T>,
+        height: usize,
+    ) -> io::Result<SharedPtr<Vec<Link<T>>>> {
+        match self.load(link, height)? {
+            Node::Branch(children) => Ok(children),
+            Node::Leaf(..) => unreachable!(),
+        }
+    }
+
+    /// Loads the node of the link and marks it as modified.
+    /// Panics if the node can not be read.
+    fn node_mut<'a, T: Element>(&self, link: &'a mut Link<T>, height: usize) -> &'a mut Node<T> {
+        let len = link.len;
+        let state = link.state_mut();
+
+        or_panic(self.fetch(state, height, len));
+        state.dirty = true;
+        state.node.as_mut().unwrap()
+    }
+
+    fn leaf_mut<'a, T: Clone + Element>(&self, link: &'a mut Link<T>) -> &'a mut Vec<T> {
+        match *self.node_mut(link, 0) {
+            Node::Leaf(ref mut items) => SharedPtr::make_mut(items),
+            Node::Branch(..) => unreachable!(),
+        }
+    }
+
+    fn branch_mut<'a, T: Clone + Element>(
+        &self,
+        link: &'a mut Link<T>,
+        height: usize,
+    ) -> &'a mut Vec<Link<T>> {
+        match *self.node_mut(link, height) {
+            Node::Branch(ref mut children) => SharedPtr::make_mut(children),
+            Node::Leaf(..) => unreachable!(),
+        }
+    }
+
+    fn new_leaf<T>(&self, items: Vec<T>) -> Link<T> {
+        self.loaded.fetch_add(1, Ordering::Relaxed);
+        Link::new(items.len(), Node::Leaf(SharedPtr::new(items)), self.tick())
+    }
+
+    fn new_branch<T>(&self, children: Vec<Link<T>>) -> Link<T> {
+        let len = children.iter().map(|child| child.len).sum();
+        Link::new(len, Node::Branch(SharedPtr::new(children)), 0)
+    }
+
+    /// Writes the nodes of the subtree which are not in the store yet or
+    /// have been modified, children before their parents, and returns the
+    /// record of the root. The other nodes keep their records.
+    fn save<T: Element>(&self, link: &Link<T>) -> io::Result<RecordId> {
+        let node = {
+            let state = lock(&link.state);
+
+            match state.record {
+                Some(record) if !state.dirty => return Ok(record),
+                _ => state
+                    .node
+                    .clone()
+                    .expect("node has neither contents nor a record"),
+            }
+        };
+
+        let mut bytes = Vec::new();
+
+        match node {
+            Node::Leaf(ref items) => {
+                bytes.push(LEAF);
+                items.len().encode(&mut bytes);
+
+                for item in items.iter() {
+                    item.encode(&mut bytes);
+                }
+            }
+            Node::Branch(ref children) => {
+                bytes.push(BRANCH);
+                children.len().encode(&mut bytes);
+
+                for child in children.iter() {
+                    self.save(child)?.0.encode(&mut bytes);
+                    child.len.encode(&mut bytes);
+                }
+            }
+        }
+
+        let record = self.store.write(&bytes)?;
+        let mut state = lock(&link.state);
+
+        state.record = Some(record);
+        state.dirty = false;
+        Ok(record)
+    }
+
+    /// Unloads the leaves of the subtree last used before the threshold,
+    /// and the branches left without loaded children, once they are in
+    /// the store. Returns the number of leaves which stay loaded.
+    fn evict<T: Element>(&self, link: &Link<T>, threshold: u64) -> usize {
+        let (node, last_used) = {
+            let state = lock(&link.state);
+
+            match state.node {
+                Some(ref node) => (node.clone(), state.last_used),
+                None => return 0,
+            }
+        };
+
+        let loaded = match node {
+            Node::Leaf(..) => usize::from(last_used >= threshold),
+            Node::Branch(ref children) => children
+                .iter()
+                .map(|child| self.evict(child, threshold))
+                .sum(),
+        };
+
+        if loaded > 0 {
+            return loaded;
+        }
+
+        // Nodes which can not be written stay loaded.
+        if self.save(link).is_err() {
+            return usize::from(matches!(node, Node::Leaf(..)));
+        }
+
+        lock(&link.state).node = None;
+        0
+    }
+
+    /// Adds the element to the last leaf of the subtree,
+    /// or returns it back if the leaf is full.
+    fn push_last<T: Clone + Element>(
+        &self,
+        link: &mut Link<T>,
+        height: usize,
+        item: T,
+    ) -> Result<(), T> {
+        if height == 0 {
+            if link.len == BRANCH_FACTOR {
+                return Err(item);
+            }
+
+            self.leaf_mut(link).push(item);
+        } else {
+            let children = self.branch_mut(link, height);
+            self.push_last(children.last_mut().unwrap(), height - 1, item)?;
+        }
+
+        link.len += 1;
+        Ok(())
+    }
+
+    /// Removes the last element of the subtree, along with the nodes left empty.
+    fn pop_last<T: Clone + Element>(&self, link: &mut Link<T>, height: usize) -> T {
+        let item = if height == 0 {
+            self.leaf_mut(link).pop().unwrap()
+        } else {
+            let children = self.branch_mut(link, height);
+            let item = self.pop_last(children.last_mut().unwrap(), height - 1);
+
+            if children.last().map_or(false, |child| child.len == 0) {
+                children.pop();
+            }
+
+            item
+        };
+
+        link.len -= 1;
+        item
+    }
+
+    /// Removes the last leaf of the subtree, along with
+    /// the nodes left empty, and returns its elements.
+    fn pop_last_leaf<T: Clone + Element>(&self, link: &mut Link<T>, height: usize) -> Vec<T> {
+        let items = if height == 0 {
+            mem::take(self.leaf_mut(link))
+        } else {
+            let children = self.branch_mut(link, height);
+            let items = self.pop_last_leaf(children.last_mut().unwrap(), height - 1);
+
+            if children.last().map_or(false, |child| child.len == 0) {
+                children.pop();
+            }
+
+            items
+        };
+
+        link.len -= items.len();
+        items
+    }
+
+    /// Inserts the elements in front of the first leaf of the subtree.
+    fn prepend<T: Clone + Element>(&self, link: &mut Link<T>, height: usize, items: Vec<T>) {
+        let len = items.len();
+
+        if height == 0 {
+            self.leaf_mut(link).splice(0..0, items);
+        } else {
+            let children = self.branch_mut(link, height);
+            self.prepend(&mut children[0], height - 1, items);
+        }
+
+        link.len += len;
+    }
+
+    /// Inserts the subtree at the last or the first edge of the node, which
+    /// must be higher than the subtree. Returns a sibling of the node holding
+    /// the subtree if the node has no room left for it.
+    fn insert_at_edge<T: Clone + Element>(
+        &self,
+        link: &mut Link<T>,
+        height: usize,
+        sub: Link<T>,
+        sub_height: usize,
+        last: bool,
+    ) -> Option<Link<T>> {
+        let children = self.branch_mut(link, height);
+
+        let overflow = if height == sub_height + 1 {
+            Some(sub)
+        } else {
+            let edge = if last {
+                children.last_mut()
+            } else {
+                children.first_mut()
+            };
+
+            self.insert_at_edge(edge.unwrap(), height - 1, sub, sub_height, last)
+        };
+
+        let sibling = match overflow {
+            Some(child) if children.len() < BRANCH_FACTOR => {
+                if last {
+                    children.push(child);
+                } else {
+                    children.insert(0, child);
+                }
+
+                None
+            }
+            Some(child) => Some(self.new_branch(vec![child])),
+            None => None,
+        };
+
+        let len = children.iter().map(|child| child.len).sum();
+        link.len = len;
+        sibling
+    }
+
+    /// Splits the subtree at the given positions, which must be sorted and
+    /// must lie strictly inside of it. Children which are not cut are moved
+    /// into the pieces along with their records.
+    fn split<T: Clone + Element>(
+        &self,
+        mut link: Link<T>,
+        height: usize,
+        cuts: &[usize],
+    ) -> Vec<Link<T>> {
+        if height == 0 {
+            let items = self.leaf_mut(&mut link);
+
+            let mut pieces: Vec<Vec<T>> =
+                cuts.iter().rev().map(|cut| items.split_off(*cut)).collect();
+            pieces.push(mem::take(items));
+
+            return pieces
+                .into_iter()
+                .rev()
+                .map(|items| self.new_leaf(items))
+                .collect();
+        }
+
+        let children = mem::take(self.branch_mut(&mut link, height));
+
+        let mut pieces = Vec::with_capacity(cuts.len() + 1);
+        let mut current = Vec::new();
+        let mut cuts = cuts.iter().cloned().peekable();
+        let mut start = 0;
+
+        for child in children {
+            let end = start + child.len;
+
+            if cuts.next_if_eq(&start).is_some() {
+                pieces.push(mem::take(&mut current));
+            }
+
+            let inner: Vec<usize> = iter::from_fn(|| cuts.next_if(|cut| *cut < end))
+                .map(|cut| cut - start)
+                .collect();
+
+            if inner.is_empty() {
+                current.push(child);
+            } else {
+                let mut parts = self.split(child, height - 1, &inner).into_iter();
+                current.extend(parts.next());
+
+                for part in parts {
+                    pieces.push(mem::replace(&mut current, vec![part]));
+                }
+            }
+
+            start = end;
+        }
+
+        pieces.push(current);
+        pieces
+            .into_iter()
+            .map(|children| self.new_branch(children))
+            .collect()
+    }
+
+    /// Loads the subtree and forgets the records of its nodes,
+    /// so that they are written into another store.
+    fn detach<T: Clone + Element>(&self, link: &mut Link<T>, height: usize) {
+        if height == 0 {
+            self.node_mut(link, 0);
+        } else {
+            for child in self.branch_mut(link, height).iter_mut() {
+                self.detach(child, height - 1);
+            }
+        }
+
+        link.state_mut().record = None;
+    }
+}
+
+impl<S: Clone> Clone for Cache<S> {
+    fn clone(&self) -> Self {
+        Cache {
+            store: self.store.clone(),
+            budget: self.budget,
+            loaded: AtomicUsize::new(self.loaded.load(Ordering::Relaxed)),
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore + Default> Default for DiskRrbVec<T, S> {
+    fn default() -> Self {
+        DiskRrbVec::new()
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore + Default> DiskRrbVec<T, S> {
+    /// Constructs a new, empty vector in a new store.
+    pub fn new() -> Self {
+        DiskRrbVec::with_store(S::default())
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore> DiskRrbVec<T, S> {
+    /// Constructs a new, empty vector, which writes its nodes into the store.
+    pub fn with_store(store: S) -> Self {
+        DiskRrbVec {
+            root: None,
+            height: 0,
+            cache: Cache::new(store),
+        }
+    }
+
+    /// Reopens the vector from the record returned by
+    /// [flush](crate::core::disk::DiskRrbVec::flush).
+    /// No nodes are loaded until they are accessed.
+    pub fn open(store: S, root: RecordId) -> io::Result<Self> {
+        let bytes = store.read(root)?;
+        let mut input = &bytes[..];
+
+        let mut vec = DiskRrbVec::with_store(store);
+
+        match u8::decode(&mut input) {
+            Some(EMPTY) => {}
+            Some(ROOTED) => {
+                let record = u64::decode(&mut input).ok_or_else(|| malformed(root))?;
+                let len = usize::decode(&mut input)
+                    .filter(|len| *len > 0)
+                    .ok_or_else(|| malformed(root))?;
+                let height = usize::decode(&mut input).ok_or_else(|| malformed(root))?;
+
+                vec.root = Some(Link::stored(RecordId(record), len));
+                vec.height = height;
+            }
+            _ => return Err(malformed(root)),
+        }
+
+        if !input.is_empty() {
+            return Err(malformed(root));
+        }
+
+        Ok(vec)
+    }
+
+    /// Writes the nodes which are not in the store yet or have been modified,
+    /// followed by a header pointing to the root, and returns the id of the
+    /// header, which reopens the vector with
+    /// [open](crate::core::disk::DiskRrbVec::open). Nodes stay loaded.
+    pub fn flush(&mut self) -> io::Result<RecordId> {
+        let mut header = Vec::new();
+
+        match self.root {
+            Some(ref root) => {
+                header.push(ROOTED);
+                self.cache.save(root)?.0.encode(&mut header);
+                root.len.encode(&mut header);
+                self.height.encode(&mut header);
+            }
+            None => header.push(EMPTY),
+        }
+
+        let id = self.cache.store.write(&header)?;
+        self.cache.store.sync()?;
+
+        Ok(id)
+    }
+
+    /// Returns the store of the vector.
+    pub fn store(&self) -> &S {
+        &self.cache.store
+    }
+
+    /// Returns the number of leaves the vector keeps in memory.
+    pub fn cache_budget(&self) -> usize {
+        self.cache.budget
+    }
+
+    /// Sets the number of leaves the vector keeps in memory.
+    /// Leaves which can not be written into the store stay loaded.
+    pub fn set_cache_budget(&mut self, budget: usize) {
+        self.cache.budget = budget;
+        self.evict_if_needed();
+    }
+
+    /// Returns the number of leaves which are currently loaded.
+    pub fn loaded_leaves(&self) -> usize {
+        self.root.as_ref().map_or(0, Link::loaded_leaves)
+    }
+
+    /// Adds an element to the back of a collection.
+    /// Panics if a node can not be read from the store.
+    pub fn push(&mut self, item: T) {
+        self.evict_if_needed();
+
+        let overflow = match self.root {
+            Some(ref mut root) => self.cache.push_last(root, self.height, item).err(),
+            None => Some(item),
+        };
+
+        if let Some(item) = overflow {
+            let mut items = Vec::with_capacity(BRANCH_FACTOR);
+            items.push(item);
+
+            let leaf = self.cache.new_leaf(items);
+            self.insert_edge(leaf, 0, true);
+        }
+    }
+
+    /// Removes the last element from a vector and returns it, or None
+    /// if it is empty. Panics if a node can not be read from the store.
+    pub fn pop(&mut self) -> Option<T> {
+        self.evict_if_needed();
+
+        let item = self.cache.pop_last(self.root.as_mut()?, self.height);
+        self.collapse_root();
+
+        Some(item)
+    }
+
+    /// Returns a reference to an element at the given position or None
+    /// if out of bounds. Loads the nodes on the path to the element if
+    /// necessary, and evicts others once the cache budget is exceeded.
+    /// Panics if a node can not be read from the store.
+    pub fn get(&self, index: usize) -> Option<DiskRef<T>> {
+        or_panic(self.try_get(index))
+    }
+
+    /// Returns a reference to an element at the given position or None if
+    /// out of bounds, or an error if a node can not be read from the store.
+    pub fn try_get(&self, index: usize) -> io::Result<Option<DiskRef<T>>> {
+        let element = self
+            .leaf_at(index)?
+            .map(|(leaf, offset)| DiskRef { leaf, offset });
+
+        Ok(element)
+    }
+
+    /// Returns a mutable reference to an element at the given
+    /// position or None if out of bounds. The nodes on the path
+    /// to the element are written into new records when they
+    /// are evicted or flushed. Panics if a node can not be read
+    /// from the store.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len() {
+            return None;
+        }
+
+        self.evict_if_needed();
+
+        let cache = &self.cache;
+        let mut link = self.root.as_mut().unwrap();
+        let mut height = self.height;
+        let mut index = index;
+
+        while height > 0 {
+            let len = link.len;
+            let children = cache.branch_mut(link, height);
+            let (child, offset) = find_child(children, len, height, index);
+
+            link = &mut children[child];
+            height -= 1;
+            index = offset;
+        }
+
+        cache.leaf_mut(link).get_mut(index)
+    }
+
+    /// Returns the number of elements in the vector.
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.len)
+    }
+
+    /// Returns true if the vector has a length of 0.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits the collection into two at the given index.
+    ///
+    /// Returns a vector containing the elements in the range [at, len).
+    /// After the call, the original vector will be left containing the
+    /// elements [0, at). Panics if a node can not be read from the store.
+    pub fn split_off(&mut self, mid: usize) -> Self {
+        assert!(mid <= self.len());
+
+        let empty = self.empty_like();
+        let mut pieces = mem::replace(self, empty).split_at_many(&[mid]).into_iter();
+
+        *self = pieces.next().unwrap();
+        pieces.next().unwrap()
+    }
+
+    /// Splits the vector into `indices.len() + 1` pieces at the given
+    /// positions, which must be sorted and must not exceed the length.
+    /// The nodes which are not cut are shared with the pieces. Panics
+    /// if a node can not be read from the store.
+    pub fn split_at_many(mut self, indices: &[usize]) -> Vec<Self> {
+        self.evict_if_needed();
+
+        let len = self.len();
+        let ranges = split_ranges(indices, len);
+
+        let mut cuts: Vec<usize> = ranges[1..]
+            .iter()
+            .map(|range| range.start)
+            .filter(|cut| *cut > 0 && *cut < len)
+            .collect();
+        cuts.dedup();
+
+        let height = self.height;
+        let mut links = match self.root.take() {
+            Some(root) if !cuts.is_empty() => self.cache.split(root, height, &cuts),
+            root => root.into_iter().collect(),
+        }
+        .into_iter();
+
+        ranges
+            .iter()
+            .map(|range| {
+                let mut piece = self.empty_like();
+
+                if !range.is_empty() {
+                    piece.root = links.next();
+                    piece.height = height;
+                    piece.collapse_root();
+                }
+
+                piece
+            })
+            .collect()
+    }
+
+    /// Moves all the elements of `that` into `self`, leaving `that` empty.
+    /// If the vectors use different stores, the nodes of `that` are loaded
+    /// first, and are written into the store of `self` once evicted.
+    /// Panics if a node can not be read from the store.
+    pub fn append(&mut self, that: &mut Self) {
+        if that.is_empty() {
+            return;
+        }
+
+        self.evict_if_needed();
+
+        if !self.cache.store.same_store(&that.cache.store) {
+            that.detach();
+        }
+
+        *self.cache.loaded.get_mut() += mem::take(that.cache.loaded.get_mut());
+
+        // Adjacent leaves are merged if they fit into one,
+        // so that alternating splits and appends do not
+        // fragment the vector into tiny leaves.
+        if !self.is_empty() && self.edge_leaf_len(true) + that.edge_leaf_len(false) <= BRANCH_FACTOR
+        {
+            let items = self
+                .cache
+                .pop_last_leaf(self.root.as_mut().unwrap(), self.height);
+            self.collapse_root();
+
+            that.cache
+                .prepend(that.root.as_mut().unwrap(), that.height, items);
+        }
+
+        let right = that.root.take().unwrap();
+        let right_height = mem::take(&mut that.height);
+
+        if self.root.is_none() || self.height >= right_height {
+            self.insert_edge(right, right_height, true);
+        } else {
+            let left = self.root.take().unwrap();
+            let left_height = mem::replace(&mut self.height, right_height);
+
+            self.root = Some(right);
+            self.insert_edge(left, left_height, false);
+        }
+    }
+
+    /// Returns an iterator over references to the elements, which
+    /// panics if a node can not be read from the store.
+    pub fn iter(&self) -> Iter<'_, T, S> {
+        Iter {
+            inner: self.try_iter(),
+        }
+    }
+
+    /// Returns an iterator over references to the elements, which
+    /// returns the errors of reading the nodes from the store.
+    pub fn try_iter(&self) -> TryIter<'_, T, S> {
+        TryIter {
+            vec: self,
+            leaf: None,
+            offset: 0,
+            index: 0,
+        }
+    }
+
+    fn empty_like(&self) -> Self {
+        DiskRrbVec {
+            root: None,
+            height: 0,
+            cache: self.cache.clone(),
+        }
+    }
+
+    /// Returns the leaf holding the element at the given index along with
+    /// the position of the element in it, loading the nodes on the path.
+    fn leaf_at(&self, index: usize) -> io::Result<Option<(SharedPtr<Vec<T>>, usize)>> {
+        let root = match self.root {
+            Some(ref root) if index < root.len => root,
+            _ => return Ok(None),
+        };
+
+        let mut node = self.cache.load(root, self.height)?;
+        let mut height = self.height;
+        let mut len = root.len;
+        let mut index = index;
+
+        let leaf = loop {
+            let children = match node {
+                Node::Leaf(items) => break items,
+                Node::Branch(children) => children,
+            };
+
+            let (child, offset) = find_child(&children, len, height, index);
+
+            height -= 1;
+            len = children[child].len;
+            index = offset;
+            node = self.cache.load(&children[child], height)?;
+        };
+
+        self.evict_if_needed();
+        Ok(Some((leaf, index)))
+    }
+
+    /// Returns the length of the last or the first leaf.
+    fn edge_leaf_len(&self, last: bool) -> usize {
+        let mut link = self.root.clone().unwrap();
+
+        for height in (1..=self.height).rev() {
+            let children = or_panic(self.cache.load_branch(&link, height));
+            let edge = if last { children.len() - 1 } else { 0 };
+
+            link = children[edge].clone();
+        }
+
+        link.len
+    }
+
+    /// Inserts the subtree, which must not be higher than the tree,
+    /// at its last or first edge, adding a level on overflow.
+    fn insert_edge(&mut self, sub: Link<T>, sub_height: usize, last: bool) {
+        let root = match self.root {
+            Some(ref mut root) => root,
+            None => {
+                self.root = Some(sub);
+                self.height = sub_height;
+                return;
+            }
+        };
+
+        let overflow = if sub_height == self.height {
+            Some(sub)
+        } else {
+            self.cache
+                .insert_at_edge(root, self.height, sub, sub_height, last)
+        };
+
+        if let Some(sibling) = overflow {
+            let root = self.root.take().unwrap();
+
+            let children = if last {
+                vec![root, sibling]
+            } else {
+                vec![sibling, root]
+            };
+
+            self.root = Some(self.cache.new_branch(children));
+            self.height += 1;
+        }
+    }
+
+    /// Drops the root once the vector is empty, and the roots which
+    /// have a single child, so that the tree is no higher than needed.
+    fn collapse_root(&mut self) {
+        while let Some(ref root) = self.root {
+            if root.len == 0 {
+                self.root = None;
+                self.height = 0;
+                return;
+            }
+
+            if self.height == 0 {
+                return;
+            }
+
+            let children = or_panic(self.cache.load_branch(root, self.height));
+            if children.len() > 1 {
+                return;
+            }
+
+            self.root = Some(children[0].clone());
+            self.height -= 1;
+        }
+    }
+
+    /// Loads all nodes and forgets their records,
+    /// so that they are written into another store.
+    fn detach(&mut self) {
+        if let Some(ref mut root) = self.root {
+            self.cache.detach(root, self.height);
+        }
+    }
+
+    /// Takes the elements of the leaf holding the element at the given index
+    /// out of the tree, which must not access the leaf afterwards. Elements
+    /// are moved unless the leaf is shared with another vector.
+    fn take_leaf(&mut self, index: usize) -> Vec<T> {
+        let cache = &self.cache;
+        let mut link = self.root.as_mut().unwrap();
+        let mut height = self.height;
+        let mut index = index;
+
+        while height > 0 {
+            let len = link.len;
+            let children = cache.branch_mut(link, height);
+            let (child, offset) = find_child(children, len, height, index);
+
+            link = &mut children[child];
+            height -= 1;
+            index = offset;
+        }
+
+        let len = link.len;
+        let state = link.state_mut();
+        or_panic(cache.fetch(state, 0, len));
+
+        match state.node.take() {
+            Some(Node::Leaf(items)) => items.take(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Evicts the least recently used leaves once the number of loaded
+    /// leaves exceeds the budget, leaving half of the budget loaded,
+    /// along with the branches which are left without loaded children.
+    fn evict_if_needed(&self) {
+        if self.cache.loaded.load(Ordering::Relaxed) <= self.cache.budget {
+            return;
+        }
+
+        let root = match self.root {
+            Some(ref root) => root,
+            None => return,
+        };
+
+        let mut stamps = Vec::new();
+        root.collect_stamps(&mut stamps);
+
+        let mut remaining = stamps.len();
+
+        if remaining > self.cache.budget {
+            stamps.sort_unstable();
+
+            let evicted = stamps.len() - self.cache.budget / 2;
+            let threshold = stamps.get(evicted).copied().unwrap_or(u64::MAX);
+
+            remaining = self.cache.evict(root, threshold);
+        }
+
+        self.cache.loaded.store(remaining, Ordering::Relaxed);
+    }
+}
+
+impl_split_vec!(balanced [T: Clone + Debug + Element, S: NodeStore + Default] DiskRrbVec<T, S>);
+
+impl<T: Clone + Debug, S: Clone> Clone for DiskRrbVec<T, S> {
+    fn clone(&self) -> Self {
+        DiskRrbVec {
+            root: self.root.clone(),
+            height: self.height,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore> Debug for DiskRrbVec<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Clone + Debug + Element + PartialEq, S: NodeStore> PartialEq for DiskRrbVec<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| *a == *b)
+    }
+}
+
+impl<T: Clone + Debug + Element + Eq, S: NodeStore> Eq for DiskRrbVec<T, S> {}
+
+impl<T: Clone + Debug + Element, S: NodeStore + Default> FromIterator<T> for DiskRrbVec<T, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = DiskRrbVec::new();
+        for i in iter {
+            vec.push(i);
+        }
+        vec
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore + Default> Sum<DiskRrbVec<T, S>>
+    for DiskRrbVec<T, S>
+{
+    fn sum<I: Iterator<Item = DiskRrbVec<T, S>>>(iter: I) -> Self {
+        DiskRrbVec::concat(iter)
+    }
+}
+
+impl<'a, T: 'a + Clone + Debug + Element, S: 'a + NodeStore + Default> Sum<&'a DiskRrbVec<T, S>>
+    for DiskRrbVec<T, S>
+{
+    fn sum<I: Iterator<Item = &'a DiskRrbVec<T, S>>>(iter: I) -> Self {
+        DiskRrbVec::concat(iter.cloned())
+    }
+}
+
+impl<T> ops::Deref for DiskRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.leaf[self.offset]
+    }
+}
+
+impl<T> Clone for DiskRef<T> {
+    fn clone(&self) -> Self {
+        DiskRef {
+            leaf: self.leaf.clone(),
+            offset: self.offset,
+        }
+    }
+}
+
+impl<T: Debug> Debug for DiskRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<'a, T: Clone + Debug + Element, S: NodeStore> Iterator for TryIter<'a, T, S> {
+    type Item = io::Result<DiskRef<T>>;
+
+    fn next(&mut self) -> Option<io::Result<DiskRef<T>>> {
+        let exhausted = match self.leaf {
+            Some(ref leaf) => self.offset == leaf.len(),
+            None => true,
+        };
+
+        if exhausted {
+            // The current leaf is released before the next one is
+            // loaded, so that it can be dropped by the eviction.
+            self.leaf = None;
+
+            match self.vec.leaf_at(self.index) {
+                Ok(Some((leaf, offset))) => {
+                    self.leaf = Some(leaf);
+                    self.offset = offset;
+                }
+                Ok(None) => return None,
+                Err(err) => {
+                    self.index += 1;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        let item = DiskRef {
+            leaf: self.leaf.clone().unwrap(),
+            offset: self.offset,
+        };
+
+        self.offset += 1;
+        self.index += 1;
+
+        Some(Ok(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vec.len() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T: Clone + Debug + Element, S: NodeStore> ExactSizeIterator for TryIter<'a, T, S> {}
+
+impl<'a, T: Clone + Debug + Element, S: NodeStore> Iterator for Iter<'a, T, S> {
+    type Item = DiskRef<T>;
+
+    fn next(&mut self) -> Option<DiskRef<T>> {
+        self.inner.next().map(or_panic)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T: Clone + Debug + Element, S: NodeStore> ExactSizeIterator for Iter<'a, T, S> {}
+
+impl<'a, T: Clone + Debug + Element, S: NodeStore> IntoIterator for &'a DiskRrbVec<T, S> {
+    type Item = DiskRef<T>;
+    type IntoIter = Iter<'a, T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore> Iterator for IntoIter<T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) = self.head_chunk.next() {
+                self.len -= 1;
+                return Some(item);
+            }
+
+            if self.head_index == self.tail_index {
+                let item = self.tail_chunk.next()?;
+                self.len -= 1;
+                return Some(item);
+            }
+
+            let items = self.vec.take_leaf(self.head_index);
+
+            self.head_index += items.len();
+            self.head_chunk = items.into_iter();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore> DoubleEndedIterator for IntoIter<T, S> {
+    fn next_back(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) = self.tail_chunk.next_back() {
+                self.len -= 1;
+                return Some(item);
+            }
+
+            if self.head_index == self.tail_index {
+                let item = self.head_chunk.next_back()?;
+                self.len -= 1;
+                return Some(item);
+            }
+
+            let items = self.vec.take_leaf(self.tail_index - 1);
+
+            self.tail_index -= items.len();
+            self.tail_chunk = items.into_iter();
+        }
+    }
+}
+
+impl<T: Clone + Debug + Element, S: NodeStore> ExactSizeIterator for IntoIter<T, S> {}
+
+impl<T: Clone + Debug + Element, S: NodeStore> IntoIterator for DiskRrbVec<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            len: self.len(),
+            head_index: 0,
+            head_chunk: Vec::new().into_iter(),
+            tail_index: self.len(),
+            tail_chunk: Vec::new().into_iter(),
+            vec: self,
+        }
+    }
+}
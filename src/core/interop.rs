@@ -0,0 +1,89 @@
+extern crate im;
+extern crate im_rc;
+
+use super::RrbVec;
+use std::fmt::Debug;
+
+impl<T: Clone + Debug> RrbVec<T> {
+    /// Calls the function with the leaves of the vector in order.
+    fn for_each_leaf<F: FnMut(&[Option<T>])>(&self, mut f: F) {
+        let mut index = 0;
+
+        while index < self.tree.len() {
+            let chunk = self.tree.get_chunk(index);
+            index += chunk.len();
+
+            f(chunk);
+        }
+
+        f(&self.tail[..self.tail_len]);
+    }
+
+    /// Calls the function with the leaves of the vector in order. Leaves
+    /// which are not shared with other vectors are moved out of the tree,
+    /// while the shared ones are cloned, so their elements can be taken.
+    fn into_each_leaf<F: FnMut(&mut [Option<T>])>(self, mut f: F) {
+        for (mut elements, len) in self.tree {
+            f(&mut elements[..len]);
+        }
+
+        let mut tail = self.tail;
+        f(&mut tail[..self.tail_len]);
+    }
+}
+
+// The trees of the libraries are laid out differently, so nodes can not be
+// shared. Instead, the vectors are read a leaf at a time, which avoids
+// walking the tree from the root for every element. The chunks of im are
+// filled through push_back, which copies the elements of a leaf into the
+// buffer at the back of the vector and links it into the tree once it is
+// full, whereas appending a vector per leaf rebalances the tree every time.
+macro_rules! impl_interop {
+    ($im:ident) => {
+        impl<'a, T: Clone + Debug> From<&'a $im::Vector<T>> for RrbVec<T> {
+            fn from(vector: &$im::Vector<T>) -> Self {
+                let mut vec = RrbVec::new();
+
+                for leaf in vector.leaves() {
+                    vec.extend_from_slice(leaf);
+                }
+
+                vec
+            }
+        }
+
+        impl<T: Clone + Debug> From<$im::Vector<T>> for RrbVec<T> {
+            fn from(vector: $im::Vector<T>) -> Self {
+                // elements are moved out of the chunks which are not shared
+                vector.into_iter().collect()
+            }
+        }
+
+        impl<'a, T: Clone + Debug> From<&'a RrbVec<T>> for $im::Vector<T> {
+            fn from(vec: &RrbVec<T>) -> Self {
+                let mut vector = $im::Vector::new();
+
+                vec.for_each_leaf(|leaf| {
+                    vector.extend(leaf.iter().map(|item| item.as_ref().unwrap().clone()))
+                });
+
+                vector
+            }
+        }
+
+        impl<T: Clone + Debug> From<RrbVec<T>> for $im::Vector<T> {
+            fn from(vec: RrbVec<T>) -> Self {
+                let mut vector = $im::Vector::new();
+
+                vec.into_each_leaf(|leaf| {
+                    vector.extend(leaf.iter_mut().map(|item| item.take().unwrap()))
+                });
+
+                vector
+            }
+        }
+    };
+}
+
+impl_interop!(im);
+impl_interop!(im_rc);
@@ -7,8 +7,8 @@ use super::RbVec;
 use super::RrbVec;
 use crate::core::rrbtree::iter::RrbTreeIter;
 use crate::core::rrbtree::BRANCH_FACTOR;
-use std::fmt::Debug;
-use std::iter::FromIterator;
+use core::fmt::Debug;
+use core::iter::{FromIterator, Sum};
 
 #[cfg(all(feature = "arc", feature = "rayon_iter"))]
 use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
@@ -19,7 +19,7 @@ use rayon::prelude::{
 };
 
 macro_rules! impl_iter {
-    ($vec:ident, $iter:ident) => {
+    ($vec:ident, $iter:ident, $ref_iter:ident) => {
         /// This struct keeps state necessary to implement Iterator
         /// for the tree-based vector. It takes the ownership of the vector
         /// contents. The iterator implementation consumes the contents of
@@ -159,6 +159,68 @@ macro_rules! impl_iter {
             }
         }
 
+        /// An iterator over references to the elements of the
+        /// tree-based vector, which walks the tree a leaf at a time.
+        #[derive(Debug, Clone)]
+        pub struct $ref_iter<'a, T: Clone + Debug> {
+            vec: &'a $vec<T>,
+            chunk: &'a [Option<T>],
+            index: usize,
+        }
+
+        impl<T: Clone + Debug> $vec<T> {
+            /// Returns an iterator over references to the elements.
+            pub fn iter(&self) -> $ref_iter<'_, T> {
+                $ref_iter {
+                    vec: self,
+                    chunk: &[],
+                    index: 0,
+                }
+            }
+        }
+
+        impl<'a, T: Clone + Debug> Iterator for $ref_iter<'a, T> {
+            type Item = &'a T;
+
+            fn next(&mut self) -> Option<&'a T> {
+                if self.chunk.is_empty() {
+                    let vec = self.vec;
+                    let tree_len = vec.tree.len();
+
+                    self.chunk = if self.index < tree_len {
+                        vec.tree.get_chunk(self.index)
+                    } else if self.index < vec.len() {
+                        &vec.tail[self.index - tree_len..vec.tail_len]
+                    } else {
+                        return None;
+                    };
+                }
+
+                let (item, rest) = self.chunk.split_first().unwrap();
+
+                self.chunk = rest;
+                self.index += 1;
+
+                item.as_ref()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let len = self.vec.len() - self.index;
+                (len, Some(len))
+            }
+        }
+
+        impl<'a, T: Clone + Debug> ExactSizeIterator for $ref_iter<'a, T> {}
+
+        impl<'a, T: Clone + Debug> IntoIterator for &'a $vec<T> {
+            type Item = &'a T;
+            type IntoIter = $ref_iter<'a, T>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
         impl<T: Clone + Debug> FromIterator<T> for $vec<T> {
             fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
                 let mut vec = $vec::new();
@@ -168,11 +230,23 @@ macro_rules! impl_iter {
                 vec
             }
         }
+
+        impl<T: Clone + Debug> Sum<$vec<T>> for $vec<T> {
+            fn sum<I: Iterator<Item = $vec<T>>>(iter: I) -> Self {
+                $vec::concat(iter)
+            }
+        }
+
+        impl<'a, T: 'a + Clone + Debug> Sum<&'a $vec<T>> for $vec<T> {
+            fn sum<I: Iterator<Item = &'a $vec<T>>>(iter: I) -> Self {
+                $vec::concat(iter.cloned())
+            }
+        }
     };
 }
 
-impl_iter!(RbVec, RbVecIter);
-impl_iter!(RrbVec, RrbVecIter);
+impl_iter!(RbVec, RbVecIter, RbVecRefIter);
+impl_iter!(RrbVec, RrbVecIter, RrbVecRefIter);
 
 macro_rules! impl_into_par_iter {
     ($vec:ident, $iter:ident, $pariter:ident, $producer:ident) => {
@@ -232,11 +306,11 @@ macro_rules! impl_into_par_iter {
             type IntoIter = $iter<T>;
 
             fn into_iter(mut self) -> Self::IntoIter {
-                std::mem::replace(&mut self.vec, $vec::new()).into_iter()
+                core::mem::replace(&mut self.vec, $vec::new()).into_iter()
             }
 
             fn split_at(mut self, index: usize) -> (Self, Self) {
-                let mut vec = std::mem::replace(&mut self.vec, $vec::new());
+                let mut vec = core::mem::replace(&mut self.vec, $vec::new());
 
                 let right = vec.split_off(index);
                 let left = vec;
@@ -264,16 +338,15 @@ macro_rules! impl_into_par_iter {
             where
                 I: IntoParallelIterator<Item = T>,
             {
-                par_iter
+                let vecs: Vec<$vec<T>> = par_iter
                     .into_par_iter()
                     .fold($vec::new, |mut vec, elem| {
                         vec.push(elem);
                         vec
                     })
-                    .reduce($vec::new, |mut list1, mut list2| {
-                        list1.append(&mut list2);
-                        list1
-                    })
+                    .collect();
+
+                $vec::concat(vecs)
             }
         }
     };
@@ -0,0 +1,392 @@
+//! A module providing a persistent vector that maintains
+//! a summary of its elements for fast aggregate queries.
+
+use super::reset_on_unwind;
+use super::rrbtree::RrbTree;
+use super::rrbtree::BRANCH_FACTOR;
+use super::split_ranges;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+use core::mem;
+use core::ops;
+
+/// A monoid over the measures of elements of type `T`.
+///
+/// Every node of a [MeasuredRrbVec](crate::core::measure::MeasuredRrbVec)
+/// caches the combined summary of its subtree. `combine` has to be associative
+/// and `identity` has to be its neutral element, as the summaries are combined
+/// in an order that depends on the shape of the tree.
+pub trait Measure<T>: Clone + Debug {
+    /// The type of the summary.
+    type Summary: Clone + Debug;
+
+    /// Returns the summary of an empty sequence.
+    fn identity() -> Self::Summary;
+
+    /// Returns the summary of a single element.
+    fn measure(item: &T) -> Self::Summary;
+
+    /// Combines the summaries of two adjacent sequences.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+
+    /// Returns true if summaries carry no information, in which
+    /// case the tree does not spend time on recomputing them.
+    #[inline(always)]
+    fn is_trivial() -> bool {
+        mem::size_of::<Self::Summary>() == 0
+    }
+}
+
+impl<T> Measure<T> for () {
+    type Summary = ();
+
+    #[inline(always)]
+    fn identity() {}
+
+    #[inline(always)]
+    fn measure(_item: &T) {}
+
+    #[inline(always)]
+    fn combine(_left: &(), _right: &()) {}
+}
+
+/// A persistent vector based on the relaxed RrbTree, where every
+/// node caches the summary of its subtree with respect to the measure `M`.
+/// This allows to compute aggregates over arbitrary ranges and to search
+/// by the prefix summary in logarithmic time.
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MeasuredRrbVec<T, M: Measure<T>> {
+    tree: RrbTree<T, M>,
+    tail: [Option<T>; BRANCH_FACTOR],
+    tail_len: usize,
+}
+
+/// A mutable reference to an element of the
+/// [MeasuredRrbVec](crate::core::measure::MeasuredRrbVec). Summaries of the
+/// vector are brought up to date once the reference is dropped.
+pub struct MeasuredRefMut<'a, T: Clone + Debug, M: Measure<T>> {
+    vec: &'a mut MeasuredRrbVec<T, M>,
+    index: usize,
+}
+
+/// An iterator over references to elements of the
+/// [MeasuredRrbVec](crate::core::measure::MeasuredRrbVec).
+/// Elements are read a leaf at a time.
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: Clone + Debug, M: Measure<T>> {
+    vec: &'a MeasuredRrbVec<T, M>,
+    chunk: &'a [Option<T>],
+    index: usize,
+}
+
+impl<T: Clone + Debug, M: Measure<T>> Default for MeasuredRrbVec<T, M> {
+    fn default() -> Self {
+        MeasuredRrbVec::new()
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> MeasuredRrbVec<T, M> {
+    /// Constructs a new, empty vector.
+    /// The vector allocates a buffer equal to
+    /// the selected branching factor size.
+    pub fn new() -> Self {
+        MeasuredRrbVec {
+            tree: RrbTree::new(),
+            tail: new_branch!(),
+            tail_len: 0,
+        }
+    }
+
+    /// Constructs a vector of `n` clones of the value, sharing
+    /// the nodes of the tree, along with their summaries.
+    pub fn from_elem(value: T, n: usize) -> Self {
+        let tree = RrbTree::repeat(&value, n / BRANCH_FACTOR);
+
+        let mut tail = new_branch!();
+        let tail_len = n % BRANCH_FACTOR;
+
+        for element in tail.iter_mut().take(tail_len) {
+            *element = Some(value.clone());
+        }
+
+        MeasuredRrbVec {
+            tree,
+            tail,
+            tail_len,
+        }
+    }
+
+    /// Adds an element to the back of a collection.
+    pub fn push(&mut self, item: T) {
+        self.tail[self.tail_len] = Some(item);
+        self.tail_len += 1;
+
+        self.push_tail();
+    }
+
+    #[inline(always)]
+    fn push_tail(&mut self) {
+        if self.tail_len == BRANCH_FACTOR {
+            let tail = mem::take(&mut self.tail);
+
+            self.tree.push(tail, self.tail_len);
+            self.tail_len = 0;
+        }
+    }
+
+    /// Removes the last element from a vector and
+    /// returns it, or None if it is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        reset_on_unwind(self, |this| {
+            if this.tail_len == 0 {
+                let (new_tail, new_tail_len) = this.tree.pop();
+
+                this.tail = new_tail;
+                this.tail_len = new_tail_len;
+            }
+
+            let item = this.tail[this.tail_len - 1].take();
+            this.tail_len -= 1;
+
+            this.fill_root_leaf();
+
+            item
+        })
+    }
+
+    /// Returns a reference to an element at the given
+    /// position or None if out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if self.tree.len() > index {
+            self.tree.get(index)
+        } else {
+            self.tail.get(index - self.tree.len())?.as_ref()
+        }
+    }
+
+    /// Returns a mutable reference to an element at the given
+    /// position or None if out of bounds. The summaries are
+    /// recomputed when the returned reference is dropped.
+    pub fn get_mut(&mut self, index: usize) -> Option<MeasuredRefMut<'_, T, M>> {
+        if index < self.len() {
+            Some(MeasuredRefMut { vec: self, index })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of elements in the vector.
+    pub fn len(&self) -> usize {
+        self.tree.len() + self.tail_len
+    }
+
+    /// Returns true if the vector has a length of 0.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the summary of all elements in the vector.
+    pub fn summary(&self) -> M::Summary {
+        M::combine(&self.tree.summary(), &self.tail_summary(0, self.tail_len))
+    }
+
+    /// Returns the summary of elements in the given range.
+    /// Panics if the range is out of bounds.
+    pub fn range_summary(&self, range: ops::Range<usize>) -> M::Summary {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range {:?} is out of bounds for the length {}",
+            range,
+            self.len()
+        );
+
+        let tree_len = self.tree.len();
+
+        let tree_from = range.start.min(tree_len);
+        let tree_to = range.end.min(tree_len);
+
+        let tail_from = range.start.max(tree_len) - tree_len;
+        let tail_to = range.end.max(tree_len) - tree_len;
+
+        M::combine(
+            &self.tree.range_summary(tree_from, tree_to),
+            &self.tail_summary(tail_from, tail_to),
+        )
+    }
+
+    /// Returns the index of the first element for which the predicate holds
+    /// on the summary of all elements up to and including it, or None if
+    /// there is no such element. For example, searching with a predicate
+    /// `|sum| *sum > x` returns the first index where the prefix sum exceeds `x`.
+    /// The predicate is expected to be monotone, in the sense that once it holds
+    /// for a prefix, it holds for all prefixes extending it as well.
+    pub fn find_by_measure<P>(&self, mut predicate: P) -> Option<usize>
+    where
+        P: FnMut(&M::Summary) -> bool,
+    {
+        match self.tree.find_by_measure(M::identity(), &mut predicate) {
+            Ok(index) => Some(index),
+            Err(mut prefix) => {
+                for i in 0..self.tail_len {
+                    prefix = M::combine(&prefix, &M::measure(self.tail[i].as_ref().unwrap()));
+
+                    if predicate(&prefix) {
+                        return Some(self.tree.len() + i);
+                    }
+                }
+
+                None
+            }
+        }
+    }
+
+    /// Returns an iterator over references to the elements.
+    pub fn iter(&self) -> Iter<'_, T, M> {
+        Iter {
+            vec: self,
+            chunk: &[],
+            index: 0,
+        }
+    }
+
+    /// Combines the elements of two vectors of the same shape pairwise, see
+    /// [RrbTree::zip_with](super::rrbtree::RrbTree::zip_with) for details.
+    pub(crate) fn zip_with<F>(&self, that: &Self, reuse_shared: bool, mut f: F) -> Option<Self>
+    where
+        F: FnMut(&T, &T) -> Option<T>,
+    {
+        if self.tail_len != that.tail_len {
+            return None;
+        }
+
+        let tree = self.tree.zip_with(&that.tree, reuse_shared, &mut f)?;
+        let mut tail = new_branch!();
+
+        for (i, item) in tail.iter_mut().enumerate().take(self.tail_len) {
+            *item = Some(f(
+                self.tail[i].as_ref().unwrap(),
+                that.tail[i].as_ref().unwrap(),
+            )?);
+        }
+
+        Some(MeasuredRrbVec {
+            tree,
+            tail,
+            tail_len: self.tail_len,
+        })
+    }
+
+    #[inline(always)]
+    fn tail_summary(&self, from: usize, to: usize) -> M::Summary {
+        self.tail[from..to]
+            .iter()
+            .flatten()
+            .fold(M::identity(), |acc, item| {
+                M::combine(&acc, &M::measure(item))
+            })
+    }
+}
+
+impl_relaxed_vec!([T: Clone + Debug, M: Measure<T>] MeasuredRrbVec<T, M>);
+
+impl<'a, T: Clone + Debug, M: Measure<T>> ops::Deref for MeasuredRefMut<'a, T, M> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.vec.get(self.index).unwrap()
+    }
+}
+
+impl<'a, T: Clone + Debug, M: Measure<T>> ops::DerefMut for MeasuredRefMut<'a, T, M> {
+    fn deref_mut(&mut self) -> &mut T {
+        let vec = &mut self.vec;
+
+        if vec.tree.len() > self.index {
+            vec.tree.get_mut(self.index).unwrap()
+        } else {
+            vec.tail[self.index - vec.tree.len()].as_mut().unwrap()
+        }
+    }
+}
+
+impl<'a, T: Clone + Debug, M: Measure<T>> Drop for MeasuredRefMut<'a, T, M> {
+    fn drop(&mut self) {
+        if self.vec.tree.len() > self.index {
+            self.vec.tree.refresh_path(self.index);
+        }
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> ops::Index<usize> for MeasuredRrbVec<T, M> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "index `{}` out of bounds in MeasuredRrbVec of length `{}`",
+                index,
+                self.len()
+            )
+        })
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> FromIterator<T> for MeasuredRrbVec<T, M> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = MeasuredRrbVec::new();
+
+        for item in iter {
+            vec.push(item);
+        }
+
+        vec
+    }
+}
+
+impl<'a, T: Clone + Debug, M: Measure<T>> Iterator for Iter<'a, T, M> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.chunk.is_empty() {
+            let vec = self.vec;
+            let tree_len = vec.tree.len();
+
+            self.chunk = if self.index < tree_len {
+                vec.tree.get_chunk(self.index)
+            } else if self.index < vec.len() {
+                &vec.tail[self.index - tree_len..vec.tail_len]
+            } else {
+                return None;
+            };
+        }
+
+        let (item, rest) = self.chunk.split_first().unwrap();
+
+        self.chunk = rest;
+        self.index += 1;
+
+        item.as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vec.len() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T: Clone + Debug, M: Measure<T>> ExactSizeIterator for Iter<'a, T, M> {}
+
+impl<'a, T: Clone + Debug, M: Measure<T>> IntoIterator for &'a MeasuredRrbVec<T, M> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,344 @@
+//! A module providing content hashes of tree nodes, which allow
+//! to replicate [RrbVec](crate::core::RrbVec) by exchanging only
+//! the nodes missing on the other side.
+
+use super::rrbtree::merkle::NodeTable;
+use super::rrbtree::BRANCH_FACTOR;
+use super::RrbVec;
+use std::collections::HashSet;
+use std::error;
+use std::fmt;
+use std::fmt::Debug;
+use std::mem;
+
+const FORMAT_VERSION: u8 = 1;
+
+/// A 128-bit content hash of a tree node or of a whole vector.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeHash(u128);
+
+/// A type of elements, which can be written to and read from the
+/// byte format of the exported nodes. The encoded bytes are also
+/// the input to the content hashes of leaves.
+pub trait Element: Sized {
+    /// Appends the encoded element to the buffer.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Reads an element from the front of the input, advancing it
+    /// past the consumed bytes, or returns None if it is malformed.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+/// An error returned by [import](crate::core::merkle::MerkleStore::import).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportError {
+    /// The input is truncated or does not describe a valid tree.
+    Malformed,
+    /// The input refers to a node which is neither in
+    /// the input nor in the store importing it.
+    MissingNode(NodeHash),
+}
+
+/// A store of hashed nodes, which is used on both sides of the exchange.
+/// Hashes are computed lazily, when a vector is hashed or exported, and
+/// cached for every node. Hashed nodes are referenced weakly, thus they
+/// stay in the store while some vector holds them, and vectors keep
+/// writing to their unshared nodes in place. Nodes added by
+/// [insert](MerkleStore::insert) or [import](MerkleStore::import) are
+/// held by the store, and vectors sharing them copy them on write.
+///
+/// Hashes depend on the shape of the tree, and not only on its elements,
+/// thus vectors with the same elements might have different hashes.
+#[derive(Debug)]
+pub struct MerkleStore<T> {
+    table: NodeTable<T>,
+}
+
+/// FNV-1a, which is used for its simplicity and its stability
+/// across platforms and releases of the standard library.
+pub(crate) struct Hasher128(u128);
+
+impl Hasher128 {
+    #[inline(always)]
+    pub(crate) fn new() -> Self {
+        Hasher128(0x6c62_272e_07bb_0142_62b8_2175_6295_c58d)
+    }
+
+    #[inline(always)]
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u128;
+            self.0 = self
+                .0
+                .wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn finish(&self) -> NodeHash {
+        NodeHash(self.0)
+    }
+}
+
+impl NodeHash {
+    /// Returns the hash of the given bytes.
+    pub(crate) fn of(bytes: &[u8]) -> NodeHash {
+        let mut hasher = Hasher128::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    /// Returns the bytes of the hash in the little-endian order.
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    /// Constructs the hash from the bytes in the little-endian order.
+    pub fn from_bytes(bytes: [u8; 16]) -> NodeHash {
+        NodeHash(u128::from_le_bytes(bytes))
+    }
+}
+
+impl Debug for NodeHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NodeHash({:032x})", self.0)
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Malformed => write!(f, "malformed input"),
+            ImportError::MissingNode(hash) => write!(f, "missing node {:?}", hash),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+/// Reads the given number of bytes from the front of the input.
+#[inline(always)]
+pub(crate) fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+
+    Some(bytes)
+}
+
+macro_rules! impl_element_for_int {
+    ($($int:ident),*) => {
+        $(
+            impl Element for $int {
+                #[inline(always)]
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline(always)]
+                fn decode(input: &mut &[u8]) -> Option<Self> {
+                    let mut bytes = [0; mem::size_of::<$int>()];
+                    bytes.copy_from_slice(read_bytes(input, mem::size_of::<$int>())?);
+
+                    Some($int::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_element_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl Element for usize {
+    #[inline(always)]
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+
+    #[inline(always)]
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        u64::decode(input).map(|value| value as usize)
+    }
+}
+
+impl Element for isize {
+    #[inline(always)]
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as i64).encode(out);
+    }
+
+    #[inline(always)]
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        i64::decode(input).map(|value| value as isize)
+    }
+}
+
+impl Element for () {
+    #[inline(always)]
+    fn encode(&self, _out: &mut Vec<u8>) {}
+
+    #[inline(always)]
+    fn decode(_input: &mut &[u8]) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl Element for bool {
+    #[inline(always)]
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    #[inline(always)]
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Element for char {
+    #[inline(always)]
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out);
+    }
+
+    #[inline(always)]
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        std::char::from_u32(u32::decode(input)?)
+    }
+}
+
+impl Element for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        let bytes = read_bytes(input, len)?;
+
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl<T: Clone + Debug + Element> Default for MerkleStore<T> {
+    fn default() -> Self {
+        MerkleStore::new()
+    }
+}
+
+impl<T: Clone + Debug + Element> MerkleStore<T> {
+    /// Constructs a new, empty store.
+    pub fn new() -> Self {
+        MerkleStore {
+            table: NodeTable::new(),
+        }
+    }
+
+    /// Returns the number of distinct nodes in the store.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns true if the store holds no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the store holds a node with the given hash.
+    pub fn contains(&self, hash: NodeHash) -> bool {
+        self.table.contains(hash)
+    }
+
+    /// Returns the hashes of all nodes in the store. These are the nodes
+    /// a peer does not have to send in [export](MerkleStore::export).
+    pub fn hashes(&self) -> HashSet<NodeHash> {
+        self.table.hashes()
+    }
+
+    /// Releases all nodes held by the store.
+    pub fn clear(&mut self) {
+        self.table = NodeTable::new();
+    }
+
+    /// Returns the hash of the vector, which covers its shape and elements.
+    /// The nodes of the vector are in the store while the vector holds them.
+    pub fn hash(&mut self, vec: &RrbVec<T>) -> NodeHash {
+        let mut out = Vec::new();
+
+        self.table.encode_tree_header(&vec.tree, &mut out);
+        encode_tail(vec, &mut out);
+
+        NodeHash::of(&out)
+    }
+
+    /// Adds all nodes of the vector to the store, which holds them
+    /// until it is cleared.
+    pub fn insert(&mut self, vec: &RrbVec<T>) {
+        self.table.hold_tree(&vec.tree);
+    }
+
+    /// Exports the vector into bytes, which contain its tail and only the
+    /// nodes whose hashes are not in the `known` set, typically obtained
+    /// from [hashes](MerkleStore::hashes) of the receiving store.
+    pub fn export(&mut self, vec: &RrbVec<T>, known: &HashSet<NodeHash>) -> Vec<u8> {
+        let mut out = vec![FORMAT_VERSION];
+
+        self.table.encode_tree_header(&vec.tree, &mut out);
+        encode_tail(vec, &mut out);
+        self.table.export_nodes(&vec.tree, known, &mut out);
+
+        out
+    }
+
+    /// Reconstructs the vector from the bytes produced by
+    /// [export](MerkleStore::export), taking the nodes which are
+    /// not in the input from the store. Imported nodes are added
+    /// to the store, and shared with the returned vector.
+    pub fn import(&mut self, bytes: &[u8]) -> Result<RrbVec<T>, ImportError> {
+        let mut input = bytes;
+
+        if read_bytes(&mut input, 1) != Some(&[FORMAT_VERSION]) {
+            return Err(ImportError::Malformed);
+        }
+
+        let header = self.table.decode_tree_header(&mut input)?;
+
+        let tail_len = usize::decode(&mut input).ok_or(ImportError::Malformed)?;
+        if tail_len > BRANCH_FACTOR {
+            return Err(ImportError::Malformed);
+        }
+
+        let mut tail = new_branch!();
+        for item in tail.iter_mut().take(tail_len) {
+            *item = Some(T::decode(&mut input).ok_or(ImportError::Malformed)?);
+        }
+
+        let tree = self.table.import_nodes(header, &mut input)?;
+
+        if !input.is_empty() {
+            return Err(ImportError::Malformed);
+        }
+
+        Ok(RrbVec {
+            tree,
+            tail,
+            tail_len,
+        })
+    }
+}
+
+fn encode_tail<T: Clone + Debug + Element>(vec: &RrbVec<T>, out: &mut Vec<u8>) {
+    vec.tail_len.encode(out);
+
+    for item in vec.tail[..vec.tail_len].iter().flatten() {
+        item.encode(out);
+    }
+}
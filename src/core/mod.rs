@@ -6,11 +6,16 @@ extern crate rayon;
 #[cfg(feature = "serde_serializer")]
 extern crate serde;
 
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::fmt::Debug;
+use core::mem;
+use core::ops;
+#[cfg(feature = "std")]
+use rrbtree::visit::NodeInfo;
 use rrbtree::RrbTree;
 use rrbtree::BRANCH_FACTOR;
-use std::fmt::Debug;
-use std::mem;
-use std::ops;
 
 pub mod iter;
 mod sharedptr;
@@ -21,6 +26,15 @@ mod rrbtree;
 #[cfg(feature = "serde_serializer")]
 pub mod serializer;
 
+#[cfg(all(feature = "arc", feature = "std"))]
+pub mod atomic;
+
+#[cfg(feature = "im_interop")]
+mod interop;
+
+#[cfg(feature = "std")]
+pub(crate) use rrbtree::visit;
+
 #[cfg(not(feature = "small_branch"))]
 macro_rules! clone_arr {
     ($source:expr) => {{
@@ -75,8 +89,90 @@ macro_rules! clone_arr {
     }};
 }
 
+/// Resets the vector to an empty one when dropped, unless it is defused.
+struct ResetOnUnwind<'a, V: Default>(&'a mut V);
+
+impl<'a, V: Default> Drop for ResetOnUnwind<'a, V> {
+    fn drop(&mut self) {
+        *self.0 = V::default();
+    }
+}
+
+/// Runs the operation on the vector, which is reset to an empty one if the
+/// operation panics. Cloning or dropping an element might panic while the tree
+/// is being restructured, when the lengths, sizes and the tail are not in sync
+/// with each other, hence the elements of the vector are given up instead.
+#[inline(always)]
+pub(crate) fn reset_on_unwind<V: Default, R, F: FnOnce(&mut V) -> R>(vec: &mut V, f: F) -> R {
+    let guard = ResetOnUnwind(vec);
+    let result = f(guard.0);
+
+    mem::forget(guard);
+    result
+}
+
+/// Checks that the positions are sorted and do not exceed the length, and
+/// returns the ranges of the `indices.len() + 1` pieces they split into.
+pub(crate) fn split_ranges(indices: &[usize], len: usize) -> Vec<ops::Range<usize>> {
+    let mut ranges = Vec::with_capacity(indices.len() + 1);
+    let mut start = 0;
+
+    for &index in indices {
+        assert!(
+            start <= index && index <= len,
+            "split index `{}` is out of order or out of bounds",
+            index
+        );
+
+        ranges.push(start..index);
+        start = index;
+    }
+
+    ranges.push(start..len);
+    ranges
+}
+
+/// The number of elements printed by `Debug`, beyond which
+/// the elements in the middle of the vector are elided.
+const DEBUG_LIMIT: usize = 1024;
+
+/// A wrapper printing the internal structure of a vector with `Debug`,
+/// instead of its elements. It is returned by `debug_structure` methods.
+pub struct DebugStructure<'a, V>(pub(crate) &'a V);
+
+/// Stands for the elements elided from the `Debug` output.
+struct Elided(usize);
+
+impl Debug for Elided {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ".. {} more ..", self.0)
+    }
+}
+
+/// Prints the elements as a list, like the standard vector does. Only the
+/// elements at both ends are printed if there are more than `DEBUG_LIMIT`.
+pub(crate) fn debug_list<'a, T, F>(f: &mut fmt::Formatter<'_>, len: usize, get: F) -> fmt::Result
+where
+    T: Debug + 'a,
+    F: Fn(usize) -> &'a T,
+{
+    let mut list = f.debug_list();
+
+    if len <= DEBUG_LIMIT {
+        list.entries((0..len).map(&get));
+    } else {
+        let half = DEBUG_LIMIT / 2;
+
+        list.entries((0..half).map(&get));
+        list.entry(&Elided(len - half * 2));
+        list.entries((len - half..len).map(&get));
+    }
+
+    list.finish()
+}
+
 /// A persistent vector based on the balanced RbTree.
-#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct RbVec<T> {
     tree: RrbTree<T>,
     tail: [Option<T>; BRANCH_FACTOR],
@@ -84,13 +180,94 @@ pub struct RbVec<T> {
 }
 
 /// A persistent vector based on the relaxed RrbTree.
-#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct RrbVec<T> {
     tree: RrbTree<T>,
     tail: [Option<T>; BRANCH_FACTOR],
     tail_len: usize,
 }
 
+/// Implements operations built on top of `split_off`, `split_at_many` and `append`.
+///
+/// The leading `balanced` or `linear` selects how `concat` joins the vectors:
+/// `balanced` suits a relaxed `append`, whose cost does not depend on the
+/// length of the right-hand side, while `linear` suits an `append` that
+/// moves the right-hand side element by element.
+macro_rules! impl_split_vec {
+    (@concat balanced $vecs:ident) => {{
+        let mut vecs: Vec<Self> = $vecs.into_iter().filter(|vec| !vec.is_empty()).collect();
+
+        while vecs.len() > 1 {
+            let mut merged = Vec::with_capacity(vecs.len() / 2 + 1);
+            let mut iter = vecs.into_iter();
+
+            while let Some(mut left) = iter.next() {
+                if let Some(mut right) = iter.next() {
+                    left.append(&mut right);
+                }
+
+                merged.push(left);
+            }
+
+            vecs = merged;
+        }
+
+        vecs.pop().unwrap_or_default()
+    }};
+    (@concat linear $vecs:ident) => {{
+        let mut iter = $vecs.into_iter();
+        let mut vec = iter.next().unwrap_or_default();
+
+        for mut right in iter {
+            vec.append(&mut right);
+        }
+
+        vec
+    }};
+    ($concat:ident [$($params:tt)*] $vec:ident<$($args:ident),*>) => {
+        impl<$($params)*> $vec<$($args),*> {
+            /// Concatenates all vectors into one, in order.
+            pub fn concat<I: IntoIterator<Item = Self>>(vecs: I) -> Self {
+                impl_split_vec!(@concat $concat vecs)
+            }
+
+            /// Splits the vector into pieces of `size` elements each.
+            /// The last piece may be shorter if the length is not
+            /// divisible by `size`.
+            pub fn into_chunks(self, size: usize) -> Vec<Self> {
+                assert_ne!(size, 0, "chunk size must be non-zero");
+
+                if self.is_empty() {
+                    return Vec::new();
+                }
+
+                let indices: Vec<usize> = (size..self.len()).step_by(size).collect();
+                self.split_at_many(&indices)
+            }
+
+            /// Rotates the vector in-place such that the first `mid`
+            /// elements move to the end.
+            pub fn rotate_left(&mut self, mid: usize) {
+                assert!(mid <= self.len());
+
+                let mut right = self.split_off(mid);
+                right.append(self);
+
+                *self = right;
+            }
+
+            /// Rotates the vector in-place such that the last `k`
+            /// elements move to the front.
+            pub fn rotate_right(&mut self, k: usize) {
+                assert!(k <= self.len());
+
+                let mid = self.len() - k;
+                self.rotate_left(mid);
+            }
+        }
+    };
+}
+
 macro_rules! impl_vec {
     ($vec:ident) => {
         impl<T: Clone + Debug> Default for $vec<T> {
@@ -111,6 +288,26 @@ macro_rules! impl_vec {
                 }
             }
 
+            /// Constructs a vector of `n` clones of the value. The leaves and
+            /// branches of the tree are shared by all positions, so that the
+            /// vector takes O(log n) memory until its elements are modified.
+            pub fn from_elem(value: T, n: usize) -> Self {
+                let tree = RrbTree::repeat(&value, n / BRANCH_FACTOR);
+
+                let mut tail = new_branch!();
+                let tail_len = n % BRANCH_FACTOR;
+
+                for element in tail.iter_mut().take(tail_len) {
+                    *element = Some(value.clone());
+                }
+
+                $vec {
+                    tree,
+                    tail,
+                    tail_len,
+                }
+            }
+
             /// Adds an element to the back of a collection.
             pub fn push(&mut self, item: T) {
                 self.tail[self.tail_len] = Some(item);
@@ -136,17 +333,21 @@ macro_rules! impl_vec {
                     return None;
                 }
 
-                if self.tail_len == 0 {
-                    let (new_tail, new_tail_len) = self.tree.pop();
-                    mem::replace(&mut self.tail, new_tail);
+                reset_on_unwind(self, |this| {
+                    if this.tail_len == 0 {
+                        let (new_tail, new_tail_len) = this.tree.pop();
+                        mem::replace(&mut this.tail, new_tail);
 
-                    self.tail_len = new_tail_len;
-                }
+                        this.tail_len = new_tail_len;
+                    }
 
-                let item = self.tail[self.tail_len - 1].take();
-                self.tail_len -= 1;
+                    let item = this.tail[this.tail_len - 1].take();
+                    this.tail_len -= 1;
 
-                item
+                    this.fill_root_leaf();
+
+                    item
+                })
             }
 
             /// Returns a reference to an element at the given
@@ -155,7 +356,7 @@ macro_rules! impl_vec {
                 if self.tree.len() > index {
                     self.tree.get(index)
                 } else {
-                    self.tail[index - self.tree.len()].as_ref()
+                    self.tail.get(index - self.tree.len())?.as_ref()
                 }
             }
 
@@ -165,10 +366,61 @@ macro_rules! impl_vec {
                 if self.tree.len() > index {
                     self.tree.get_mut(index)
                 } else {
-                    self.tail[index - self.tree.len()].as_mut()
+                    self.tail.get_mut(index - self.tree.len())?.as_mut()
+                }
+            }
+
+            /// Returns a mutable reference to an element at the given position
+            /// or None if out of bounds, along with true if any of the nodes on
+            /// the path to the element was shared, and had to be copied.
+            pub fn get_mut_cow(&mut self, index: usize) -> Option<(&mut T, bool)> {
+                if self.tree.len() > index {
+                    let copied = !self.tree.is_unique_path(index);
+                    self.tree.get_mut(index).map(|item| (item, copied))
+                } else {
+                    self.tail
+                        .get_mut(index - self.tree.len())?
+                        .as_mut()
+                        .map(|item| (item, false))
                 }
             }
 
+            /// Returns true if none of the nodes of the vector is shared
+            /// with other vectors, hence no write would copy a node.
+            pub fn is_unique(&self) -> bool {
+                self.tree.is_unique()
+            }
+
+            /// Returns true if both vectors refer to the same tree and have tails
+            /// of the same length. The tail, holding up to the last BRANCH_FACTOR
+            /// elements, is stored inline and is not shared between clones,
+            /// hence its elements are not compared. Vectors without a tree
+            /// share nothing, so they are only equal when both are empty.
+            pub fn ptr_eq(&self, that: &Self) -> bool {
+                if self.tree.is_empty() || that.tree.is_empty() {
+                    return self.is_empty() && that.is_empty();
+                }
+
+                self.tree.ptr_eq(&that.tree)
+                    && self.tree.len() == that.tree.len()
+                    && self.tail_len == that.tail_len
+            }
+
+            /// Copies all of the nodes shared with other vectors up front,
+            /// so that subsequent writes do not pay for copy-on-write.
+            pub fn make_unique(&mut self) {
+                self.tree.make_unique();
+            }
+
+            /// Copies the nodes shared with other vectors on the paths
+            /// to the elements in the range, leaving the rest shared.
+            pub fn make_unique_range(&mut self, range: ops::Range<usize>) {
+                assert!(range.start <= range.end && range.end <= self.len());
+
+                let end = cmp::min(range.end, self.tree.len());
+                self.tree.make_unique_range(range.start..end);
+            }
+
             /// Returns the number of elements in the vector.
             pub fn len(&self) -> usize {
                 self.tree.len() + self.tail_len
@@ -180,6 +432,39 @@ macro_rules! impl_vec {
             }
         }
 
+        #[cfg(all(feature = "arc", feature = "rayon_iter"))]
+        impl<T: Clone + Debug + Send + Sync> $vec<T> {
+            /// Copies all of the nodes shared with other vectors
+            /// up front, unsharing the subtrees in parallel.
+            pub fn par_make_unique(&mut self) {
+                self.tree.par_make_unique();
+            }
+        }
+
+        impl<T: Clone + Debug> $vec<T> {
+            /// Returns a wrapper, which prints the tree and
+            /// the tail of the vector with `Debug`.
+            pub fn debug_structure(&self) -> DebugStructure<'_, Self> {
+                DebugStructure(self)
+            }
+        }
+
+        impl<T: Clone + Debug> Debug for $vec<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                debug_list(f, self.len(), |index| &self[index])
+            }
+        }
+
+        impl<'a, T: Clone + Debug> Debug for DebugStructure<'a, $vec<T>> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($vec))
+                    .field("tree", &self.0.tree)
+                    .field("tail", &self.0.tail)
+                    .field("tail_len", &self.0.tail_len)
+                    .finish()
+            }
+        }
+
         impl<T: Clone + Debug> ops::Index<usize> for $vec<T> {
             type Output = T;
 
@@ -211,265 +496,508 @@ macro_rules! impl_vec {
 impl_vec!(RbVec);
 impl_vec!(RrbVec);
 
+impl_split_vec!(linear [T: Clone + Debug] RbVec<T>);
+impl_split_vec!(balanced [T: Clone + Debug] RrbVec<T>);
+
 impl<T: Clone + Debug> RbVec<T> {
+    /// The tree of RbVec is never relaxed, thus the root leaf is always full.
+    #[inline(always)]
+    fn fill_root_leaf(&mut self) {}
+
     /// Splits the collection into two at the given index.
     ///
     /// Returns a newly allocated vector containing the elements
     /// in the range [at, len). After the call, the original vector
     /// will be left containing the elements [0, at).
     pub fn split_off(&mut self, mid: usize) -> Self {
-        if mid == 0 {
-            mem::replace(self, Self::new())
-        } else if mid < self.len() {
-            if self.tree.len() > mid {
-                let chunks_count = (self.tree.len() - mid) / BRANCH_FACTOR;
-                let mut chunks = Vec::with_capacity(chunks_count);
-
-                while self.tree.len() - BRANCH_FACTOR > mid {
-                    chunks.push(self.tree.pop());
-                }
+        assert!(
+            mid <= self.len(),
+            "split index `{}` out of bounds in RbVec of length `{}`",
+            mid,
+            self.len()
+        );
+
+        reset_on_unwind(self, |this| {
+            if mid == 0 {
+                mem::take(this)
+            } else if mid < this.len() {
+                if this.tree.len() > mid {
+                    let chunks_count = (this.tree.len() - mid) / BRANCH_FACTOR;
+                    let mut chunks = Vec::with_capacity(chunks_count);
+
+                    while this.tree.len() - BRANCH_FACTOR > mid {
+                        chunks.push(this.tree.pop());
+                    }
 
-                let (mut left_tail, mut left_tail_len) = self.tree.pop();
+                    let (mut left_tail, mut left_tail_len) = this.tree.pop();
 
-                let from_i = mid - self.tree.len();
-                let to_len = left_tail_len;
+                    let from_i = mid - this.tree.len();
+                    let to_len = left_tail_len;
 
-                let mut right = Self::new();
-                for i in from_i..to_len {
-                    right.push(left_tail[i].take().unwrap());
-                    left_tail_len -= 1;
-                }
+                    let mut right = Self::new();
+                    for i in from_i..to_len {
+                        right.push(left_tail[i].take().unwrap());
+                        left_tail_len -= 1;
+                    }
 
-                let mut right_tail = mem::replace(&mut self.tail, left_tail);
-                let right_tail_len = mem::replace(&mut self.tail_len, left_tail_len);
+                    let mut right_tail = mem::replace(&mut this.tail, left_tail);
+                    let right_tail_len = mem::replace(&mut this.tail_len, left_tail_len);
 
-                for (mut chunk, chunk_len) in chunks.into_iter().rev() {
-                    for i in 0..chunk_len {
-                        right.push(chunk[i].take().unwrap());
+                    for (mut chunk, chunk_len) in chunks.into_iter().rev() {
+                        for i in 0..chunk_len {
+                            right.push(chunk[i].take().unwrap());
+                        }
                     }
-                }
 
-                for i in 0..right_tail_len {
-                    right.push(right_tail[i].take().unwrap());
-                }
+                    for i in 0..right_tail_len {
+                        right.push(right_tail[i].take().unwrap());
+                    }
 
-                right
-            } else {
-                let left_tail_len = mid - self.tree.len();
+                    right
+                } else {
+                    let left_tail_len = mid - this.tree.len();
 
-                let mut right_tail = new_branch!();
-                let mut right_tail_len = 0;
+                    let mut right_tail = new_branch!();
+                    let mut right_tail_len = 0;
 
-                for i in left_tail_len..self.tail_len {
-                    right_tail[right_tail_len] = self.tail[i].take();
-                    right_tail_len += 1;
-                }
+                    for i in left_tail_len..this.tail_len {
+                        right_tail[right_tail_len] = this.tail[i].take();
+                        right_tail_len += 1;
+                    }
 
-                self.tail_len = left_tail_len;
+                    this.tail_len = left_tail_len;
 
-                RbVec {
-                    tree: RrbTree::new(),
-                    tail: right_tail,
-                    tail_len: right_tail_len,
+                    RbVec {
+                        tree: RrbTree::new(),
+                        tail: right_tail,
+                        tail_len: right_tail_len,
+                    }
                 }
+            } else {
+                Self::new()
             }
-        } else if mid == self.len() {
-            Self::new()
-        } else {
-            panic!()
-        }
+        })
     }
 
-    /// Moves all the elements of `that` into
-    /// `Self`, leaving `other` empty.
-    pub fn append(&mut self, that: &mut RbVec<T>) {
-        let that_is_empty = that.is_empty();
-
-        if self.is_empty() {
-            mem::swap(&mut self.tree, &mut that.tree);
-            mem::swap(&mut self.tail, &mut that.tail);
-            mem::swap(&mut self.tail_len, &mut that.tail_len);
-        } else if !that_is_empty {
-            let that_tree = mem::replace(&mut that.tree, RrbTree::new());
-            let that_tail = mem::replace(&mut that.tail, new_branch!());
+    /// Splits the vector into `indices.len() + 1` pieces at the given
+    /// positions, which must be sorted and must not exceed the length.
+    ///
+    /// The tree of RbVec can not be cut in the middle of a leaf, hence
+    /// the left-most piece keeps the tree, while the elements after it
+    /// are taken out once and pushed into the remaining pieces.
+    pub fn split_at_many(mut self, indices: &[usize]) -> Vec<Self> {
+        let ranges = split_ranges(indices, self.len());
+
+        let mut elements = Vec::with_capacity(self.len() - ranges[0].end);
+        while self.len() > ranges[0].end {
+            elements.push(self.pop().unwrap());
+        }
 
-            let that_tail_len = that.tail_len;
-            that.tail_len = 0;
+        let mut pieces = Vec::with_capacity(ranges.len());
+        pieces.push(self);
 
-            let that_vec = RbVec {
-                tree: that_tree,
-                tail: that_tail,
-                tail_len: that_tail_len,
-            };
+        for range in &ranges[1..] {
+            let mut piece = Self::new();
 
-            for value in that_vec.into_iter() {
-                self.push(value);
+            for _ in range.clone() {
+                piece.push(elements.pop().unwrap());
             }
+
+            pieces.push(piece);
         }
+
+        pieces
+    }
+
+    /// Moves all the elements of `that` into
+    /// `Self`, leaving `other` empty.
+    pub fn append(&mut self, that: &mut RbVec<T>) {
+        reset_on_unwind(that, |that| {
+            reset_on_unwind(self, |this| {
+                let that_is_empty = that.is_empty();
+
+                if this.is_empty() {
+                    mem::swap(&mut this.tree, &mut that.tree);
+                    mem::swap(&mut this.tail, &mut that.tail);
+                    mem::swap(&mut this.tail_len, &mut that.tail_len);
+                } else if !that_is_empty {
+                    let that_tree = mem::replace(&mut that.tree, RrbTree::new());
+                    let that_tail = mem::take(&mut that.tail);
+
+                    let that_tail_len = that.tail_len;
+                    that.tail_len = 0;
+
+                    let that_vec = RbVec {
+                        tree: that_tree,
+                        tail: that_tail,
+                        tail_len: that_tail_len,
+                    };
+
+                    for value in that_vec.into_iter() {
+                        this.push(value);
+                    }
+                }
+            })
+        })
     }
 }
 
-impl<T: Clone + Debug> RrbVec<T> {
-    /// Splits the collection into two at the given index.
-    ///
-    /// Returns a vector containing the elements in the range [at, len).
-    /// After the call, the original vector will be left
-    /// containing the elements [0, at).
-    pub fn split_off(&mut self, mid: usize) -> Self {
-        if mid == 0 {
-            mem::replace(self, RrbVec::new())
-        } else if mid < self.len() {
-            if self.tree.len() > mid {
-                let right_tree = self.tree.split_off(mid);
+macro_rules! impl_relaxed_vec {
+    ([$($params:tt)*] $vec:ident<$($args:ident),*>) => {
+        impl<$($params)*> $vec<$($args),*> {
+            /// Splits the collection into two at the given index.
+            ///
+            /// Returns a vector containing the elements in the range [at, len).
+            /// After the call, the original vector will be left
+            /// containing the elements [0, at).
+            pub fn split_off(&mut self, mid: usize) -> Self {
+                assert!(
+                    mid <= self.len(),
+                    "split index `{}` out of bounds in {} of length `{}`",
+                    mid,
+                    stringify!($vec),
+                    self.len()
+                );
+
+                reset_on_unwind(self, |this| {
+                    if mid == 0 {
+                        mem::take(this)
+                    } else if mid < this.len() {
+                        if this.tree.len() > mid {
+                            let right_tree = this.tree.split_off(mid);
+
+                            let (left_tail, left_tail_len) = this.tree.pop();
+
+                            let right_tail = mem::replace(&mut this.tail, left_tail);
+                            let right_tail_len = mem::replace(&mut this.tail_len, left_tail_len);
+
+                            let mut right = $vec {
+                                tree: right_tree,
+                                tail: right_tail,
+                                tail_len: right_tail_len,
+                            };
+
+                            this.fill_root_leaf();
+                            right.fill_root_leaf();
+
+                            right
+                        } else {
+                            let left_tail_len = mid - this.tree.len();
+
+                            let mut right_tail = new_branch!();
+                            let mut right_tail_len = 0;
+
+                            for i in left_tail_len..this.tail_len {
+                                right_tail[right_tail_len] = this.tail[i].take();
+                                right_tail_len += 1;
+                            }
+
+                            this.tail_len = left_tail_len;
+
+                            $vec {
+                                tree: RrbTree::new(),
+                                tail: right_tail,
+                                tail_len: right_tail_len,
+                            }
+                        }
+                    } else {
+                        $vec::new()
+                    }
+                })
+            }
 
-                let (left_tail, left_tail_len) = self.tree.pop();
+            /// Splits the vector into `indices.len() + 1` pieces at the given
+            /// positions, which must be sorted and must not exceed the length.
+            ///
+            /// The tree is walked down once for all positions, and the subtrees
+            /// in between them are moved into the pieces as they are.
+            pub fn split_at_many(self, indices: &[usize]) -> Vec<Self> {
+                let ranges = split_ranges(indices, self.len());
+                let tree_len = self.tree.len();
+
+                let tree_indices: Vec<usize> = ranges[1..]
+                    .iter()
+                    .map(|range| cmp::min(range.start, tree_len))
+                    .collect();
+
+                let trees = self.tree.split_at_many(&tree_indices);
+                let mut tail = self.tail;
+
+                trees
+                    .into_iter()
+                    .zip(ranges)
+                    .map(|(tree, range)| {
+                        let mut piece = $vec::from_tree(tree);
+
+                        // the piece spanning the end of the tree takes the
+                        // beginning of the tail, and the following pieces
+                        // are cut out of the tail
+                        let tail_start = cmp::max(range.start, tree_len) - tree_len;
+                        let tail_end = cmp::max(range.end, tree_len) - tree_len;
+
+                        if tail_start < tail_end {
+                            let mut that = $vec::new();
+
+                            for element in tail[tail_start..tail_end].iter_mut() {
+                                that.tail[that.tail_len] = element.take();
+                                that.tail_len += 1;
+                            }
+
+                            piece.append(&mut that);
+                        }
+
+                        piece
+                    })
+                    .collect()
+            }
 
-                let right_tail = mem::replace(&mut self.tail, left_tail);
-                let right_tail_len = mem::replace(&mut self.tail_len, left_tail_len);
+            /// Constructs a vector out of a tree, whose last leaf becomes the tail.
+            fn from_tree(mut tree: RrbTree<$($args),*>) -> Self {
+                if tree.is_empty() {
+                    return $vec::new();
+                }
 
-                let mut right = RrbVec {
-                    tree: right_tree,
-                    tail: right_tail,
-                    tail_len: right_tail_len,
+                let (tail, tail_len) = tree.pop();
+                let mut vec = $vec {
+                    tree,
+                    tail,
+                    tail_len,
                 };
 
-                if right.tree.is_root_leaf() {
-                    if right.len() <= BRANCH_FACTOR {
-                        // all values can fit into a single tail
-                        let (mut new_tail, mut new_tail_len) = right.tree.pop();
+                vec.fill_root_leaf();
 
-                        for i in 0..right.tail_len {
-                            new_tail[new_tail_len] = right.tail[i].take();
-                            new_tail_len += 1;
-                        }
+                // a full leaf can not stay in the tail, so it is put back
+                vec.push_tail();
+                vec
+            }
 
-                        right.tail = new_tail;
-                        right.tail_len = new_tail_len;
+            /// Resizes the vector in-place, so that its length is equal to `n`.
+            /// The vector is either truncated, or extended by clones of the
+            /// value sharing the nodes of the tree, as built by `from_elem`.
+            pub fn resize(&mut self, n: usize, value: T) {
+                let len = self.len();
 
-                        // in case if tail is exactly BRANCH_FACTOR long, we should push it to the tree
-                        right.push_tail()
-                    } else if right.tree.len() < BRANCH_FACTOR {
-                        // root is leaf, but it is not fully dense
-                        // hence, some of the values should be redistributed to the actual leaf
+                if n < len {
+                    self.split_off(n);
+                } else if n > len {
+                    let mut left = n - len;
 
-                        let (mut root, mut root_len) = right.tree.pop();
-                        let mut index = 0;
+                    // the tail is filled up first, so that the shared
+                    // leaves are appended to the tree as they are
+                    while self.tail_len > 0 && left > 0 {
+                        self.push(value.clone());
+                        left -= 1;
+                    }
 
-                        while root_len < BRANCH_FACTOR && index < right.tail_len {
-                            root[root_len] = right.tail[index].take();
+                    self.append(&mut $vec::from_elem(value, left));
+                }
+            }
 
-                            root_len += 1;
-                            index += 1;
-                        }
+            /// Restores the invariant of the root leaf being full, which might be
+            /// broken after splitting or popping from a relaxed tree. The elements
+            /// of the tail are moved to the root leaf, or all of them to the tail.
+            fn fill_root_leaf(&mut self) {
+                if !self.tree.is_root_leaf() {
+                    return;
+                }
 
-                        right.tree.push(root, root_len);
+                if self.len() <= BRANCH_FACTOR {
+                    // all values can fit into a single tail
+                    let (mut new_tail, mut new_tail_len) = self.tree.pop();
 
-                        let (mut new_tail, mut new_tail_len) = (new_branch!(), 0);
-                        while index < right.tail_len {
-                            new_tail[new_tail_len] = right.tail[index].take();
+                    for i in 0..self.tail_len {
+                        new_tail[new_tail_len] = self.tail[i].take();
+                        new_tail_len += 1;
+                    }
 
-                            new_tail_len += 1;
-                            index += 1;
-                        }
+                    self.tail = new_tail;
+                    self.tail_len = new_tail_len;
 
-                        right.tail = new_tail;
-                        right.tail_len = new_tail_len;
-                    }
-                }
+                    // in case if tail is exactly BRANCH_FACTOR long, we should push it to the tree
+                    self.push_tail()
+                } else if self.tree.len() < BRANCH_FACTOR {
+                    // root is leaf, but it is not fully dense
+                    // hence, some of the values should be redistributed to the actual leaf
 
-                right
-            } else {
-                let left_tail_len = mid - self.tree.len();
+                    let (mut root, mut root_len) = self.tree.pop();
+                    let mut index = 0;
 
-                let mut right_tail = new_branch!();
-                let mut right_tail_len = 0;
+                    while root_len < BRANCH_FACTOR && index < self.tail_len {
+                        root[root_len] = self.tail[index].take();
 
-                for i in left_tail_len..self.tail_len {
-                    right_tail[right_tail_len] = self.tail[i].take();
-                    right_tail_len += 1;
-                }
+                        root_len += 1;
+                        index += 1;
+                    }
 
-                self.tail_len = left_tail_len;
+                    self.tree.push(root, root_len);
 
-                RrbVec {
-                    tree: RrbTree::new(),
-                    tail: right_tail,
-                    tail_len: right_tail_len,
+                    let (mut new_tail, mut new_tail_len) = (new_branch!(), 0);
+                    while index < self.tail_len {
+                        new_tail[new_tail_len] = self.tail[index].take();
+
+                        new_tail_len += 1;
+                        index += 1;
+                    }
+
+                    self.tail = new_tail;
+                    self.tail_len = new_tail_len;
                 }
             }
-        } else if mid == self.len() {
-            RrbVec::new()
-        } else {
-            panic!()
-        }
-    }
 
-    /// Moves all the elements of `that` into `Self` by concatenating
-    /// the underlying tree structures, leaving `other` empty.
-    pub fn append(&mut self, that: &mut RrbVec<T>) {
-        if self.is_empty() {
-            self.tail = mem::replace(&mut that.tail, new_branch!());
-            self.tree = mem::replace(&mut that.tree, RrbTree::new());
+            /// Moves all the elements of `that` into `Self` by concatenating
+            /// the underlying tree structures, leaving `other` empty.
+            pub fn append(&mut self, that: &mut Self) {
+                reset_on_unwind(that, |that| {
+                    reset_on_unwind(self, |this| {
+                        if this.is_empty() {
+                            this.tail = mem::replace(&mut that.tail, new_branch!());
+                            this.tree = mem::replace(&mut that.tree, RrbTree::new());
+
+                            this.tail_len = that.tail_len;
+                            that.tail_len = 0;
+                        } else if !that.is_empty() {
+                            let mut that_tail = mem::replace(&mut that.tail, new_branch!());
+                            let that_tail_len = that.tail_len;
+
+                            that.tail_len = 0;
+
+                            if that.tree.is_empty() {
+                                if this.tail_len == BRANCH_FACTOR {
+                                    let self_tail = mem::replace(&mut this.tail, that_tail);
+                                    let self_tail_len = this.tail_len;
+
+                                    this.tail_len = that_tail_len;
+                                    this.tree.push(self_tail, self_tail_len);
+                                } else if this.tail_len + that_tail_len <= BRANCH_FACTOR {
+                                    for item in that_tail.iter_mut().take(that_tail_len) {
+                                        this.tail[this.tail_len] = item.take();
+                                        this.tail_len += 1;
+                                    }
+                                } else {
+                                    let mut self_tail = mem::replace(&mut this.tail, new_branch!());
+                                    let mut self_tail_i = mem::replace(&mut this.tail_len, 0);
+                                    let mut that_tail_i = 0;
+
+                                    while self_tail_i < BRANCH_FACTOR && that_tail_i < that_tail_len {
+                                        self_tail[self_tail_i] = that_tail[that_tail_i].take();
+
+                                        self_tail_i += 1;
+                                        that_tail_i += 1;
+                                    }
+
+                                    this.tree.push(self_tail, self_tail_i);
+
+                                    let that_tail_elements_left = that_tail_len - that_tail_i;
+                                    for i in 0..that_tail_elements_left {
+                                        this.tail[i] = that_tail[that_tail_i].take();
+                                        that_tail_i += 1;
+                                    }
+
+                                    this.tail_len = that_tail_elements_left;
+                                }
+                            } else {
+                                if this.tail_len == 0 {
+                                    this.tail = that_tail;
+                                    this.tail_len = that_tail_len;
+                                } else {
+                                    let self_tail = mem::replace(&mut this.tail, that_tail);
+                                    let self_tail_len = this.tail_len;
+
+                                    this.tail_len = that_tail_len;
+                                    this.tree.push(self_tail, self_tail_len);
+                                }
+
+                                this.tree.append(&mut that.tree);
+                            }
+                        }
 
-            self.tail_len = that.tail_len;
-            that.tail_len = 0;
-        } else if !that.is_empty() {
-            let mut that_tail = mem::replace(&mut that.tail, new_branch!());
-            let that_tail_len = that.tail_len;
+                        this.push_tail();
+                    })
+                })
+            }
+        }
+    };
+}
 
-            that.tail_len = 0;
+impl_relaxed_vec!([T: Clone + Debug] RrbVec<T>);
 
-            if that.tree.is_empty() {
-                if self.tail_len == BRANCH_FACTOR {
-                    let self_tail = mem::replace(&mut self.tail, that_tail);
-                    let self_tail_len = self.tail_len;
-
-                    self.tail_len = that_tail_len;
-                    self.tree.push(self_tail, self_tail_len);
-                } else if self.tail_len + that_tail_len <= BRANCH_FACTOR {
-                    for item in that_tail.iter_mut().take(that_tail_len) {
-                        self.tail[self.tail_len] = item.take();
-                        self.tail_len += 1;
-                    }
-                } else {
-                    let mut self_tail = mem::replace(&mut self.tail, new_branch!());
-                    let mut self_tail_i = mem::replace(&mut self.tail_len, 0);
-                    let mut that_tail_i = 0;
-
-                    while self_tail_i < BRANCH_FACTOR && that_tail_i < that_tail_len {
-                        self_tail[self_tail_i] = that_tail[that_tail_i].take();
+impl<T: Clone + Debug> RrbVec<T> {
+    /// Repacks elements into fully dense leaves and rebuilds the tree out
+    /// of regular branches, removing the relaxed nodes that accumulate after
+    /// a number of `append` and `split_off` calls. Elements of the nodes that
+    /// are not shared with other vectors are moved, while the rest are cloned.
+    pub fn compact(&mut self) {
+        reset_on_unwind(self, |this| {
+            let tail = mem::take(&mut this.tail);
+            let tail_len = mem::take(&mut this.tail_len);
+
+            let (new_tail, new_tail_len) = this.tree.compact(tail, tail_len);
+
+            this.tail = new_tail;
+            this.tail_len = new_tail_len;
+        })
+    }
 
-                        self_tail_i += 1;
-                        that_tail_i += 1;
-                    }
+    /// Incrementally compacts the vector by repacking only the relaxed
+    /// subtrees, while dense subtrees are left untouched. If `preserve_shared`
+    /// is set, the nodes that are shared with other vectors are not copied,
+    /// hence the subtrees containing them may stay relaxed.
+    pub fn compact_relaxed(&mut self, preserve_shared: bool) {
+        reset_on_unwind(self, |this| {
+            this.tree.compact_relaxed(preserve_shared);
+        })
+    }
 
-                    self.tree.push(self_tail, self_tail_i);
+    /// Walks the nodes of the tree in depth-first order. Children
+    /// of a node are skipped if the visitor returns false for it.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    pub(crate) fn visit_nodes<F>(&self, visitor: &mut F)
+    where
+        F: FnMut(&NodeInfo) -> bool,
+    {
+        self.tree.visit_nodes(visitor);
+    }
 
-                    let that_tail_elements_left = that_tail_len - that_tail_i;
-                    for i in 0..that_tail_elements_left {
-                        self.tail[i] = that_tail[that_tail_i].take();
-                        that_tail_i += 1;
-                    }
+    /// Returns the elements of the leaf holding the given index,
+    /// starting from the element at the index.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    pub(crate) fn chunk(&self, index: usize) -> &[Option<T>] {
+        if self.tree.len() > index {
+            self.tree.get_chunk(index)
+        } else {
+            &self.tail[index - self.tree.len()..self.tail_len]
+        }
+    }
 
-                    self.tail_len = that_tail_elements_left;
-                }
-            } else {
-                if self.tail_len == 0 {
-                    self.tail = that_tail;
-                    self.tail_len = that_tail_len;
-                } else {
-                    let self_tail = mem::replace(&mut self.tail, that_tail);
-                    let self_tail_len = self.tail_len;
+    /// Returns the elements of the leaf holding the given index, starting
+    /// from the element at the index, copying the leaf if it is shared.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    pub(crate) fn chunk_mut(&mut self, index: usize) -> &mut [Option<T>] {
+        if self.tree.len() > index {
+            self.tree.get_chunk_mut(index)
+        } else {
+            &mut self.tail[index - self.tree.len()..self.tail_len]
+        }
+    }
 
-                    self.tail_len = that_tail_len;
-                    self.tree.push(self_tail, self_tail_len);
-                }
+    /// Appends the elements by filling the tail, which
+    /// is pushed into the tree a leaf at a time.
+    #[cfg(feature = "std")]
+    pub(crate) fn extend_from_slice(&mut self, mut items: &[T]) {
+        while !items.is_empty() {
+            let len = cmp::min(BRANCH_FACTOR - self.tail_len, items.len());
 
-                self.tree.append(&mut that.tree);
+            for item in &items[..len] {
+                self.tail[self.tail_len] = Some(item.clone());
+                self.tail_len += 1;
             }
-        }
 
-        self.push_tail();
+            self.push_tail();
+            items = &items[len..];
+        }
     }
 }
 
@@ -498,3 +1026,15 @@ impl<T: Clone + Debug> From<&Vec<T>> for RrbVec<T> {
         }
     }
 }
+
+#[cfg(feature = "std")]
+pub mod dedup;
+#[cfg(feature = "std")]
+pub mod disk;
+pub mod measure;
+#[cfg(feature = "std")]
+pub mod merkle;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod stats;
@@ -0,0 +1,47 @@
+//! A module rendering the structure of [RrbVec](crate::core::RrbVec)
+//! trees, either as a Graphviz graph or as indented text. Nodes are
+//! shown along with their fill levels and reference counts, and the
+//! nodes shared by many vectors are shown once.
+
+use super::rrbtree::render::{DotWriter, TextWriter};
+use super::RrbVec;
+use std::fmt::Debug;
+
+/// Renders the trees of the vectors, typically versions of the same
+/// vector, as a Graphviz graph in the DOT language. Shared nodes are
+/// filled, and have an incoming edge from each of their parents.
+pub fn to_dot<T: Clone + Debug>(vecs: &[&RrbVec<T>]) -> String {
+    let mut writer = DotWriter::new();
+
+    for vec in vecs {
+        writer.add_vec(&vec.tree, vec.tail_len);
+    }
+
+    writer.finish()
+}
+
+/// Renders the trees of the vectors as indented text. Every node gets
+/// a number, which is printed instead of the node when it is seen again.
+pub fn to_text<T: Clone + Debug>(vecs: &[&RrbVec<T>]) -> String {
+    let mut writer = TextWriter::new();
+
+    for vec in vecs {
+        writer.add_vec(&vec.tree, vec.tail_len);
+    }
+
+    writer.finish()
+}
+
+impl<T: Clone + Debug> RrbVec<T> {
+    /// Renders the tree of the vector as a Graphviz graph.
+    /// See [to_dot](crate::core::render::to_dot) for many vectors.
+    pub fn to_dot(&self) -> String {
+        to_dot(&[self])
+    }
+
+    /// Renders the tree of the vector as indented text.
+    /// See [to_text](crate::core::render::to_text) for many vectors.
+    pub fn to_text(&self) -> String {
+        to_text(&[self])
+    }
+}
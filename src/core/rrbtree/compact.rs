@@ -0,0 +1,366 @@
+use super::{Branch, BranchBuilder, Index, Leaf, Node, RrbTree, Shift};
+use super::{SharedPtr, Take};
+use super::{BITS_PER_LEVEL, BRANCH_FACTOR};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::mem;
+
+/// Accumulates elements of the consumed nodes into densely
+/// packed leaves. Leaves that are full and happen to be aligned
+/// with the packer are kept as they are, without moving elements.
+struct LeafPacker<T> {
+    nodes: Vec<Node<T>>,
+    leaf: Leaf<T>,
+}
+
+impl<T: Clone + Debug> LeafPacker<T> {
+    #[inline(always)]
+    fn new() -> Self {
+        LeafPacker {
+            nodes: Vec::new(),
+            leaf: Leaf::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn add(&mut self, element: Option<T>) {
+        self.leaf.add(element);
+
+        if self.leaf.is_full() {
+            let leaf = mem::replace(&mut self.leaf, Leaf::new());
+            self.nodes.push(Node::Leaf(SharedPtr::new(leaf)));
+        }
+    }
+
+    fn push_leaf(&mut self, leaf: SharedPtr<Leaf<T>>) {
+        if self.leaf.is_empty() && leaf.is_full() {
+            self.nodes.push(Node::Leaf(leaf));
+        } else {
+            let mut leaf = leaf.take();
+
+            for i in 0..leaf.len {
+                self.add(leaf.elements[i].take());
+            }
+        }
+    }
+
+    fn push_node(&mut self, node: Node<T>) {
+        match node {
+            Node::Leaf(leaf) => self.push_leaf(leaf),
+            Node::Branch(branch) => {
+                let mut branch = branch.take();
+
+                for child in branch.children.iter_mut().take(branch.len) {
+                    self.push_node(child.take().unwrap());
+                }
+            }
+            Node::RelaxedBranch(branch) => {
+                let mut branch = branch.take();
+
+                for child in branch.children.iter_mut().take(branch.len) {
+                    self.push_node(child.take().unwrap());
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone + Debug> Node<T> {
+    /// Checks whether there is a relaxed branch somewhere in the subtree.
+    /// All children of a regular branch except the last one are dense,
+    /// hence only the right-most path has to be inspected.
+    fn has_relaxed_branch(&self) -> bool {
+        let mut node = self;
+
+        loop {
+            match *node {
+                Node::RelaxedBranch(..) => return true,
+                Node::Branch(ref branch) => {
+                    node = branch.children[branch.len - 1].as_ref().unwrap();
+                }
+                Node::Leaf(..) => return false,
+            }
+        }
+    }
+
+    /// Builds the smallest tree of regular branches on top of the
+    /// given nodes. All nodes except the last one have to be dense.
+    fn build_dense(mut nodes: Vec<Node<T>>) -> (Node<T>, Shift) {
+        let mut shift = Shift(0);
+
+        while nodes.len() > 1 {
+            let mut parents = Vec::with_capacity(nodes.len() / BRANCH_FACTOR + 1);
+            let mut branch = Branch::new();
+
+            for node in nodes {
+                if branch.len == BRANCH_FACTOR {
+                    let full_branch = mem::replace(&mut branch, Branch::new());
+                    parents.push(Node::Branch(SharedPtr::new(full_branch)));
+                }
+
+                branch.add(Some(node));
+            }
+
+            parents.push(Node::Branch(SharedPtr::new(branch)));
+
+            nodes = parents;
+            shift = shift.inc();
+        }
+
+        (nodes.pop().unwrap(), shift)
+    }
+
+    /// Builds a subtree of exactly the given height on top of the given
+    /// nodes. The branches that end-up being not fully dense are relaxed.
+    fn build_subtree(mut nodes: Vec<Node<T>>, shift: Shift) -> Node<T> {
+        let mut level = Shift(0);
+
+        while level < shift {
+            let mut parents = Vec::with_capacity(nodes.len() / BRANCH_FACTOR + 1);
+            let mut builder = BranchBuilder::new(level);
+
+            for node in nodes {
+                if builder.is_full() {
+                    parents.push(builder.build());
+                }
+
+                builder.push(node);
+            }
+
+            if !builder.is_empty() {
+                parents.push(builder.build());
+            }
+
+            nodes = parents;
+            level = level.inc();
+        }
+
+        debug_assert_eq!(nodes.len(), 1);
+        nodes.pop().unwrap()
+    }
+
+    fn compact(self, shift: Shift, preserve_shared: bool) -> Node<T> {
+        if !self.has_relaxed_branch() || (preserve_shared && self.is_shared()) {
+            return self;
+        }
+
+        let repack = self.is_relaxed_branch() && (!preserve_shared || self.is_unique_subtree());
+
+        match self {
+            Node::Branch(mut ptr) => {
+                let branch = SharedPtr::make_mut(&mut ptr);
+                let last = branch.len - 1;
+
+                let child = branch.children[last].take().unwrap();
+                branch.children[last] = Some(child.compact(shift.dec(), preserve_shared));
+
+                Node::Branch(ptr)
+            }
+            Node::RelaxedBranch(ptr) if repack => {
+                let mut packer = LeafPacker::new();
+                packer.push_node(Node::RelaxedBranch(ptr));
+
+                if !packer.leaf.is_empty() {
+                    packer.nodes.push(Node::Leaf(SharedPtr::new(packer.leaf)));
+                }
+
+                Node::build_subtree(packer.nodes, shift)
+            }
+            Node::RelaxedBranch(mut ptr) => {
+                // the subtree has nodes shared with other trees, thus it is
+                // not possible to repack it without copying them. Instead,
+                // the children which are owned exclusively are compacted.
+                let branch = SharedPtr::make_mut(&mut ptr);
+
+                for child in branch.children.iter_mut().take(branch.len) {
+                    *child = child.take().map(|it| it.compact(shift.dec(), true));
+                }
+
+                Node::RelaxedBranch(ptr)
+            }
+            Node::Leaf(..) => unreachable!(),
+        }
+    }
+}
+
+impl<T: Clone + Debug> RrbTree<T> {
+    /// Repacks all elements of the tree, followed by the given chunk,
+    /// into fully dense leaves and rebuilds the tree out of regular
+    /// branches only. The elements that do not fill up the last leaf
+    /// are returned back.
+    pub fn compact(
+        &mut self,
+        mut tail: [Option<T>; BRANCH_FACTOR],
+        tail_len: usize,
+    ) -> ([Option<T>; BRANCH_FACTOR], usize) {
+        let mut packer = LeafPacker::new();
+
+        if let Some(root) = self.root.take() {
+            packer.push_node(root);
+        }
+
+        for item in tail.iter_mut().take(tail_len) {
+            packer.add(item.take());
+        }
+
+        let root_len = packer.nodes.len() * BRANCH_FACTOR;
+
+        if packer.nodes.is_empty() {
+            *self = RrbTree::new();
+        } else {
+            let (root, shift) = Node::build_dense(packer.nodes);
+
+            self.root = Some(root);
+            self.root_len = Index(root_len);
+            self.shift = shift;
+        }
+
+        (packer.leaf.elements, packer.leaf.len)
+    }
+
+    /// Repacks only the relaxed subtrees, leaving the dense ones untouched.
+    /// If `preserve_shared` is set, the nodes referenced by other trees are
+    /// not copied, at the cost of some subtrees staying relaxed.
+    pub fn compact_relaxed(&mut self, preserve_shared: bool) {
+        if let Some(root) = self.root.take() {
+            let mut root = root.compact(self.shift, preserve_shared);
+
+            while self.shift.0 > BITS_PER_LEVEL && root.len() == 1 {
+                root = root.as_mut_children()[0].take().unwrap();
+                self.shift = self.shift.dec();
+            }
+
+            self.root = Some(root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, RrbTree, BITS_PER_LEVEL, BRANCH_FACTOR};
+
+    fn create_tree_of_size(n: usize, val: usize) -> RrbTree<usize> {
+        let mut tree = RrbTree::new();
+        let mut value = val;
+        let mut left = n;
+
+        while left > 0 {
+            let count = core::cmp::min(left, BRANCH_FACTOR);
+            let mut elements = new_branch!();
+
+            for element in elements.iter_mut().take(count) {
+                *element = Some(value);
+                value += 1;
+            }
+
+            tree.push(elements, count);
+            left -= count;
+        }
+
+        tree
+    }
+
+    fn create_relaxed_tree(chunks: usize, chunk_size: usize) -> RrbTree<usize> {
+        let mut tree = RrbTree::new();
+
+        for i in 0..chunks {
+            let mut chunk = create_tree_of_size(chunk_size, i * chunk_size);
+            tree.append(&mut chunk);
+        }
+
+        tree
+    }
+
+    fn count_relaxed_branches(node: &Node<usize>) -> usize {
+        match node {
+            Node::RelaxedBranch(ref branch) => {
+                1 + branch.children[..branch.len]
+                    .iter()
+                    .map(|child| count_relaxed_branches(child.as_ref().unwrap()))
+                    .sum::<usize>()
+            }
+            Node::Branch(ref branch) => branch.children[..branch.len]
+                .iter()
+                .map(|child| count_relaxed_branches(child.as_ref().unwrap()))
+                .sum(),
+            Node::Leaf(..) => 0,
+        }
+    }
+
+    #[test]
+    fn compact_relaxed_tree() {
+        let chunk_size = BRANCH_FACTOR * 3 + 1;
+        let chunks = BRANCH_FACTOR * 2;
+
+        let mut tree = create_relaxed_tree(chunks, chunk_size);
+        assert!(count_relaxed_branches(tree.root.as_ref().unwrap()) > 0);
+
+        let (tail, tail_len) = tree.compact(new_branch!(), 0);
+        assert_eq!(count_relaxed_branches(tree.root.as_ref().unwrap()), 0);
+
+        let len = chunks * chunk_size;
+        assert_eq!(tree.len() + tail_len, len);
+        assert_eq!(tree.len() % BRANCH_FACTOR, 0);
+
+        for i in 0..tree.len() {
+            assert_eq!(tree.get(i).cloned(), Some(i));
+        }
+
+        for (i, item) in tail.iter().take(tail_len).enumerate() {
+            assert_eq!(*item, Some(tree.len() + i));
+        }
+    }
+
+    #[test]
+    fn compact_must_not_mutate_cloned_tree() {
+        let chunk_size = BRANCH_FACTOR + 1;
+        let mut tree = create_relaxed_tree(BRANCH_FACTOR, chunk_size);
+        let tree_clone = tree.clone();
+
+        tree.compact(new_branch!(), 0);
+
+        assert_eq!(tree_clone.len(), BRANCH_FACTOR * chunk_size);
+        for i in 0..tree_clone.len() {
+            assert_eq!(tree_clone.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    fn compact_relaxed_keeps_elements() {
+        let chunk_size = BRANCH_FACTOR * 3 + 1;
+        let chunks = BRANCH_FACTOR * 2;
+
+        let mut tree = create_relaxed_tree(chunks, chunk_size);
+        tree.compact_relaxed(false);
+
+        assert_eq!(tree.len(), chunks * chunk_size);
+        for i in 0..tree.len() {
+            assert_eq!(tree.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    fn compact_relaxed_preserves_shared_nodes() {
+        let chunk_size = BRANCH_FACTOR + 1;
+        let chunks = BRANCH_FACTOR * 2;
+
+        let mut tree = create_relaxed_tree(chunks, chunk_size);
+        let tree_clone = tree.clone();
+
+        tree.compact_relaxed(true);
+        assert_eq!(tree, tree_clone);
+
+        drop(tree_clone);
+
+        tree.compact_relaxed(true);
+        assert_eq!(tree.len(), chunks * chunk_size);
+
+        // only the right-most path is allowed to stay relaxed
+        let levels = tree.shift.0 / BITS_PER_LEVEL;
+        assert!(count_relaxed_branches(tree.root.as_ref().unwrap()) <= levels);
+
+        for i in 0..tree.len() {
+            assert_eq!(tree.get(i).cloned(), Some(i));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use super::super::dedup::DedupStats;
+use super::SharedPtr;
+use super::BRANCH_FACTOR;
+use super::{Branch, Node, RelaxedBranch, RrbTree};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+const LEAF: u8 = 0;
+const BRANCH: u8 = 1;
+const RELAXED_BRANCH: u8 = 2;
+
+/// Canonical instances of the nodes seen so far, grouped by their hashes.
+/// Leaves are hashed by their elements, while branches are hashed by the
+/// addresses of their children, which are canonical themselves. Every
+/// canonical node is referenced by the table, so that its address can
+/// not be reused while it is in the table.
+#[derive(Debug)]
+pub struct NodeInterner<T> {
+    buckets: HashMap<u64, Vec<Node<T>>>,
+    canonical: HashSet<usize>,
+}
+
+impl<T: Clone + Debug> Node<T> {
+    /// Returns the memory taken by the node itself, along with the
+    /// reference counters allocated next to it.
+    #[inline(always)]
+    fn size(&self) -> usize {
+        let counters = 2 * mem::size_of::<usize>();
+
+        match self {
+            Node::RelaxedBranch(ref ptr) => counters + mem::size_of_val(&**ptr),
+            Node::Branch(ref ptr) => counters + mem::size_of_val(&**ptr),
+            Node::Leaf(ref ptr) => counters + mem::size_of_val(&**ptr),
+        }
+    }
+
+    /// Returns a copy of the branch pointing to the given children.
+    fn with_children(&self, children: [Option<Node<T>>; BRANCH_FACTOR]) -> Node<T> {
+        match self {
+            Node::RelaxedBranch(ref branch) => Node::RelaxedBranch(SharedPtr::new(RelaxedBranch {
+                children,
+                sizes: branch.sizes,
+                len: branch.len,
+                summary: branch.summary.clone(),
+            })),
+            Node::Branch(ref branch) => Node::Branch(SharedPtr::new(Branch {
+                children,
+                len: branch.len,
+                summary: branch.summary.clone(),
+            })),
+            Node::Leaf(..) => unreachable!(),
+        }
+    }
+}
+
+#[inline(always)]
+fn same_children<T: Clone + Debug>(a: &[Option<Node<T>>], b: &[Option<Node<T>>]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| a.as_ref().map(Node::address) == b.as_ref().map(Node::address))
+}
+
+impl<T: Clone + Debug + Hash + Eq> NodeInterner<T> {
+    pub fn new() -> Self {
+        NodeInterner {
+            buckets: HashMap::new(),
+            canonical: HashSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.canonical.len()
+    }
+
+    /// Replaces the nodes of the tree with their canonical instances.
+    pub fn dedup(&mut self, tree: &mut RrbTree<T>, stats: &mut DedupStats) {
+        if let Some(ref mut root) = tree.root {
+            *root = self.intern(root, stats);
+        }
+    }
+
+    /// Drops the canonical nodes that are referenced only by the table.
+    /// Releasing a branch might release its children, hence the table
+    /// is swept until no more nodes are dropped.
+    pub fn purge(&mut self) {
+        loop {
+            let len = self.len();
+
+            for nodes in self.buckets.values_mut() {
+                nodes.retain(|node| node.strong_count() > 1);
+            }
+
+            self.buckets.retain(|_, nodes| !nodes.is_empty());
+            self.canonical = self.buckets.values().flatten().map(Node::address).collect();
+
+            if self.len() == len {
+                break;
+            }
+        }
+    }
+
+    /// Returns the canonical instance of the node, interning its
+    /// descendants first. The node becomes canonical if there is
+    /// no equal node in the table.
+    fn intern(&mut self, node: &Node<T>, stats: &mut DedupStats) -> Node<T> {
+        stats.visited_nodes += 1;
+
+        if self.canonical.contains(&node.address()) {
+            return node.clone();
+        }
+
+        let mut children = new_branch!();
+        let mut changed = false;
+
+        for (i, child) in node.children().iter().enumerate() {
+            let child = child.as_ref().unwrap();
+            let canonical = self.intern(child, stats);
+
+            changed |= canonical.address() != child.address();
+            children[i] = Some(canonical);
+        }
+
+        let hash = Self::hash(node, &children[..node.len()]);
+
+        if let Some(nodes) = self.buckets.get(&hash) {
+            let equal = nodes
+                .iter()
+                .find(|that| Self::equals(that, node, &children[..node.len()]));
+
+            if let Some(that) = equal {
+                stats.replaced_nodes += 1;
+                stats.bytes_saved += node.size();
+
+                return that.clone();
+            }
+        }
+
+        let node = if changed {
+            node.with_children(children)
+        } else {
+            node.clone()
+        };
+
+        self.canonical.insert(node.address());
+        self.buckets.entry(hash).or_default().push(node.clone());
+
+        node
+    }
+
+    fn hash(node: &Node<T>, children: &[Option<Node<T>>]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        match node {
+            Node::RelaxedBranch(ref branch) => {
+                RELAXED_BRANCH.hash(&mut hasher);
+                branch.sizes[..branch.len].hash(&mut hasher);
+            }
+            Node::Branch(..) => BRANCH.hash(&mut hasher),
+            Node::Leaf(ref leaf) => {
+                LEAF.hash(&mut hasher);
+                leaf.elements[..leaf.len].hash(&mut hasher);
+            }
+        }
+
+        for child in children.iter().flatten() {
+            child.address().hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// Compares the canonical node with the node, whose
+    /// children are replaced by the given canonical ones.
+    fn equals(canonical: &Node<T>, node: &Node<T>, children: &[Option<Node<T>>]) -> bool {
+        match (canonical, node) {
+            (Node::RelaxedBranch(ref a), Node::RelaxedBranch(ref b)) => {
+                a.sizes[..a.len] == b.sizes[..b.len]
+                    && same_children(canonical.children(), children)
+            }
+            (Node::Branch(..), Node::Branch(..)) => same_children(canonical.children(), children),
+            (Node::Leaf(ref a), Node::Leaf(ref b)) => a.elements[..a.len] == b.elements[..b.len],
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::dedup::DedupStats;
+    use super::super::{Node, RrbTree, BRANCH_FACTOR};
+    use super::NodeInterner;
+
+    fn create_tree(leaves: usize) -> RrbTree<usize> {
+        let mut tree = RrbTree::new();
+
+        for _ in 0..leaves {
+            let mut elements = new_branch!();
+            for (i, element) in elements.iter_mut().enumerate() {
+                *element = Some(i);
+            }
+
+            tree.push(elements, BRANCH_FACTOR);
+        }
+
+        tree
+    }
+
+    fn leaf_addresses(tree: &RrbTree<usize>) -> Vec<usize> {
+        fn walk(node: &Node<usize>, out: &mut Vec<usize>) {
+            match node {
+                Node::Leaf(..) => out.push(node.address()),
+                _ => node
+                    .children()
+                    .iter()
+                    .flatten()
+                    .for_each(|child| walk(child, out)),
+            }
+        }
+
+        let mut out = Vec::new();
+        if let Some(ref root) = tree.root {
+            walk(root, &mut out);
+        }
+
+        out
+    }
+
+    #[test]
+    fn dedup_must_share_equal_leaves_and_branches() {
+        let leaves = BRANCH_FACTOR * 2;
+
+        let mut left = create_tree(leaves);
+        let mut right = create_tree(leaves);
+
+        let mut interner = NodeInterner::new();
+        let mut stats = DedupStats::default();
+
+        interner.dedup(&mut left, &mut stats);
+        // all leaves but one, and the second of two equal branches
+        assert_eq!(stats.replaced_nodes, leaves);
+
+        let addresses = leaf_addresses(&left);
+        assert!(addresses.iter().all(|address| *address == addresses[0]));
+
+        interner.dedup(&mut right, &mut stats);
+        assert_eq!(leaf_addresses(&left), leaf_addresses(&right));
+        assert_eq!(left.root.unwrap().address(), right.root.unwrap().address());
+    }
+
+    #[test]
+    fn purge_must_drop_unreferenced_nodes() {
+        let mut tree = create_tree(BRANCH_FACTOR + 1);
+
+        let mut interner = NodeInterner::new();
+        interner.dedup(&mut tree, &mut DedupStats::default());
+
+        assert!(interner.len() > 0);
+
+        interner.purge();
+        assert!(interner.len() > 0);
+
+        drop(tree);
+        interner.purge();
+        assert_eq!(interner.len(), 0);
+    }
+}
@@ -1,6 +1,6 @@
 use super::{get_branch_index, Index, Leaf, Node, RrbTree, Shift, BRANCH_FACTOR};
 use super::{SharedPtr, Take};
-use std::fmt::Debug;
+use core::fmt::Debug;
 
 #[derive(Debug, Clone)]
 pub struct RrbTreeIter<T> {
@@ -0,0 +1,432 @@
+use super::super::merkle::{read_bytes, Element, ImportError, NodeHash};
+use super::super::sharedptr::WeakPtr;
+use super::SharedPtr;
+use super::{Branch, Index, Leaf, Node, RelaxedBranch, RrbTree, Shift};
+use super::{BITS_PER_LEVEL, BRANCH_FACTOR};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+
+const LEAF: u8 = 0;
+const BRANCH: u8 = 1;
+const RELAXED_BRANCH: u8 = 2;
+
+/// A weak reference to a node. It keeps the allocation of the node,
+/// so that its address is not reused, but not the node itself.
+/// A node that is modified in place while it is weakly referenced
+/// is moved to a new allocation, and the reference no longer upgrades.
+#[derive(Debug)]
+enum WeakNode<T> {
+    RelaxedBranch(WeakPtr<RelaxedBranch<T>>),
+    Branch(WeakPtr<Branch<T>>),
+    Leaf(WeakPtr<Leaf<T>>),
+}
+
+impl<T> WeakNode<T> {
+    fn new(node: &Node<T>) -> Self {
+        match node {
+            Node::RelaxedBranch(ref ptr) => WeakNode::RelaxedBranch(SharedPtr::downgrade(ptr)),
+            Node::Branch(ref ptr) => WeakNode::Branch(SharedPtr::downgrade(ptr)),
+            Node::Leaf(ref ptr) => WeakNode::Leaf(SharedPtr::downgrade(ptr)),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            WeakNode::RelaxedBranch(ref ptr) => ptr.strong_count() > 0,
+            WeakNode::Branch(ref ptr) => ptr.strong_count() > 0,
+            WeakNode::Leaf(ref ptr) => ptr.strong_count() > 0,
+        }
+    }
+
+    fn upgrade(&self) -> Option<Node<T>> {
+        match self {
+            WeakNode::RelaxedBranch(ref ptr) => ptr.upgrade().map(Node::RelaxedBranch),
+            WeakNode::Branch(ref ptr) => ptr.upgrade().map(Node::Branch),
+            WeakNode::Leaf(ref ptr) => ptr.upgrade().map(Node::Leaf),
+        }
+    }
+}
+
+/// Nodes hashed so far, along with their hashes. Hashed nodes are
+/// referenced weakly, so that vectors keep modifying their nodes in
+/// place, and a cached hash is used only while its node is alive.
+/// Inserted and imported nodes are also held by the table.
+#[derive(Debug)]
+pub struct NodeTable<T> {
+    by_address: HashMap<usize, (NodeHash, WeakNode<T>)>,
+    by_hash: HashMap<NodeHash, WeakNode<T>>,
+    held: HashMap<NodeHash, Node<T>>,
+    shapes: HashMap<NodeHash, (usize, usize)>,
+    pruned_len: usize,
+}
+
+/// The shape of the tree read from the input, before its nodes are imported.
+pub struct TreeHeader {
+    root: Option<NodeHash>,
+    root_len: usize,
+    shift: usize,
+}
+
+fn decode_hash(input: &mut &[u8]) -> Result<NodeHash, ImportError> {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(read_bytes(input, 16).ok_or(ImportError::Malformed)?);
+
+    Ok(NodeHash::from_bytes(bytes))
+}
+
+fn decode_len(input: &mut &[u8]) -> Result<usize, ImportError> {
+    match u8::decode(input) {
+        Some(len) if len as usize <= BRANCH_FACTOR && len > 0 => Ok(len as usize),
+        _ => Err(ImportError::Malformed),
+    }
+}
+
+impl<T: Clone + Debug + Element> NodeTable<T> {
+    pub fn new() -> Self {
+        NodeTable {
+            by_address: HashMap::new(),
+            by_hash: HashMap::new(),
+            held: HashMap::new(),
+            shapes: HashMap::new(),
+            pruned_len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_hash.values().filter(|node| node.is_alive()).count()
+    }
+
+    pub fn contains(&self, hash: NodeHash) -> bool {
+        self.by_hash.get(&hash).map_or(false, WeakNode::is_alive)
+    }
+
+    pub fn hashes(&self) -> HashSet<NodeHash> {
+        self.by_hash
+            .iter()
+            .filter(|(_, node)| node.is_alive())
+            .map(|(hash, _)| *hash)
+            .collect()
+    }
+
+    /// Returns the hash of the node, computing it for the
+    /// node and its descendants if it has not been cached.
+    fn hash(&mut self, node: &Node<T>) -> NodeHash {
+        if let Some((hash, cached)) = self.by_address.get(&node.address()) {
+            if cached.is_alive() {
+                return *hash;
+            }
+        }
+
+        let mut record = Vec::new();
+        self.encode_node(node, &mut record);
+
+        let hash = NodeHash::of(&record);
+        self.cache(hash, node);
+
+        hash
+    }
+
+    fn cache(&mut self, hash: NodeHash, node: &Node<T>) {
+        if self.by_address.len() >= 2 * self.pruned_len + BRANCH_FACTOR {
+            self.prune();
+        }
+
+        let alive = self.by_hash.get(&hash).map_or(false, WeakNode::is_alive);
+        if !alive {
+            self.by_hash.insert(hash, WeakNode::new(node));
+        }
+
+        self.by_address
+            .insert(node.address(), (hash, WeakNode::new(node)));
+    }
+
+    /// Drops the entries of released nodes, so that their allocations
+    /// are freed. It runs once the table doubles since the last run,
+    /// which keeps its cost amortized over the cached hashes.
+    fn prune(&mut self) {
+        self.by_address.retain(|_, (_, node)| node.is_alive());
+        self.by_hash.retain(|_, node| node.is_alive());
+
+        let by_hash = &self.by_hash;
+        self.shapes.retain(|hash, _| by_hash.contains_key(hash));
+
+        self.pruned_len = self.by_address.len();
+    }
+
+    /// Hashes the tree and holds its nodes in the table,
+    /// so that they stay available to imports.
+    pub fn hold_tree(&mut self, tree: &RrbTree<T>) {
+        if let Some(ref root) = tree.root {
+            self.hold(root);
+        }
+    }
+
+    fn hold(&mut self, node: &Node<T>) {
+        let hash = self.hash(node);
+
+        if !self.held.contains_key(&hash) {
+            self.hold_node(hash, node.clone());
+
+            for child in node.children().iter().flatten() {
+                self.hold(child);
+            }
+        }
+    }
+
+    fn hold_node(&mut self, hash: NodeHash, node: Node<T>) {
+        self.by_hash.insert(hash, WeakNode::new(&node));
+        self.held.insert(hash, node);
+    }
+
+    /// Writes the record of the node, which is also the input to its hash.
+    /// Leaves contain encoded elements, while branches refer to their
+    /// children by hashes. Relaxed branches contain sizes as well.
+    fn encode_node(&mut self, node: &Node<T>, out: &mut Vec<u8>) {
+        match node {
+            Node::RelaxedBranch(ref branch) => {
+                out.push(RELAXED_BRANCH);
+                out.push(branch.len as u8);
+
+                for size in branch.sizes[..branch.len].iter().flatten() {
+                    size.encode(out);
+                }
+            }
+            Node::Branch(ref branch) => {
+                out.push(BRANCH);
+                out.push(branch.len as u8);
+            }
+            Node::Leaf(ref leaf) => {
+                out.push(LEAF);
+                out.push(leaf.len as u8);
+
+                for item in leaf.elements[..leaf.len].iter().flatten() {
+                    item.encode(out);
+                }
+            }
+        }
+
+        for child in node.children().iter().flatten() {
+            out.extend_from_slice(&self.hash(child).to_bytes());
+        }
+    }
+
+    pub fn encode_tree_header(&mut self, tree: &RrbTree<T>, out: &mut Vec<u8>) {
+        tree.shift.0.encode(out);
+        tree.root_len.0.encode(out);
+
+        match tree.root {
+            Some(ref root) => {
+                out.push(1);
+                out.extend_from_slice(&self.hash(root).to_bytes());
+            }
+            None => out.push(0),
+        }
+    }
+
+    pub fn decode_tree_header(&self, input: &mut &[u8]) -> Result<TreeHeader, ImportError> {
+        let shift = usize::decode(input).ok_or(ImportError::Malformed)?;
+        let root_len = usize::decode(input).ok_or(ImportError::Malformed)?;
+
+        let root = match u8::decode(input) {
+            Some(0) => None,
+            Some(1) => Some(decode_hash(input)?),
+            _ => return Err(ImportError::Malformed),
+        };
+
+        Ok(TreeHeader {
+            root,
+            root_len,
+            shift,
+        })
+    }
+
+    /// Writes the records of nodes missing in the `known` set, children
+    /// before parents, so that they can be rebuilt in a single pass.
+    pub fn export_nodes(
+        &mut self,
+        tree: &RrbTree<T>,
+        known: &HashSet<NodeHash>,
+        out: &mut Vec<u8>,
+    ) {
+        let mut records = Vec::new();
+        let mut count = 0;
+
+        if let Some(ref root) = tree.root {
+            let mut visited = HashSet::new();
+            self.export_node(root, known, &mut visited, &mut records, &mut count);
+        }
+
+        count.encode(out);
+        out.extend(records);
+    }
+
+    fn export_node(
+        &mut self,
+        node: &Node<T>,
+        known: &HashSet<NodeHash>,
+        visited: &mut HashSet<NodeHash>,
+        out: &mut Vec<u8>,
+        count: &mut usize,
+    ) {
+        let hash = self.hash(node);
+
+        if known.contains(&hash) || !visited.insert(hash) {
+            return;
+        }
+
+        for child in node.children().iter().flatten() {
+            self.export_node(child, known, visited, out, count);
+        }
+
+        self.encode_node(node, out);
+        *count += 1;
+    }
+
+    fn import_node(&mut self, input: &mut &[u8]) -> Result<(), ImportError> {
+        let record = *input;
+
+        let tag = u8::decode(input).ok_or(ImportError::Malformed)?;
+        let len = decode_len(input)?;
+
+        let node = match tag {
+            RELAXED_BRANCH => {
+                let mut branch = RelaxedBranch::new();
+                let mut sizes = [0; BRANCH_FACTOR];
+
+                for size in sizes.iter_mut().take(len) {
+                    *size = usize::decode(input).ok_or(ImportError::Malformed)?;
+                }
+
+                for size in sizes.iter().take(len) {
+                    branch.add(Some(self.lookup(decode_hash(input)?)?), Some(*size));
+                }
+
+                Node::RelaxedBranch(SharedPtr::new(branch))
+            }
+            BRANCH => {
+                let mut branch = Branch::new();
+
+                for _ in 0..len {
+                    branch.add(Some(self.lookup(decode_hash(input)?)?));
+                }
+
+                Node::Branch(SharedPtr::new(branch))
+            }
+            LEAF => {
+                let mut leaf = Leaf::new();
+
+                for _ in 0..len {
+                    leaf.add(Some(T::decode(input).ok_or(ImportError::Malformed)?));
+                }
+
+                Node::Leaf(SharedPtr::new(leaf))
+            }
+            _ => return Err(ImportError::Malformed),
+        };
+
+        let hash = NodeHash::of(&record[..record.len() - input.len()]);
+        self.cache(hash, &node);
+        self.hold_node(hash, node);
+
+        Ok(())
+    }
+
+    fn lookup(&self, hash: NodeHash) -> Result<Node<T>, ImportError> {
+        self.by_hash
+            .get(&hash)
+            .and_then(WeakNode::upgrade)
+            .ok_or(ImportError::MissingNode(hash))
+    }
+
+    /// Reads the records of nodes and rebuilds the tree with the given shape.
+    pub fn import_nodes(
+        &mut self,
+        header: TreeHeader,
+        input: &mut &[u8],
+    ) -> Result<RrbTree<T>, ImportError> {
+        let count = usize::decode(input).ok_or(ImportError::Malformed)?;
+
+        for _ in 0..count {
+            self.import_node(input)?;
+        }
+
+        let root = match header.root {
+            Some(hash) => Some(self.lookup(hash)?),
+            None => None,
+        };
+
+        let len = match root {
+            Some(ref root) => self.validate(root, header.shift),
+            None if header.shift == 0 => Some(0),
+            None => None,
+        };
+
+        if len != Some(header.root_len) {
+            return Err(ImportError::Malformed);
+        }
+
+        Ok(RrbTree {
+            root,
+            root_len: Index(header.root_len),
+            shift: Shift(header.shift),
+        })
+    }
+
+    /// Checks that the subtree is a valid tree of the given height,
+    /// and returns the number of elements in it. The shapes of
+    /// checked nodes are remembered, so that shared subtrees
+    /// are checked only once.
+    fn validate(&mut self, node: &Node<T>, shift: usize) -> Option<usize> {
+        if shift % BITS_PER_LEVEL != 0 {
+            return None;
+        }
+
+        let hash = self.hash(node);
+
+        if let Some((checked_shift, len)) = self.shapes.get(&hash) {
+            return if *checked_shift == shift {
+                Some(*len)
+            } else {
+                None
+            };
+        }
+
+        let len = match node {
+            Node::Leaf(ref leaf) if shift == 0 => leaf.len,
+            Node::Branch(ref branch) if shift >= BITS_PER_LEVEL => {
+                let width = 1usize.checked_shl(shift as u32)?;
+                let mut len = 0;
+
+                for (i, child) in branch.children[..branch.len].iter().flatten().enumerate() {
+                    let child_len = self.validate(child, shift - BITS_PER_LEVEL)?;
+
+                    if child_len != width && i + 1 != branch.len {
+                        return None;
+                    }
+
+                    len += child_len;
+                }
+
+                len
+            }
+            Node::RelaxedBranch(ref branch) if shift >= BITS_PER_LEVEL => {
+                let mut len = 0;
+
+                for i in 0..branch.len {
+                    let child = branch.children[i].as_ref()?;
+                    len += self.validate(child, shift - BITS_PER_LEVEL)?;
+
+                    if branch.sizes[i] != Some(len) {
+                        return None;
+                    }
+                }
+
+                len
+            }
+            _ => return None,
+        };
+
+        self.shapes.insert(hash, (shift, len));
+        Some(len)
+    }
+}
@@ -1,7 +1,10 @@
+use super::measure::Measure;
 use super::sharedptr::{SharedPtr, Take};
-use std::cmp;
-use std::fmt::Debug;
-use std::mem;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt::Debug;
+use core::mem;
 
 #[cfg(not(feature = "small_branch"))]
 pub const BRANCH_FACTOR: usize = 32;
@@ -84,55 +87,122 @@ impl Index {
     }
 }
 
+/// A summary cached in a node. It is derived from the elements
+/// of the subtree, hence it is not taken into account when the
+/// nodes are compared.
+#[derive(Debug, Clone)]
+struct Cached<S>(S);
+
+impl<S> PartialEq for Cached<S> {
+    #[inline(always)]
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<S> Eq for Cached<S> {}
+
+impl<S> PartialOrd for Cached<S> {
+    #[inline(always)]
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> Ord for Cached<S> {
+    #[inline(always)]
+    fn cmp(&self, _other: &Self) -> cmp::Ordering {
+        cmp::Ordering::Equal
+    }
+}
+
+// The projection is hidden behind an alias, so that the derived
+// traits of nodes do not require summaries to implement them.
+type CachedSummary<T, M> = Cached<<M as Measure<T>>::Summary>;
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct RelaxedBranch<T> {
-    children: [Option<Node<T>>; BRANCH_FACTOR],
+struct RelaxedBranch<T, M: Measure<T> = ()> {
+    children: [Option<Node<T, M>>; BRANCH_FACTOR],
     sizes: [Option<usize>; BRANCH_FACTOR],
     len: usize,
+    summary: CachedSummary<T, M>,
 }
 
 #[derive(Debug)]
-struct BranchBuilder<T> {
-    children: [Option<Node<T>>; BRANCH_FACTOR],
+struct BranchBuilder<T, M: Measure<T> = ()> {
+    children: [Option<Node<T, M>>; BRANCH_FACTOR],
     is_relaxed: bool,
     shift: Shift,
     len: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Branch<T> {
-    children: [Option<Node<T>>; BRANCH_FACTOR],
+struct Branch<T, M: Measure<T> = ()> {
+    children: [Option<Node<T, M>>; BRANCH_FACTOR],
     len: usize,
+    summary: CachedSummary<T, M>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Leaf<T> {
+struct Leaf<T, M: Measure<T> = ()> {
     elements: [Option<T>; BRANCH_FACTOR],
     len: usize,
+    summary: CachedSummary<T, M>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum Node<T> {
-    RelaxedBranch(SharedPtr<RelaxedBranch<T>>),
-    Branch(SharedPtr<Branch<T>>),
-    Leaf(SharedPtr<Leaf<T>>),
+enum Node<T, M: Measure<T> = ()> {
+    RelaxedBranch(SharedPtr<RelaxedBranch<T, M>>),
+    Branch(SharedPtr<Branch<T, M>>),
+    Leaf(SharedPtr<Leaf<T, M>>),
 }
 
-impl<T: Clone + Debug> Leaf<T> {
+impl<T: Clone + Debug, M: Measure<T>> Leaf<T, M> {
     #[inline(always)]
     fn new() -> Self {
         Leaf {
             elements: new_branch!(),
             len: 0,
+            summary: Cached(M::identity()),
         }
     }
 
+    #[inline(always)]
+    fn with_elements(elements: [Option<T>; BRANCH_FACTOR], len: usize) -> Self {
+        let mut leaf = Leaf {
+            elements,
+            len,
+            summary: Cached(M::identity()),
+        };
+
+        leaf.refresh();
+        leaf
+    }
+
     #[inline(always)]
     fn add(&mut self, element: Option<T>) {
+        if let Some(ref item) = element {
+            self.summary.0 = M::combine(&self.summary.0, &M::measure(item));
+        }
+
         self.elements[self.len] = element;
         self.len += 1;
     }
 
+    #[inline(always)]
+    fn refresh(&mut self) {
+        if M::is_trivial() {
+            return;
+        }
+
+        self.summary.0 = self.elements[..self.len]
+            .iter()
+            .flatten()
+            .fold(M::identity(), |acc, item| {
+                M::combine(&acc, &M::measure(item))
+            });
+    }
+
     #[inline(always)]
     fn take(&mut self, i: usize) -> Option<T> {
         self.len -= 1;
@@ -150,7 +220,7 @@ impl<T: Clone + Debug> Leaf<T> {
     }
 
     #[inline(always)]
-    fn merge(&mut self, mut that: Leaf<T>) -> Node<T> {
+    fn merge(&mut self, mut that: Leaf<T, M>) -> Node<T, M> {
         let mut leaf_l = Leaf::new();
         let mut leaf_r = Leaf::new();
 
@@ -178,45 +248,41 @@ impl<T: Clone + Debug> Leaf<T> {
         }
 
         if leaf_l.is_full() && leaf_r.is_full() {
-            let mut children = new_branch!();
-            children[0] = Some(Node::Leaf(SharedPtr::new(leaf_l)));
-            children[1] = Some(Node::Leaf(SharedPtr::new(leaf_r)));
+            let mut branch = Branch::new();
+            branch.add(Some(Node::Leaf(SharedPtr::new(leaf_l))));
+            branch.add(Some(Node::Leaf(SharedPtr::new(leaf_r))));
 
-            Node::Branch(SharedPtr::new(Branch { children, len: 2 }))
+            Node::Branch(SharedPtr::new(branch))
         } else {
-            let mut sizes = new_branch!();
-            sizes[0] = Some(leaf_l.len);
-            sizes[1] = Some(leaf_l.len + leaf_r.len);
+            let size_l = leaf_l.len;
+            let size_r = leaf_l.len + leaf_r.len;
 
-            let mut children = new_branch!();
-            children[0] = Some(Node::Leaf(SharedPtr::new(leaf_l)));
-            children[1] = Some(Node::Leaf(SharedPtr::new(leaf_r)));
+            let mut branch = RelaxedBranch::new();
+            branch.add(Some(Node::Leaf(SharedPtr::new(leaf_l))), Some(size_l));
+            branch.add(Some(Node::Leaf(SharedPtr::new(leaf_r))), Some(size_r));
 
-            Node::RelaxedBranch(SharedPtr::new(RelaxedBranch {
-                children,
-                sizes,
-                len: 2,
-            }))
+            Node::RelaxedBranch(SharedPtr::new(branch))
         }
     }
 
     #[inline(always)]
-    fn split_off(&mut self, index: Index, shift: Shift) -> Node<T> {
+    fn split_off(&mut self, index: Index, shift: Shift) -> Node<T, M> {
         let mut leaf = Leaf::new();
 
         for i in index.child(shift)..self.len {
             leaf.add(self.take(i));
         }
 
+        self.refresh();
         Node::Leaf(SharedPtr::new(leaf))
     }
 
     #[inline(always)]
-    fn rebalance(merged: Vec<Node<T>>, shift: Shift) -> Node<T> {
+    fn rebalance(merged: Vec<Node<T, M>>, shift: Shift) -> Node<T, M> {
         #[inline(always)]
-        fn check_subtree<P: Clone + Debug>(
-            root: &mut BranchBuilder<P>,
-            subtree: &mut BranchBuilder<P>,
+        fn check_subtree<P: Clone + Debug, Q: Measure<P>>(
+            root: &mut BranchBuilder<P, Q>,
+            subtree: &mut BranchBuilder<P, Q>,
         ) {
             if subtree.is_full() {
                 root.push(subtree.build());
@@ -263,7 +329,7 @@ impl<T: Clone + Debug> Leaf<T> {
     }
 }
 
-impl<T: Clone + Debug> BranchBuilder<T> {
+impl<T: Clone + Debug, M: Measure<T>> BranchBuilder<T, M> {
     #[inline(always)]
     fn new(shift: Shift) -> Self {
         BranchBuilder {
@@ -275,25 +341,36 @@ impl<T: Clone + Debug> BranchBuilder<T> {
     }
 
     #[inline(always)]
-    fn build(&mut self) -> Node<T> {
+    fn build(&mut self) -> Node<T, M> {
         let is_relaxed = mem::replace(&mut self.is_relaxed, false);
         let children = mem::replace(&mut self.children, new_branch!());
         let len = mem::replace(&mut self.len, 0);
 
         if is_relaxed {
             let sizes = BranchBuilder::compute_sizes(&children, self.shift, len);
-            Node::RelaxedBranch(SharedPtr::new(RelaxedBranch {
+            let mut branch = RelaxedBranch {
                 children,
                 sizes,
                 len,
-            }))
+                summary: Cached(M::identity()),
+            };
+
+            branch.refresh();
+            Node::RelaxedBranch(SharedPtr::new(branch))
         } else {
-            Node::Branch(SharedPtr::new(Branch { children, len }))
+            let mut branch = Branch {
+                children,
+                len,
+                summary: Cached(M::identity()),
+            };
+
+            branch.refresh();
+            Node::Branch(SharedPtr::new(branch))
         }
     }
 
     #[inline(always)]
-    fn push(&mut self, node: Node<T>) {
+    fn push(&mut self, node: Node<T, M>) {
         let node_is_not_fully_dense = node.is_relaxed_branch() || !node.is_full();
 
         self.is_relaxed = self.is_relaxed || node_is_not_fully_dense;
@@ -302,7 +379,7 @@ impl<T: Clone + Debug> BranchBuilder<T> {
     }
 
     #[inline(always)]
-    fn give(&mut self, child: Option<Node<T>>) {
+    fn give(&mut self, child: Option<Node<T, M>>) {
         if let Some(ref node) = child.as_ref() {
             let node_is_not_fully_dense = node.is_relaxed_branch() || !node.is_full();
 
@@ -324,7 +401,7 @@ impl<T: Clone + Debug> BranchBuilder<T> {
 
     #[inline(always)]
     fn compute_sizes(
-        children: &[Option<Node<T>>; BRANCH_FACTOR],
+        children: &[Option<Node<T, M>>; BRANCH_FACTOR],
         shift: Shift,
         len: usize,
     ) -> [Option<usize>; BRANCH_FACTOR] {
@@ -339,7 +416,7 @@ impl<T: Clone + Debug> BranchBuilder<T> {
         size_table
     }
 
-    fn size_sub_trie(node: &Node<T>, shift: Shift) -> usize {
+    fn size_sub_trie(node: &Node<T, M>, shift: Shift) -> usize {
         match node {
             Node::Branch(ref branch) => {
                 let last_size = BranchBuilder::size_sub_trie(
@@ -360,11 +437,11 @@ impl<T: Clone + Debug> BranchBuilder<T> {
     }
 
     #[inline(always)]
-    fn rebalance(merged: Vec<Node<T>>, shift: Shift) -> Node<T> {
+    fn rebalance(merged: Vec<Node<T, M>>, shift: Shift) -> Node<T, M> {
         #[inline(always)]
-        fn check_subtree<P: Clone + Debug>(
-            root: &mut BranchBuilder<P>,
-            subtree: &mut BranchBuilder<P>,
+        fn check_subtree<P: Clone + Debug, Q: Measure<P>>(
+            root: &mut BranchBuilder<P, Q>,
+            subtree: &mut BranchBuilder<P, Q>,
         ) {
             if subtree.is_full() {
                 root.push(subtree.build());
@@ -408,29 +485,43 @@ impl<T: Clone + Debug> BranchBuilder<T> {
     }
 }
 
-impl<T: Clone + Debug> Branch<T> {
+impl<T: Clone + Debug, M: Measure<T>> Branch<T, M> {
     #[inline(always)]
     fn new() -> Self {
         Branch {
             children: new_branch!(),
             len: 0,
+            summary: Cached(M::identity()),
         }
     }
 
     #[inline(always)]
-    fn add(&mut self, child: Option<Node<T>>) {
+    fn add(&mut self, child: Option<Node<T, M>>) {
+        if let Some(ref node) = child {
+            self.summary.0 = M::combine(&self.summary.0, node.summary());
+        }
+
         self.children[self.len] = child;
         self.len += 1;
     }
 
     #[inline(always)]
-    fn take(&mut self, i: usize) -> Option<Node<T>> {
+    fn refresh(&mut self) {
+        if M::is_trivial() {
+            return;
+        }
+
+        self.summary.0 = Node::summarize(&self.children[..self.len]);
+    }
+
+    #[inline(always)]
+    fn take(&mut self, i: usize) -> Option<Node<T, M>> {
         self.len -= 1;
         self.children[i].take()
     }
 
     #[inline(always)]
-    fn push_leaf(&mut self, index: Index, shift: Shift, leaf: Leaf<T>) {
+    fn push_leaf(&mut self, index: Index, shift: Shift, leaf: Leaf<T, M>) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
 
         let mut branch = self;
@@ -439,16 +530,15 @@ impl<T: Clone + Debug> Branch<T> {
         while shift.0 > BITS_PER_LEVEL {
             let i = index.child(shift);
 
+            branch.summary.0 = M::combine(&branch.summary.0, &leaf.summary.0);
+
             let child = &mut branch.children[i];
             let len = &mut branch.len;
 
             let node = child.get_or_insert_with(|| {
                 *len += 1;
 
-                Node::Branch(SharedPtr::new(Branch {
-                    children: new_branch!(),
-                    len: 0,
-                }))
+                Node::Branch(SharedPtr::new(Branch::new()))
             });
 
             branch = SharedPtr::make_mut(node.as_mut_branch());
@@ -457,12 +547,13 @@ impl<T: Clone + Debug> Branch<T> {
 
         debug_assert_eq!(shift.0, BITS_PER_LEVEL);
 
+        branch.summary.0 = M::combine(&branch.summary.0, &leaf.summary.0);
         branch.len += 1;
         branch.children[index.child(shift)] = Some(Node::Leaf(SharedPtr::new(leaf)));
     }
 
     #[inline(always)]
-    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T>, usize) {
+    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T, M>, usize) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
 
         let index = self.len - 1;
@@ -473,6 +564,7 @@ impl<T: Clone + Debug> Branch<T> {
             let leaf_node = self.children[index].take().unwrap();
             let leaf = leaf_node.into_leaf().take();
 
+            self.refresh();
             (leaf, self.len)
         } else {
             let (leaf, child_len) = self.children[index]
@@ -485,6 +577,7 @@ impl<T: Clone + Debug> Branch<T> {
                 self.children[index] = None;
             }
 
+            self.refresh();
             (leaf, self.len)
         }
     }
@@ -494,17 +587,19 @@ impl<T: Clone + Debug> Branch<T> {
         for i in index..self.len {
             self.take(i);
         }
+
+        self.refresh();
     }
 }
 
-impl<T: Clone + Debug> RelaxedBranch<T> {
+impl<T: Clone + Debug, M: Measure<T>> RelaxedBranch<T, M> {
     #[inline(always)]
     fn push_leaf(
         &mut self,
         index: Index,
         shift: Shift,
         shift_new_branch: Option<Shift>,
-        leaf: Leaf<T>,
+        leaf: Leaf<T, M>,
     ) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
         debug_assert!(self.len > 0);
@@ -524,6 +619,8 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
 
             shift = shift.dec();
 
+            branch.summary.0 = M::combine(&branch.summary.0, &leaf.summary.0);
+
             let len = &mut branch.len;
             let child_node = &mut branch.children[branch_index];
             let child_node_size = branch.sizes[branch_index];
@@ -540,10 +637,7 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
             let node = child_node.get_or_insert_with(|| {
                 *len += 1;
 
-                Node::Branch(SharedPtr::new(Branch {
-                    children: new_branch!(),
-                    len: 0,
-                }))
+                Node::Branch(SharedPtr::new(Branch::new()))
             });
 
             if branch_index != 0 {
@@ -570,12 +664,13 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
             branch.sizes[branch_index] = Some(branch.sizes[branch_index - 1].unwrap() + leaf.len);
         }
 
+        branch.summary.0 = M::combine(&branch.summary.0, &leaf.summary.0);
         branch.len += 1;
         branch.children[branch_index] = Some(Node::Leaf(SharedPtr::new(leaf)));
     }
 
     #[inline(always)]
-    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T>, usize) {
+    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T, M>, usize) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
 
         let index = self.len - 1;
@@ -589,6 +684,7 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
             let size = self.sizes[index].as_mut().unwrap();
             *size -= leaf.len;
 
+            self.refresh();
             (leaf, self.len)
         } else {
             let (leaf, child_len) = self.children[index]
@@ -605,6 +701,7 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
                 self.sizes[index] = None;
             }
 
+            self.refresh();
             (leaf, self.len)
         }
     }
@@ -615,18 +712,32 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
             children: new_branch!(),
             sizes: new_branch!(),
             len: 0,
+            summary: Cached(M::identity()),
         }
     }
 
     #[inline(always)]
-    fn add(&mut self, child: Option<Node<T>>, size: Option<usize>) {
+    fn refresh(&mut self) {
+        if M::is_trivial() {
+            return;
+        }
+
+        self.summary.0 = Node::summarize(&self.children[..self.len]);
+    }
+
+    #[inline(always)]
+    fn add(&mut self, child: Option<Node<T, M>>, size: Option<usize>) {
+        if let Some(ref node) = child {
+            self.summary.0 = M::combine(&self.summary.0, node.summary());
+        }
+
         self.children[self.len] = child;
         self.sizes[self.len] = size;
         self.len += 1;
     }
 
     #[inline(always)]
-    fn take(&mut self, i: usize) -> (Option<Node<T>>, Option<usize>) {
+    fn take(&mut self, i: usize) -> (Option<Node<T, M>>, Option<usize>) {
         self.len -= 1;
         (self.children[i].take(), self.sizes[i].take())
     }
@@ -636,10 +747,29 @@ impl<T: Clone + Debug> RelaxedBranch<T> {
         for i in index..self.len {
             self.take(i);
         }
+
+        self.refresh();
     }
 }
 
-impl<T: Clone + Debug> Node<T> {
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    #[inline(always)]
+    fn summary(&self) -> &M::Summary {
+        match self {
+            Node::Branch(ref node) => &node.summary.0,
+            Node::RelaxedBranch(ref node) => &node.summary.0,
+            Node::Leaf(ref leaf) => &leaf.summary.0,
+        }
+    }
+
+    #[inline(always)]
+    fn summarize(nodes: &[Option<Node<T, M>>]) -> M::Summary {
+        nodes
+            .iter()
+            .flatten()
+            .fold(M::identity(), |acc, node| M::combine(&acc, node.summary()))
+    }
+
     #[inline(always)]
     fn len(&self) -> usize {
         match self {
@@ -654,6 +784,47 @@ impl<T: Clone + Debug> Node<T> {
         self.len() == BRANCH_FACTOR
     }
 
+    #[inline(always)]
+    fn address(&self) -> usize {
+        match self {
+            Node::RelaxedBranch(ref ptr) => SharedPtr::as_ptr(ptr) as usize,
+            Node::Branch(ref ptr) => SharedPtr::as_ptr(ptr) as usize,
+            Node::Leaf(ref ptr) => SharedPtr::as_ptr(ptr) as usize,
+        }
+    }
+
+    #[inline(always)]
+    fn children(&self) -> &[Option<Node<T, M>>] {
+        match self {
+            Node::RelaxedBranch(ref branch) => &branch.children[..branch.len],
+            Node::Branch(ref branch) => &branch.children[..branch.len],
+            Node::Leaf(..) => &[],
+        }
+    }
+
+    #[inline(always)]
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::RelaxedBranch(ref ptr) => SharedPtr::strong_count(ptr),
+            Node::Branch(ref ptr) => SharedPtr::strong_count(ptr),
+            Node::Leaf(ref ptr) => SharedPtr::strong_count(ptr),
+        }
+    }
+
+    #[inline(always)]
+    fn is_shared(&self) -> bool {
+        self.strong_count() > 1
+    }
+
+    fn is_unique_subtree(&self) -> bool {
+        !self.is_shared()
+            && self
+                .children()
+                .iter()
+                .flatten()
+                .all(Node::is_unique_subtree)
+    }
+
     #[inline(always)]
     fn is_leaf(&self) -> bool {
         match self {
@@ -673,7 +844,7 @@ impl<T: Clone + Debug> Node<T> {
     }
 
     #[inline(always)]
-    fn as_mut_children(&mut self) -> &mut [Option<Node<T>>] {
+    fn as_mut_children(&mut self) -> &mut [Option<Node<T, M>>] {
         match self {
             Node::Branch(ref mut node) => {
                 let branch = SharedPtr::make_mut(node);
@@ -739,7 +910,7 @@ impl<T: Clone + Debug> Node<T> {
         (shift_has_enough_capacity, Some(shift_new_branch))
     }
 
-    fn merge(&mut self, mut that: Node<T>, self_shift: Shift, that_shift: Shift) -> Node<T> {
+    fn merge(&mut self, mut that: Node<T, M>, self_shift: Shift, that_shift: Shift) -> Node<T, M> {
         if self_shift > that_shift {
             let branch_l = self.as_mut_children();
 
@@ -796,17 +967,17 @@ impl<T: Clone + Debug> Node<T> {
 
     #[inline(always)]
     fn merge_all(
-        node_l: Option<&mut [Option<Node<T>>]>,
-        node_c: Option<&mut [Option<Node<T>>]>,
-        node_r: Option<&mut [Option<Node<T>>]>,
-    ) -> Vec<Node<T>> {
+        node_l: Option<&mut [Option<Node<T, M>>]>,
+        node_c: Option<&mut [Option<Node<T, M>>]>,
+        node_r: Option<&mut [Option<Node<T, M>>]>,
+    ) -> Vec<Node<T, M>> {
         let mut merged = Vec::with_capacity(
             node_l.as_ref().map_or(0, |it| it.len())
                 + node_c.as_ref().map_or(0, |it| it.len())
                 + node_r.as_ref().map_or(0, |it| it.len()),
         );
 
-        let mut merge_nodes = |mut node: Option<&mut [Option<Node<T>>]>| {
+        let mut merge_nodes = |mut node: Option<&mut [Option<Node<T, M>>]>| {
             if let Some(items) = node.as_mut() {
                 for item in items.iter_mut() {
                     merged.push(item.take().unwrap());
@@ -822,11 +993,11 @@ impl<T: Clone + Debug> Node<T> {
     }
 
     fn rebalance(
-        node_l: Option<&mut [Option<Node<T>>]>,
-        node_c: Option<&mut [Option<Node<T>>]>,
-        node_r: Option<&mut [Option<Node<T>>]>,
+        node_l: Option<&mut [Option<Node<T, M>>]>,
+        node_c: Option<&mut [Option<Node<T, M>>]>,
+        node_r: Option<&mut [Option<Node<T, M>>]>,
         shift: Shift,
-    ) -> Node<T> {
+    ) -> Node<T, M> {
         let merged = Node::merge_all(node_l, node_c, node_r);
 
         if shift.is_level_with_leaves() {
@@ -837,9 +1008,9 @@ impl<T: Clone + Debug> Node<T> {
     }
 }
 
-impl<T: Clone + Debug> Node<T> {
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
     #[inline(always)]
-    fn as_mut_branch(&mut self) -> &mut SharedPtr<Branch<T>> {
+    fn as_mut_branch(&mut self) -> &mut SharedPtr<Branch<T, M>> {
         if let Node::Branch(ref mut branch_arc) = self {
             branch_arc
         } else {
@@ -848,7 +1019,7 @@ impl<T: Clone + Debug> Node<T> {
     }
 
     #[inline(always)]
-    fn as_mut_relaxed_branch(&mut self) -> &mut SharedPtr<RelaxedBranch<T>> {
+    fn as_mut_relaxed_branch(&mut self) -> &mut SharedPtr<RelaxedBranch<T, M>> {
         if let Node::RelaxedBranch(ref mut branch_arc) = self {
             branch_arc
         } else {
@@ -857,7 +1028,7 @@ impl<T: Clone + Debug> Node<T> {
     }
 
     #[inline(always)]
-    fn as_mut_leaf(&mut self) -> &mut SharedPtr<Leaf<T>> {
+    fn as_mut_leaf(&mut self) -> &mut SharedPtr<Leaf<T, M>> {
         if let Node::Leaf(ref mut leaf_arc) = self {
             leaf_arc
         } else {
@@ -866,7 +1037,7 @@ impl<T: Clone + Debug> Node<T> {
     }
 
     #[inline(always)]
-    fn into_leaf(self) -> SharedPtr<Leaf<T>> {
+    fn into_leaf(self) -> SharedPtr<Leaf<T, M>> {
         if let Node::Leaf(leaf_arc) = self {
             leaf_arc
         } else {
@@ -875,8 +1046,14 @@ impl<T: Clone + Debug> Node<T> {
     }
 }
 
-impl<T: Clone + Debug> Node<T> {
-    fn push(&mut self, index: Index, shift: Shift, shift_new_branch: Option<Shift>, leaf: Leaf<T>) {
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    fn push(
+        &mut self,
+        index: Index,
+        shift: Shift,
+        shift_new_branch: Option<Shift>,
+        leaf: Leaf<T, M>,
+    ) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
 
         match self {
@@ -890,11 +1067,11 @@ impl<T: Clone + Debug> Node<T> {
         }
     }
 
-    fn pop(&mut self, shift: Shift) -> Leaf<T> {
+    fn pop(&mut self, shift: Shift) -> Leaf<T, M> {
         self.pop_leaf(shift).0
     }
 
-    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T>, usize) {
+    fn pop_leaf(&mut self, shift: Shift) -> (Leaf<T, M>, usize) {
         debug_assert!(shift.0 >= BITS_PER_LEVEL);
 
         match self {
@@ -906,7 +1083,13 @@ impl<T: Clone + Debug> Node<T> {
         }
     }
 
+    #[inline(always)]
     fn get(&self, index: Index, shift: Shift) -> Option<&T> {
+        let (leaf, element) = self.get_leaf(index, shift);
+        leaf.elements[element].as_ref()
+    }
+
+    fn get_leaf(&self, index: Index, shift: Shift) -> (&Leaf<T, M>, usize) {
         let mut node = self;
         let mut shift = shift;
         let mut idx = index;
@@ -935,13 +1118,21 @@ impl<T: Clone + Debug> Node<T> {
                 Node::Leaf(ref leaf) => {
                     debug_assert_eq!(shift.0, 0);
 
-                    return leaf.elements[idx.element()].as_ref();
+                    return (leaf, idx.element());
                 }
             }
         }
     }
 
     fn get_mut(&mut self, index: Index, shift: Shift) -> Option<&mut T> {
+        let (leaf, element) = self.get_leaf_mut(index, shift);
+        leaf.elements[element].as_mut()
+    }
+
+    /// Returns the leaf holding the given index, copying the nodes
+    /// on the path to it if they are shared, along with the position
+    /// of the element within the leaf.
+    fn get_leaf_mut(&mut self, index: Index, shift: Shift) -> (&mut Leaf<T, M>, usize) {
         let mut node = self;
         let mut shift = shift;
         let mut idx = index;
@@ -974,24 +1165,23 @@ impl<T: Clone + Debug> Node<T> {
                 Node::Leaf(ref mut leaf_arc) => {
                     debug_assert_eq!(shift.0, 0);
 
-                    let leaf = SharedPtr::make_mut(leaf_arc);
-                    return leaf.elements[idx.element()].as_mut();
+                    return (SharedPtr::make_mut(leaf_arc), idx.element());
                 }
             }
         }
     }
 }
 
-type SubTree<T> = (Node<T>, Shift);
+type SubTree<T, M> = (Node<T, M>, Shift);
 
-impl<T: Clone + Debug> Node<T> {
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
     fn split_off(
         mut self,
         shift: Shift,
         index: Index,
         has_left: bool,
         has_right: bool,
-    ) -> (SubTree<T>, SubTree<T>) {
+    ) -> (SubTree<T, M>, SubTree<T, M>) {
         match &mut self {
             Node::Leaf(ptr) => {
                 let right = (SharedPtr::make_mut(ptr).split_off(index, shift), shift);
@@ -1056,6 +1246,7 @@ impl<T: Clone + Debug> Node<T> {
                         root.len += 1;
                     }
 
+                    root.refresh();
                     (Node::RelaxedBranch(SharedPtr::new(root)), shift)
                 };
 
@@ -1141,13 +1332,14 @@ impl<T: Clone + Debug> Node<T> {
                         root.len += 1;
                     }
 
+                    root.refresh();
                     (Node::RelaxedBranch(SharedPtr::new(root)), shift)
                 };
 
                 let left = if subidx == 0 {
                     if has_left {
                         let mut branch = RelaxedBranch::new();
-                        branch.add(Some(left_node), Some(index.0 + 1));
+                        branch.add(Some(left_node), Some(index.0));
 
                         let node = Node::RelaxedBranch(SharedPtr::new(branch));
                         (node, shift)
@@ -1156,7 +1348,7 @@ impl<T: Clone + Debug> Node<T> {
                     }
                 } else {
                     branch.split_right_at(subidx);
-                    branch.add(Some(left_node), Some(index.0 + 1));
+                    branch.add(Some(left_node), Some(index.0));
 
                     (self, shift)
                 };
@@ -1165,16 +1357,137 @@ impl<T: Clone + Debug> Node<T> {
             }
         }
     }
+
+    /// Cuts the subtree of `len` elements at every index, which must be
+    /// sorted, distinct and strictly inside of the subtree. The pieces are
+    /// returned at the level of the subtree, while the children in between
+    /// the indices are moved into them without being visited.
+    fn split_at_many(self, shift: Shift, len: usize, indices: &[usize]) -> Vec<Node<T, M>> {
+        if indices.is_empty() {
+            return vec![self];
+        }
+
+        let children: Vec<(Node<T, M>, usize)> = match self {
+            Node::Leaf(mut ptr) => {
+                let leaf = SharedPtr::make_mut(&mut ptr);
+
+                let mut pieces: Vec<Node<T, M>> = indices
+                    .iter()
+                    .rev()
+                    .map(|&index| leaf.split_off(Index(index), shift))
+                    .collect();
+
+                pieces.push(Node::Leaf(ptr));
+                pieces.reverse();
+
+                return pieces;
+            }
+            Node::Branch(mut ptr) => {
+                let branch = SharedPtr::make_mut(&mut ptr);
+
+                (0..branch.len)
+                    .map(|i| {
+                        let size = cmp::min(1 << shift.0, len - (i << shift.0));
+                        (branch.children[i].take().unwrap(), size)
+                    })
+                    .collect()
+            }
+            Node::RelaxedBranch(mut ptr) => {
+                let branch = SharedPtr::make_mut(&mut ptr);
+                let mut start = 0;
+
+                (0..branch.len)
+                    .map(|i| {
+                        let end = branch.sizes[i].unwrap();
+                        let size = end - mem::replace(&mut start, end);
+
+                        (branch.children[i].take().unwrap(), size)
+                    })
+                    .collect()
+            }
+        };
+
+        let subshift = shift.dec();
+        let mut indices = indices.iter().peekable();
+
+        let mut pieces = vec![Vec::new()];
+        let mut start = 0;
+
+        for (child, child_len) in children {
+            let end = start + child_len;
+
+            // an index at the boundary of children starts a new piece
+            // without touching either of them
+            if indices.next_if_eq(&&start).is_some() {
+                pieces.push(Vec::new());
+            }
+
+            let mut cuts = Vec::new();
+            while let Some(index) = indices.next_if(|&&index| index < end) {
+                cuts.push(index - start);
+            }
+
+            let fragments = child.split_at_many(subshift, child_len, &cuts);
+            let mut from = 0;
+
+            for (i, fragment) in fragments.into_iter().enumerate() {
+                if i > 0 {
+                    pieces.push(Vec::new());
+                }
+
+                let to = cuts.get(i).map_or(child_len, |&cut| cut);
+                pieces.last_mut().unwrap().push((fragment, to - from));
+
+                from = to;
+            }
+
+            start = end;
+        }
+
+        pieces
+            .into_iter()
+            .map(|children| Node::from_children(children, shift))
+            .collect()
+    }
+
+    /// Builds a branch out of the children along with their sizes. The branch
+    /// is regular only if all children but the last one are fully dense.
+    fn from_children(children: Vec<(Node<T, M>, usize)>, shift: Shift) -> Node<T, M> {
+        let last = children.len() - 1;
+        let is_regular = children.iter().enumerate().all(|(i, (child, size))| {
+            !child.is_relaxed_branch() && (i == last || *size == 1 << shift.0)
+        });
+
+        if is_regular {
+            let mut branch = Branch::new();
+
+            for (child, _) in children {
+                branch.add(Some(child));
+            }
+
+            Node::Branch(SharedPtr::new(branch))
+        } else {
+            let mut branch = RelaxedBranch::new();
+            let mut size = 0;
+
+            for (child, child_size) in children {
+                size += child_size;
+                branch.add(Some(child), Some(size));
+            }
+
+            Node::RelaxedBranch(SharedPtr::new(branch))
+        }
+    }
 }
 
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
-pub struct RrbTree<T> {
-    root: Option<Node<T>>,
+pub struct RrbTree<T, M: Measure<T> = ()> {
+    root: Option<Node<T, M>>,
     root_len: Index,
     shift: Shift,
 }
 
-impl<T: Clone + Debug> RrbTree<T> {
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
     pub fn new() -> Self {
         RrbTree {
             root: None,
@@ -1196,6 +1509,8 @@ impl<T: Clone + Debug> RrbTree<T> {
                 let mut new_children = new_branch!();
                 new_children[0] = Some(root.clone());
 
+                let summary = Cached(root.summary().clone());
+
                 self.shift = self.shift.inc();
 
                 *root = match root {
@@ -1204,24 +1519,24 @@ impl<T: Clone + Debug> RrbTree<T> {
                         new_sizes[0] = branch.sizes[branch.len - 1];
                         new_sizes[1] = branch.sizes[branch.len - 1];
 
-                        new_children[1] = Some(Node::Branch(SharedPtr::new(Branch {
-                            children: new_branch!(),
-                            len: 0,
-                        })));
+                        new_children[1] = Some(Node::Branch(SharedPtr::new(Branch::new())));
 
                         Node::RelaxedBranch(SharedPtr::new(RelaxedBranch {
                             children: new_children,
                             sizes: new_sizes,
                             len: 2,
+                            summary,
                         }))
                     }
                     Node::Branch(..) => Node::Branch(SharedPtr::new(Branch {
                         children: new_children,
                         len: 1,
+                        summary,
                     })),
                     Node::Leaf(..) => Node::Branch(SharedPtr::new(Branch {
                         children: new_children,
                         len: 1,
+                        summary,
                     })),
                 }
             }
@@ -1230,16 +1545,12 @@ impl<T: Clone + Debug> RrbTree<T> {
                 self.root_len,
                 self.shift,
                 shift_new_branch,
-                Leaf {
-                    elements: tail,
-                    len: tail_len,
-                },
+                Leaf::with_elements(tail, tail_len),
             );
         } else {
-            self.root = Some(Node::Leaf(SharedPtr::new(Leaf {
-                elements: tail,
-                len: tail_len,
-            })));
+            self.root = Some(Node::Leaf(SharedPtr::new(Leaf::with_elements(
+                tail, tail_len,
+            ))));
         }
 
         self.root_len.0 += tail_len;
@@ -1258,7 +1569,24 @@ impl<T: Clone + Debug> RrbTree<T> {
         let leaf = root.pop(self.shift);
         self.root_len.0 -= leaf.len;
 
-        if root.len() == 1 {
+        if self.root_len.0 == 0 {
+            *self = RrbTree::new();
+            return (leaf.elements, leaf.len);
+        }
+
+        self.collapse_root();
+        (leaf.elements, leaf.len)
+    }
+
+    /// Collapses the chain of single-child branches on the top
+    /// of the tree, which might be left by splits and appends.
+    fn collapse_root(&mut self) {
+        while !self.shift.is_leaf_level() {
+            let root = match self.root {
+                Some(ref mut root) if root.len() == 1 => root,
+                _ => return,
+            };
+
             self.shift = self.shift.dec();
 
             *root = match root {
@@ -1271,14 +1599,34 @@ impl<T: Clone + Debug> RrbTree<T> {
                 Node::Leaf(..) => unreachable!(),
             };
         }
-
-        (leaf.elements, leaf.len)
     }
 
     pub fn get(&self, index: usize) -> Option<&T> {
         self.root.as_ref().unwrap().get(Index(index), self.shift)
     }
 
+    /// Returns the elements of the leaf holding the given
+    /// index, starting from the element at the index.
+    pub fn get_chunk(&self, index: usize) -> &[Option<T>] {
+        let (leaf, element) = self
+            .root
+            .as_ref()
+            .unwrap()
+            .get_leaf(Index(index), self.shift);
+        &leaf.elements[element..leaf.len]
+    }
+
+    /// Returns the elements of the leaf holding the given index, starting
+    /// from the element at the index, copying the leaf if it is shared.
+    pub fn get_chunk_mut(&mut self, index: usize) -> &mut [Option<T>] {
+        let (leaf, element) = self
+            .root
+            .as_mut()
+            .unwrap()
+            .get_leaf_mut(Index(index), self.shift);
+        &mut leaf.elements[element..leaf.len]
+    }
+
     pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
         self.root
             .as_mut()
@@ -1301,7 +1649,7 @@ impl<T: Clone + Debug> RrbTree<T> {
         self.root.as_ref().map_or(false, |node| node.is_leaf())
     }
 
-    pub fn append(&mut self, that: &mut RrbTree<T>) {
+    pub fn append(&mut self, that: &mut RrbTree<T, M>) {
         if !self.is_empty() && !that.is_empty() {
             let this_root = self.root.as_mut().unwrap();
             let that_root = that.root.take().unwrap();
@@ -1370,9 +1718,66 @@ impl<T: Clone + Debug> RrbTree<T> {
             panic!();
         }
     }
+
+    /// Splits the tree into `indices.len() + 1` pieces at the given positions,
+    /// which must be sorted and must not exceed the length. The tree is walked
+    /// down once for all positions, and the subtrees in between them are moved
+    /// into the pieces as they are.
+    pub fn split_at_many(self, indices: &[usize]) -> Vec<Self> {
+        let len = self.len();
+        let shift = self.shift;
+
+        let mut cuts: Vec<usize> = indices
+            .iter()
+            .cloned()
+            .filter(|&index| index > 0 && index < len)
+            .collect();
+        cuts.dedup();
+
+        let mut roots = match self.root {
+            Some(root) => root.split_at_many(shift, len, &cuts).into_iter(),
+            None => Vec::new().into_iter(),
+        };
+
+        let mut pieces = Vec::with_capacity(indices.len() + 1);
+        let mut start = 0;
+
+        for end in indices.iter().cloned().chain(Some(len)) {
+            if end == start {
+                pieces.push(RrbTree::new());
+                continue;
+            }
+
+            let mut piece = RrbTree {
+                root: roots.next(),
+                root_len: Index(end - start),
+                shift,
+            };
+
+            piece.collapse_root();
+            pieces.push(piece);
+
+            start = end;
+        }
+
+        pieces
+    }
 }
 
+mod compact;
+#[cfg(feature = "std")]
+pub mod dedup;
 pub mod iter;
+#[cfg(feature = "std")]
+pub mod merkle;
+#[cfg(feature = "std")]
+pub mod render;
+mod repeat;
+mod summary;
+mod unique;
+#[cfg(feature = "std")]
+pub mod visit;
+mod zip;
 
 #[cfg(feature = "serde_serializer")]
 mod serializer;
@@ -0,0 +1,183 @@
+use super::{Node, RrbTree, BRANCH_FACTOR};
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+
+/// Numbers the nodes in the order they are first seen, so that
+/// the output does not depend on the addresses of the nodes.
+#[derive(Debug, Default)]
+struct NodeIds(HashMap<usize, usize>);
+
+impl NodeIds {
+    /// Returns the id of the node, and true if it is seen for the first time.
+    fn get<T: Clone + Debug>(&mut self, node: &Node<T>) -> (usize, bool) {
+        let next = self.0.len();
+        let id = *self.0.entry(node.address()).or_insert(next);
+
+        (id, id == next)
+    }
+}
+
+impl<T: Clone + Debug> Node<T> {
+    fn describe(&self) -> String {
+        let name = match self {
+            Node::RelaxedBranch(..) => "RelaxedBranch",
+            Node::Branch(..) => "Branch",
+            Node::Leaf(..) => "Leaf",
+        };
+
+        let mut description = format!(
+            "{} {}/{} refs {}",
+            name,
+            self.len(),
+            BRANCH_FACTOR,
+            self.strong_count()
+        );
+
+        if let Node::RelaxedBranch(ref branch) = self {
+            let sizes: Vec<usize> = branch.sizes[..branch.len]
+                .iter()
+                .flatten()
+                .cloned()
+                .collect();
+            write!(description, " sizes {:?}", sizes).unwrap();
+        }
+
+        description
+    }
+}
+
+/// Writes trees of one or many vectors as a Graphviz graph. Every node is
+/// written once, hence the shared nodes have many incoming edges.
+#[derive(Debug, Default)]
+pub struct DotWriter {
+    ids: NodeIds,
+    vecs: usize,
+    out: String,
+}
+
+impl DotWriter {
+    pub fn new() -> Self {
+        DotWriter::default()
+    }
+
+    pub fn add_vec<T: Clone + Debug>(&mut self, tree: &RrbTree<T>, tail_len: usize) {
+        let vec = self.vecs;
+        let len = tree.len() + tail_len;
+
+        writeln!(
+            self.out,
+            "  v{0} [label=\"vec {0}\\nlen {1}\\nshift {2}\", shape=ellipse];",
+            vec, len, tree.shift.0
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "  v{0}_tail [label=\"Tail {1}/{2}\", style=dashed];",
+            vec, tail_len, BRANCH_FACTOR
+        )
+        .unwrap();
+        writeln!(self.out, "  v{0} -> v{0}_tail [style=dashed];", vec).unwrap();
+
+        if let Some(ref root) = tree.root {
+            let root = self.add_node(root);
+            writeln!(self.out, "  v{} -> n{};", vec, root).unwrap();
+        }
+
+        self.vecs += 1;
+    }
+
+    fn add_node<T: Clone + Debug>(&mut self, node: &Node<T>) -> usize {
+        let (id, is_new) = self.ids.get(node);
+
+        if is_new {
+            let style = if node.is_shared() {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+
+            writeln!(
+                self.out,
+                "  n{} [label=\"{}\"{}];",
+                id,
+                node.describe(),
+                style
+            )
+            .unwrap();
+
+            for child in node.children().iter().flatten() {
+                let child = self.add_node(child);
+                writeln!(self.out, "  n{} -> n{};", id, child).unwrap();
+            }
+        }
+
+        id
+    }
+
+    pub fn finish(self) -> String {
+        format!(
+            "digraph pvec {{\n  node [shape=box, fontname=\"monospace\"];\n{}}}\n",
+            self.out
+        )
+    }
+}
+
+/// Writes trees of one or many vectors as indented text. Nodes seen
+/// before are referred to by their ids instead of being written again.
+#[derive(Debug, Default)]
+pub struct TextWriter {
+    ids: NodeIds,
+    vecs: usize,
+    out: String,
+}
+
+impl TextWriter {
+    pub fn new() -> Self {
+        TextWriter::default()
+    }
+
+    pub fn add_vec<T: Clone + Debug>(&mut self, tree: &RrbTree<T>, tail_len: usize) {
+        writeln!(
+            self.out,
+            "vec {}: len {}, shift {}",
+            self.vecs,
+            tree.len() + tail_len,
+            tree.shift.0
+        )
+        .unwrap();
+
+        if let Some(ref root) = tree.root {
+            self.add_node(root, "", false);
+        }
+
+        writeln!(self.out, "└── Tail {}/{}", tail_len, BRANCH_FACTOR).unwrap();
+        self.vecs += 1;
+    }
+
+    fn add_node<T: Clone + Debug>(&mut self, node: &Node<T>, prefix: &str, is_last: bool) {
+        let (id, is_new) = self.ids.get(node);
+        let (branch, indent) = if is_last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+
+        if !is_new {
+            writeln!(self.out, "{}{}#{} (see above)", prefix, branch, id).unwrap();
+            return;
+        }
+
+        writeln!(self.out, "{}{}#{} {}", prefix, branch, id, node.describe()).unwrap();
+
+        let prefix = format!("{}{}", prefix, indent);
+        let children = node.children();
+
+        for (i, child) in children.iter().flatten().enumerate() {
+            self.add_node(child, &prefix, i == children.len() - 1);
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
@@ -0,0 +1,126 @@
+use super::SharedPtr;
+use super::BRANCH_FACTOR;
+use super::{Branch, Index, Leaf, Measure, Node, RrbTree, Shift};
+use alloc::vec;
+use core::fmt::Debug;
+
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    /// Builds a node holding the given number of full leaves, where
+    /// `full[level]` is the shared node with the maximum number of leaves
+    /// for the level. Only the right-most path is allocated, the rest of
+    /// the children point to the shared nodes.
+    fn repeat(full: &[Node<T, M>], level: usize, capacity: usize, leaves: usize) -> Self {
+        if leaves == capacity {
+            return full[level].clone();
+        }
+
+        let child_capacity = capacity / BRANCH_FACTOR;
+        let mut branch = Branch::new();
+
+        for _ in 0..leaves / child_capacity {
+            branch.add(Some(full[level - 1].clone()));
+        }
+
+        let remainder = leaves % child_capacity;
+        if remainder > 0 {
+            let child = Node::repeat(full, level - 1, child_capacity, remainder);
+            branch.add(Some(child));
+        }
+
+        Node::Branch(SharedPtr::new(branch))
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
+    /// Builds a tree of the given number of full leaves, each of them holding
+    /// clones of the item. A single leaf and a single branch per level are
+    /// shared by all positions, hence the tree takes O(log n) memory.
+    pub fn repeat(item: &T, leaves: usize) -> Self {
+        if leaves == 0 {
+            return RrbTree::new();
+        }
+
+        let mut elements = new_branch!();
+        for element in elements.iter_mut() {
+            *element = Some(item.clone());
+        }
+
+        let leaf = Leaf::with_elements(elements, BRANCH_FACTOR);
+        let mut full = vec![Node::Leaf(SharedPtr::new(leaf))];
+
+        let mut shift = Shift(0);
+        let mut capacity = 1;
+
+        while capacity < leaves {
+            let mut branch = Branch::new();
+            for _ in 0..BRANCH_FACTOR {
+                branch.add(Some(full[full.len() - 1].clone()));
+            }
+
+            full.push(Node::Branch(SharedPtr::new(branch)));
+
+            shift = shift.inc();
+            capacity *= BRANCH_FACTOR;
+        }
+
+        let level = full.len() - 1;
+
+        RrbTree {
+            root: Some(Node::repeat(&full, level, capacity, leaves)),
+            root_len: Index(leaves * BRANCH_FACTOR),
+            shift,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Node, RrbTree, BRANCH_FACTOR};
+    use super::SharedPtr;
+
+    #[test]
+    fn repeat_must_build_the_same_tree_as_pushing_leaves() {
+        let sizes = [
+            0,
+            1,
+            2,
+            BRANCH_FACTOR,
+            BRANCH_FACTOR + 1,
+            BRANCH_FACTOR * 3 + 2,
+        ];
+
+        for &leaves in sizes.iter() {
+            let repeated: RrbTree<usize> = RrbTree::repeat(&7, leaves);
+            let mut pushed = RrbTree::new();
+
+            for _ in 0..leaves {
+                let mut elements = new_branch!();
+                for element in elements.iter_mut() {
+                    *element = Some(7);
+                }
+
+                pushed.push(elements, BRANCH_FACTOR);
+            }
+
+            assert_eq!(repeated, pushed);
+        }
+    }
+
+    #[test]
+    fn repeat_must_share_full_nodes() {
+        let tree: RrbTree<usize> = RrbTree::repeat(&7, BRANCH_FACTOR);
+
+        match tree.root {
+            Some(Node::Branch(ref branch)) => {
+                let leaf = |i: usize| match branch.children[i] {
+                    Some(Node::Leaf(ref leaf)) => leaf.clone(),
+                    _ => panic!(),
+                };
+
+                assert!(SharedPtr::ptr_eq(&leaf(0), &leaf(1)));
+                assert!(SharedPtr::ptr_eq(&leaf(0), &leaf(BRANCH_FACTOR - 1)));
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use super::SharedPtr;
+use super::{get_branch_index, Index, Measure, Node, RrbTree, Shift};
+use core::cmp;
+use core::fmt::Debug;
+
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    /// Folds the summaries of elements in the range [from, to) of the subtree
+    /// holding `size` elements. The children which are entirely covered by the
+    /// range contribute their cached summaries, hence only the nodes on the
+    /// boundaries of the range are visited.
+    fn range_summary(&self, shift: Shift, size: usize, from: usize, to: usize) -> M::Summary {
+        if from == 0 && to == size {
+            return self.summary().clone();
+        }
+
+        let mut summary = M::identity();
+
+        match self {
+            Node::RelaxedBranch(ref branch) => {
+                let mut start = 0;
+
+                for i in 0..branch.len {
+                    let end = branch.sizes[i].unwrap();
+
+                    if start < to && from < end {
+                        let child = branch.children[i].as_ref().unwrap();
+                        let child_summary = child.range_summary(
+                            shift.dec(),
+                            end - start,
+                            cmp::max(from, start) - start,
+                            cmp::min(to, end) - start,
+                        );
+
+                        summary = M::combine(&summary, &child_summary);
+                    }
+
+                    start = end;
+                }
+            }
+            Node::Branch(ref branch) => {
+                let width = 1 << shift.0;
+
+                for i in from / width..=(to - 1) / width {
+                    let start = i * width;
+                    let end = cmp::min(start + width, size);
+
+                    let child = branch.children[i].as_ref().unwrap();
+                    let child_summary = child.range_summary(
+                        shift.dec(),
+                        end - start,
+                        cmp::max(from, start) - start,
+                        cmp::min(to, end) - start,
+                    );
+
+                    summary = M::combine(&summary, &child_summary);
+                }
+            }
+            Node::Leaf(ref leaf) => {
+                for item in leaf.elements[from..to].iter().flatten() {
+                    summary = M::combine(&summary, &M::measure(item));
+                }
+            }
+        }
+
+        summary
+    }
+
+    /// Looks for the first element at which the predicate holds for the
+    /// summary of all elements up to and including it. The `prefix` is the
+    /// summary of elements preceding the subtree. If the element is not
+    /// found, the prefix combined with the summary of the subtree is returned.
+    fn find_by_measure<P>(
+        &self,
+        shift: Shift,
+        prefix: M::Summary,
+        predicate: &mut P,
+    ) -> Result<usize, M::Summary>
+    where
+        P: FnMut(&M::Summary) -> bool,
+    {
+        let mut prefix = prefix;
+
+        match self {
+            Node::RelaxedBranch(ref branch) => {
+                for i in 0..branch.len {
+                    let child = branch.children[i].as_ref().unwrap();
+                    let summary = M::combine(&prefix, child.summary());
+
+                    if predicate(&summary) {
+                        let offset = if i == 0 {
+                            0
+                        } else {
+                            branch.sizes[i - 1].unwrap()
+                        };
+
+                        match child.find_by_measure(shift.dec(), prefix, predicate) {
+                            Ok(index) => return Ok(offset + index),
+                            Err(..) => prefix = summary,
+                        }
+                    } else {
+                        prefix = summary;
+                    }
+                }
+            }
+            Node::Branch(ref branch) => {
+                for i in 0..branch.len {
+                    let child = branch.children[i].as_ref().unwrap();
+                    let summary = M::combine(&prefix, child.summary());
+
+                    if predicate(&summary) {
+                        match child.find_by_measure(shift.dec(), prefix, predicate) {
+                            Ok(index) => return Ok((i << shift.0) + index),
+                            Err(..) => prefix = summary,
+                        }
+                    } else {
+                        prefix = summary;
+                    }
+                }
+            }
+            Node::Leaf(ref leaf) => {
+                for (i, item) in leaf.elements[..leaf.len].iter().flatten().enumerate() {
+                    prefix = M::combine(&prefix, &M::measure(item));
+
+                    if predicate(&prefix) {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+
+        Err(prefix)
+    }
+
+    /// Recomputes summaries of the nodes on the path to the given index,
+    /// after the element at the index has been modified in place.
+    fn refresh_path(&mut self, index: Index, shift: Shift) {
+        match self {
+            Node::RelaxedBranch(ref mut ptr) => {
+                let branch = SharedPtr::make_mut(ptr);
+
+                let i = get_branch_index(&branch.sizes, index);
+
+                let child_index = if i == 0 {
+                    index
+                } else {
+                    Index(index.0 - branch.sizes[i - 1].unwrap())
+                };
+
+                let child = branch.children[i].as_mut().unwrap();
+                child.refresh_path(child_index, shift.dec());
+
+                branch.refresh();
+            }
+            Node::Branch(ref mut ptr) => {
+                let branch = SharedPtr::make_mut(ptr);
+
+                let child = branch.children[index.child(shift)].as_mut().unwrap();
+                child.refresh_path(index, shift.dec());
+
+                branch.refresh();
+            }
+            Node::Leaf(ref mut ptr) => {
+                SharedPtr::make_mut(ptr).refresh();
+            }
+        }
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
+    /// Returns the summary of all elements in the tree.
+    pub fn summary(&self) -> M::Summary {
+        self.root
+            .as_ref()
+            .map_or_else(M::identity, |root| root.summary().clone())
+    }
+
+    /// Returns the summary of elements in the range [from, to).
+    pub fn range_summary(&self, from: usize, to: usize) -> M::Summary {
+        debug_assert!(from <= to && to <= self.len());
+
+        match self.root {
+            Some(ref root) if from < to => root.range_summary(self.shift, self.len(), from, to),
+            _ => M::identity(),
+        }
+    }
+
+    /// Returns the index of the first element at which the predicate holds
+    /// for the prefix summary, where `prefix` is the summary of elements
+    /// preceding the tree. Otherwise, returns the summary of all elements.
+    pub fn find_by_measure<P>(
+        &self,
+        prefix: M::Summary,
+        predicate: &mut P,
+    ) -> Result<usize, M::Summary>
+    where
+        P: FnMut(&M::Summary) -> bool,
+    {
+        match self.root {
+            Some(ref root) => root.find_by_measure(self.shift, prefix, predicate),
+            None => Err(prefix),
+        }
+    }
+
+    /// Brings summaries up to date after the element at the
+    /// given index has been modified through `get_mut`.
+    pub fn refresh_path(&mut self, index: usize) {
+        if M::is_trivial() {
+            return;
+        }
+
+        if let Some(ref mut root) = self.root {
+            root.refresh_path(Index(index), self.shift);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use super::SharedPtr;
+use super::{get_branch_index, Index, Measure, Node, RrbTree, Shift};
+use core::fmt::Debug;
+use core::ops;
+
+#[cfg(all(feature = "arc", feature = "rayon_iter"))]
+use rayon::prelude::{IntoParallelRefMutIterator, ParallelIterator};
+
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    /// Returns the children of the node, copying it first if it is shared.
+    #[inline(always)]
+    fn children_mut(&mut self) -> &mut [Option<Node<T, M>>] {
+        match self {
+            Node::RelaxedBranch(ref mut ptr) => {
+                let branch = SharedPtr::make_mut(ptr);
+                &mut branch.children[..branch.len]
+            }
+            Node::Branch(ref mut ptr) => {
+                let branch = SharedPtr::make_mut(ptr);
+                &mut branch.children[..branch.len]
+            }
+            Node::Leaf(ref mut ptr) => {
+                SharedPtr::make_mut(ptr);
+                &mut []
+            }
+        }
+    }
+
+    fn make_unique(&mut self) {
+        for child in self.children_mut().iter_mut().flatten() {
+            child.make_unique();
+        }
+    }
+
+    /// Returns true if none of the nodes on the path
+    /// to the element at the index is shared.
+    fn is_unique_path(&self, index: Index, shift: Shift) -> bool {
+        let mut node = self;
+        let mut shift = shift;
+        let mut idx = index;
+
+        loop {
+            if node.is_shared() {
+                return false;
+            }
+
+            match *node {
+                Node::RelaxedBranch(ref relaxed_branch) => {
+                    let sizes = &relaxed_branch.sizes;
+                    let branch_index = get_branch_index(sizes, idx);
+
+                    if branch_index != 0 {
+                        idx = Index(idx.0 - sizes[branch_index - 1].unwrap());
+                    }
+
+                    node = relaxed_branch.children[branch_index].as_ref().unwrap();
+                    shift = shift.dec();
+                }
+                Node::Branch(ref branch) => {
+                    node = branch.children[idx.child(shift)].as_ref().unwrap();
+                    shift = shift.dec();
+                }
+                Node::Leaf(..) => return true,
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "arc", feature = "rayon_iter"))]
+impl<T: Clone + Debug + Send + Sync, M: Measure<T>> Node<T, M>
+where
+    M::Summary: Send + Sync,
+{
+    fn par_make_unique(&mut self) {
+        self.children_mut()
+            .par_iter_mut()
+            .flatten()
+            .for_each(Node::make_unique);
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
+    /// Returns true if none of the nodes is shared with another tree.
+    pub fn is_unique(&self) -> bool {
+        self.root.as_ref().map_or(true, Node::is_unique_subtree)
+    }
+
+    /// Returns true if both trees refer to the same root node.
+    pub fn ptr_eq(&self, that: &RrbTree<T, M>) -> bool {
+        match (&self.root, &that.root) {
+            (Some(ref a), Some(ref b)) => a.address() == b.address(),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Copies all of the shared nodes, so that the tree is unique.
+    pub fn make_unique(&mut self) {
+        if let Some(ref mut root) = self.root {
+            root.make_unique();
+        }
+    }
+
+    /// Copies the shared nodes on the paths to the elements in the range.
+    pub fn make_unique_range(&mut self, range: ops::Range<usize>) {
+        let mut index = range.start;
+
+        while index < range.end {
+            let len = self.get_chunk(index).len();
+
+            self.get_mut(index);
+            index += len;
+        }
+    }
+
+    /// Returns true if `get_mut` of the index would not copy any node.
+    pub fn is_unique_path(&self, index: usize) -> bool {
+        self.root
+            .as_ref()
+            .unwrap()
+            .is_unique_path(Index(index), self.shift)
+    }
+}
+
+#[cfg(all(feature = "arc", feature = "rayon_iter"))]
+impl<T: Clone + Debug + Send + Sync, M: Measure<T>> RrbTree<T, M>
+where
+    M::Summary: Send + Sync,
+{
+    /// Copies all of the shared nodes, unsharing the subtrees of the root in parallel.
+    pub fn par_make_unique(&mut self) {
+        if let Some(ref mut root) = self.root {
+            root.par_make_unique();
+        }
+    }
+}
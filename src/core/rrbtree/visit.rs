@@ -0,0 +1,85 @@
+use super::SharedPtr;
+use super::{Branch, Leaf, Measure, Node, RelaxedBranch, RrbTree, Shift};
+use core::fmt::Debug;
+use core::mem;
+
+/// The kind of a node passed to the visitor of [RrbTree::visit_nodes].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Leaf,
+    Branch,
+    RelaxedBranch,
+}
+
+/// Describes a node passed to the visitor of [RrbTree::visit_nodes].
+/// The length is the number of elements of a leaf, or the number
+/// of children of a branch.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeInfo {
+    pub address: usize,
+    pub bytes: usize,
+    pub kind: NodeKind,
+    pub len: usize,
+    pub shift: usize,
+    pub strong_count: usize,
+}
+
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    fn info(&self, shift: Shift) -> NodeInfo {
+        let (address, bytes, kind, strong_count) = match self {
+            Node::RelaxedBranch(ref ptr) => (
+                SharedPtr::as_ptr(ptr) as usize,
+                mem::size_of::<RelaxedBranch<T, M>>(),
+                NodeKind::RelaxedBranch,
+                SharedPtr::strong_count(ptr),
+            ),
+            Node::Branch(ref ptr) => (
+                SharedPtr::as_ptr(ptr) as usize,
+                mem::size_of::<Branch<T, M>>(),
+                NodeKind::Branch,
+                SharedPtr::strong_count(ptr),
+            ),
+            Node::Leaf(ref ptr) => (
+                SharedPtr::as_ptr(ptr) as usize,
+                mem::size_of::<Leaf<T, M>>(),
+                NodeKind::Leaf,
+                SharedPtr::strong_count(ptr),
+            ),
+        };
+
+        NodeInfo {
+            address,
+            bytes,
+            kind,
+            len: self.len(),
+            shift: shift.0,
+            strong_count,
+        }
+    }
+
+    fn visit_nodes<F>(&self, shift: Shift, visitor: &mut F)
+    where
+        F: FnMut(&NodeInfo) -> bool,
+    {
+        if !visitor(&self.info(shift)) {
+            return;
+        }
+
+        for child in self.children().iter().flatten() {
+            child.visit_nodes(shift.dec(), visitor);
+        }
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
+    /// Walks the nodes of the tree in depth-first order. Children
+    /// of a node are skipped if the visitor returns false for it.
+    pub fn visit_nodes<F>(&self, visitor: &mut F)
+    where
+        F: FnMut(&NodeInfo) -> bool,
+    {
+        if let Some(ref root) = self.root {
+            root.visit_nodes(self.shift, visitor);
+        }
+    }
+}
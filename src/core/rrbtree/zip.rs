@@ -0,0 +1,107 @@
+use super::SharedPtr;
+use super::{Branch, Leaf, Measure, Node, RelaxedBranch, RrbTree};
+use core::fmt::Debug;
+
+impl<T: Clone + Debug, M: Measure<T>> Node<T, M> {
+    #[inline(always)]
+    fn ptr_eq(&self, that: &Node<T, M>) -> bool {
+        match (self, that) {
+            (Node::RelaxedBranch(ref a), Node::RelaxedBranch(ref b)) => SharedPtr::ptr_eq(a, b),
+            (Node::Branch(ref a), Node::Branch(ref b)) => SharedPtr::ptr_eq(a, b),
+            (Node::Leaf(ref a), Node::Leaf(ref b)) => SharedPtr::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Combines the elements of two subtrees of the same shape. Returns
+    /// None if the shapes are different or if the function rejects a pair.
+    fn zip_with<F>(&self, that: &Node<T, M>, reuse_shared: bool, f: &mut F) -> Option<Node<T, M>>
+    where
+        F: FnMut(&T, &T) -> Option<T>,
+    {
+        if reuse_shared && self.ptr_eq(that) {
+            return Some(self.clone());
+        }
+
+        match (self, that) {
+            (Node::RelaxedBranch(ref a), Node::RelaxedBranch(ref b)) => {
+                if a.len != b.len || a.sizes != b.sizes {
+                    return None;
+                }
+
+                let mut branch = RelaxedBranch::new();
+
+                for i in 0..a.len {
+                    let child_a = a.children[i].as_ref().unwrap();
+                    let child_b = b.children[i].as_ref().unwrap();
+
+                    let child = child_a.zip_with(child_b, reuse_shared, f)?;
+                    branch.add(Some(child), a.sizes[i]);
+                }
+
+                Some(Node::RelaxedBranch(SharedPtr::new(branch)))
+            }
+            (Node::Branch(ref a), Node::Branch(ref b)) => {
+                if a.len != b.len {
+                    return None;
+                }
+
+                let mut branch = Branch::new();
+
+                for i in 0..a.len {
+                    let child_a = a.children[i].as_ref().unwrap();
+                    let child_b = b.children[i].as_ref().unwrap();
+
+                    let child = child_a.zip_with(child_b, reuse_shared, f)?;
+                    branch.add(Some(child));
+                }
+
+                Some(Node::Branch(SharedPtr::new(branch)))
+            }
+            (Node::Leaf(ref a), Node::Leaf(ref b)) => {
+                if a.len != b.len {
+                    return None;
+                }
+
+                let mut leaf = Leaf::new();
+
+                for i in 0..a.len {
+                    let item_a = a.elements[i].as_ref().unwrap();
+                    let item_b = b.elements[i].as_ref().unwrap();
+
+                    leaf.add(Some(f(item_a, item_b)?));
+                }
+
+                Some(Node::Leaf(SharedPtr::new(leaf)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: Clone + Debug, M: Measure<T>> RrbTree<T, M> {
+    /// Builds a tree of the same shape by combining pairs of elements at the same
+    /// positions. If `reuse_shared` is set, the subtrees shared between both trees
+    /// are taken as they are, which is only valid when `f(x, x)` equals `x`.
+    /// Returns None if the trees have different shapes.
+    pub fn zip_with<F>(&self, that: &RrbTree<T, M>, reuse_shared: bool, f: &mut F) -> Option<Self>
+    where
+        F: FnMut(&T, &T) -> Option<T>,
+    {
+        if self.shift != that.shift || self.root_len != that.root_len {
+            return None;
+        }
+
+        let root = match (self.root.as_ref(), that.root.as_ref()) {
+            (Some(a), Some(b)) => Some(a.zip_with(b, reuse_shared, f)?),
+            (None, None) => None,
+            _ => return None,
+        };
+
+        Some(RrbTree {
+            root,
+            root_len: self.root_len,
+            shift: self.shift,
+        })
+    }
+}
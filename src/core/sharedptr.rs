@@ -1,9 +1,9 @@
 #[cfg(not(feature = "arc"))]
-use std::rc::Rc;
+use alloc::rc::{Rc, Weak};
 #[cfg(feature = "arc")]
-use std::sync::Arc;
+use alloc::sync::{Arc, Weak};
 
-use std::fmt::Debug;
+use core::fmt::Debug;
 
 #[cfg(feature = "arc")]
 pub type SharedPtr<K> = Arc<K>;
@@ -11,6 +11,8 @@ pub type SharedPtr<K> = Arc<K>;
 #[cfg(not(feature = "arc"))]
 pub type SharedPtr<K> = Rc<K>;
 
+pub type WeakPtr<K> = Weak<K>;
+
 pub trait Take<T: Clone> {
     fn take(self) -> T;
 }
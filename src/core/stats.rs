@@ -0,0 +1,121 @@
+//! A module providing memory and sharing statistics
+//! of the [RrbVec](crate::core::RrbVec) trees.
+
+use super::rrbtree::BRANCH_FACTOR;
+use super::visit::{NodeInfo, NodeKind};
+use super::RrbVec;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+
+/// The shape and the memory usage of a vector, as returned by
+/// [stats](crate::core::RrbVec::stats). Nodes referenced more than
+/// once within the vector are counted once.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct VecStats {
+    /// The height of the tree, as the shift of its root, which
+    /// is 0 if the root is a leaf or if the tree is empty.
+    pub shift: usize,
+    /// The number of leaves.
+    pub leaves: usize,
+    /// The number of regular branches.
+    pub branches: usize,
+    /// The number of relaxed branches, which keep the sizes of their children.
+    pub relaxed_branches: usize,
+    /// The ratio of the elements in leaves to the capacity of the leaves.
+    pub leaf_fill_ratio: f64,
+    /// The memory taken by the nodes of the tree. Memory owned
+    /// by elements and the tail stored inline are not counted.
+    pub heap_bytes: usize,
+    /// The part of `heap_bytes` taken by the nodes shared with other vectors,
+    /// that is, the nodes with more than one reference and their descendants.
+    pub shared_bytes: usize,
+}
+
+impl<T: Clone + Debug> RrbVec<T> {
+    /// Returns the shape and the memory usage of the vector.
+    pub fn stats(&self) -> VecStats {
+        let mut stats = VecStats::default();
+        let mut elements = 0;
+        let mut is_root = true;
+
+        self.visit_shared_nodes(&mut HashSet::new(), |node, is_shared| {
+            if is_root {
+                stats.shift = node.shift;
+                is_root = false;
+            }
+
+            match node.kind {
+                NodeKind::Leaf => {
+                    stats.leaves += 1;
+                    elements += node.len;
+                }
+                NodeKind::Branch => stats.branches += 1,
+                NodeKind::RelaxedBranch => stats.relaxed_branches += 1,
+            }
+
+            stats.heap_bytes += node.bytes;
+            if is_shared {
+                stats.shared_bytes += node.bytes;
+            }
+        });
+
+        if stats.leaves > 0 {
+            stats.leaf_fill_ratio = elements as f64 / (stats.leaves * BRANCH_FACTOR) as f64;
+        }
+
+        stats
+    }
+
+    /// Walks the nodes of the vector which are not in `visited`, adding
+    /// them to it, and passes each node to the callback along with whether
+    /// it is shared with other vectors. Subtrees of visited nodes are skipped.
+    pub(crate) fn visit_shared_nodes<F: FnMut(&NodeInfo, bool)>(
+        &self,
+        visited: &mut HashSet<usize>,
+        mut callback: F,
+    ) {
+        // Nodes below a shared node are shared as well, even though their
+        // reference counts are one, hence the shift of the shared ancestor
+        // is tracked while the nodes are walked in depth-first order.
+        let mut shared_shift = None;
+
+        self.visit_nodes(&mut |node: &NodeInfo| {
+            if shared_shift.map_or(false, |shift| node.shift >= shift) {
+                shared_shift = None;
+            }
+
+            if !visited.insert(node.address) {
+                return false;
+            }
+
+            if shared_shift.is_none() && node.strong_count > 1 {
+                shared_shift = Some(node.shift);
+            }
+
+            callback(node, shared_shift.is_some());
+            true
+        });
+    }
+
+    /// Returns the memory taken by the nodes the vector shares with the
+    /// other one, for instance with one of its previous versions.
+    pub fn shared_with(&self, that: &RrbVec<T>) -> usize {
+        let mut nodes = HashMap::new();
+
+        self.visit_nodes(&mut |node: &NodeInfo| nodes.insert(node.address, node.bytes).is_none());
+
+        let mut visited = HashSet::new();
+        let mut bytes = 0;
+
+        that.visit_nodes(&mut |node: &NodeInfo| {
+            if !visited.insert(node.address) {
+                return false;
+            }
+
+            bytes += nodes.get(&node.address).cloned().unwrap_or(0);
+            true
+        });
+
+        bytes
+    }
+}
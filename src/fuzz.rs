@@ -0,0 +1,141 @@
+//! A module providing the harness used for fuzzing the vectors. It replays
+//! operations decoded from raw bytes against a vector and the standard
+//! [vec](std::vec::Vec), which serves as the oracle. The harness is shared
+//! by the targets in the `fuzz` directory and the `fuzz` test, which feeds
+//! it with random bytes.
+
+use crate::Vector;
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use std::fmt::Debug;
+
+/// An operation replayed against both the vector and the oracle.
+/// Indices are taken modulo the length of the oracle plus one,
+/// so that both valid and out of bounds indices are exercised.
+#[derive(Debug)]
+pub enum Op<V> {
+    /// Pushes the element to the back.
+    Push(u32),
+    /// Pops the element from the back.
+    Pop,
+    /// Reads the element at the index.
+    Get(usize),
+    /// Overwrites the element at the index.
+    Set(usize, u32),
+    /// Appends a vector of a random shape.
+    Append(V),
+    /// Splits the vector at the index, keeping the right half if set.
+    SplitOff(usize, bool),
+    /// Keeps a clone of the vector, which must not change afterwards.
+    Snapshot,
+    /// Replaces the vector with one of the snapshots.
+    Restore(usize),
+}
+
+impl<'a, V: Arbitrary<'a>> Arbitrary<'a> for Op<V> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.choose_index(8)? {
+            0 => Op::Push(u32::arbitrary(u)?),
+            1 => Op::Pop,
+            2 => Op::Get(usize::arbitrary(u)?),
+            3 => Op::Set(usize::arbitrary(u)?, u32::arbitrary(u)?),
+            4 => Op::Append(V::arbitrary(u)?),
+            5 => Op::SplitOff(usize::arbitrary(u)?, bool::arbitrary(u)?),
+            6 => Op::Snapshot,
+            _ => Op::Restore(usize::arbitrary(u)?),
+        })
+    }
+}
+
+fn to_vec<V: Vector<u32>>(vec: &V) -> Vec<u32> {
+    vec.iter().map(|item| *item).collect()
+}
+
+/// Replays the operations decoded from the bytes, starting from a vector
+/// of a random shape. Panics as soon as the vector diverges from the oracle.
+pub fn replay<V>(data: &[u8])
+where
+    V: Vector<u32> + for<'a> Arbitrary<'a> + Debug,
+{
+    let mut u = Unstructured::new(data);
+
+    let mut vec = match V::arbitrary(&mut u) {
+        Ok(vec) => vec,
+        Err(..) => return,
+    };
+
+    let mut oracle = to_vec(&vec);
+    let mut snapshots: Vec<(V, Vec<u32>)> = Vec::new();
+
+    for op in u.arbitrary_iter::<Op<V>>().unwrap() {
+        let op = match op {
+            Ok(op) => op,
+            Err(..) => break,
+        };
+
+        match op {
+            Op::Push(item) => {
+                vec.push(item);
+                oracle.push(item);
+            }
+            Op::Pop => {
+                assert_eq!(vec.pop(), oracle.pop());
+            }
+            Op::Get(index) => {
+                let index = index % (oracle.len() + 1);
+                assert_eq!(vec.get(index).as_deref(), oracle.get(index));
+            }
+            Op::Set(index, item) => {
+                let index = index % (oracle.len() + 1);
+
+                match (vec.get_mut(index), oracle.get_mut(index)) {
+                    (Some(element), Some(expected)) => {
+                        *element = item;
+                        *expected = item;
+                    }
+                    (None, None) => {}
+                    (element, expected) => {
+                        panic!("get_mut({}): {:?} != {:?}", index, element, expected)
+                    }
+                }
+            }
+            Op::Append(mut that) => {
+                oracle.append(&mut to_vec(&that));
+                vec.append(&mut that);
+
+                assert!(that.is_empty());
+            }
+            Op::SplitOff(mid, keep_right) => {
+                let mid = mid % (oracle.len() + 1);
+
+                let that = vec.split_off(mid);
+                let oracle_that = oracle.split_off(mid);
+
+                assert_eq!(to_vec(&that), oracle_that);
+
+                if keep_right {
+                    vec = that;
+                    oracle = oracle_that;
+                }
+            }
+            Op::Snapshot => {
+                snapshots.push((vec.clone(), oracle.clone()));
+            }
+            Op::Restore(index) => {
+                if !snapshots.is_empty() {
+                    let (ref snapshot, ref oracle_snapshot) = snapshots[index % snapshots.len()];
+
+                    vec = snapshot.clone();
+                    oracle = oracle_snapshot.clone();
+                }
+            }
+        }
+
+        assert_eq!(vec.len(), oracle.len());
+    }
+
+    assert_eq!(to_vec(&vec), oracle);
+
+    for (snapshot, oracle_snapshot) in snapshots.iter() {
+        assert_eq!(&to_vec(snapshot), oracle_snapshot);
+    }
+}
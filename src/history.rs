@@ -0,0 +1,338 @@
+//! A module providing a history of versions of a persistent
+//! value, supporting undo and redo with branching timelines.
+
+use crate::core::RrbVec;
+use crate::{PVec, Representation};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Debug;
+use std::mem;
+
+/// Identifies a version recorded in a [History](crate::history::History).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionId(usize);
+
+/// The number of bytes taken by the versions retained in a
+/// [History](crate::history::History), as reported by
+/// [memory_usage](crate::history::History::memory_usage).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of the tree nodes which are referenced from more than one
+    /// place, either by several versions or by vectors outside of the
+    /// history, along with all nodes reachable from them.
+    pub shared_bytes: usize,
+    /// Bytes of the tree nodes, tails and flat buffers
+    /// which belong to exactly one version.
+    pub unique_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Version<V> {
+    value: V,
+    parent: Option<VersionId>,
+    children: Vec<VersionId>,
+    redo: Option<VersionId>,
+}
+
+/// A history of versions of a value, which is meant to be used with
+/// the persistent vectors, where recording a version is as cheap as
+/// a clone. Versions form a tree: committing after an undo starts a
+/// new branch, while the old one remains reachable through
+/// [checkout](crate::history::History::checkout). Redo follows the
+/// branch that was visited last.
+///
+/// Versions can be given names with [checkpoint](crate::history::History::checkpoint).
+/// If a retention limit is set, the oldest versions are discarded once
+/// the limit is exceeded. The current version and checkpoints count
+/// towards the limit, but are never discarded.
+#[derive(Debug, Clone)]
+pub struct History<V> {
+    versions: BTreeMap<VersionId, Version<V>>,
+    checkpoints: BTreeMap<String, VersionId>,
+    current: VersionId,
+    next_id: usize,
+    limit: Option<usize>,
+}
+
+impl<V: Clone> History<V> {
+    /// Constructs a history with the given value as the initial version.
+    pub fn new(value: V) -> Self {
+        let mut versions = BTreeMap::new();
+
+        versions.insert(
+            VersionId(0),
+            Version {
+                value,
+                parent: None,
+                children: Vec::new(),
+                redo: None,
+            },
+        );
+
+        History {
+            versions,
+            checkpoints: BTreeMap::new(),
+            current: VersionId(0),
+            next_id: 1,
+            limit: None,
+        }
+    }
+
+    /// Constructs a history which retains at most `limit` versions. The
+    /// current version and checkpoints count towards the limit, but are
+    /// never discarded, so the history exceeds the limit when more of
+    /// them are kept than the limit allows.
+    pub fn with_limit(value: V, limit: usize) -> Self {
+        let mut history = History::new(value);
+        history.limit = Some(limit);
+        history
+    }
+
+    /// Sets the maximum number of retained versions, discarding
+    /// the oldest ones if the history exceeds it already.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        self.enforce_limit();
+    }
+
+    /// Returns the number of retained versions.
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// Always returns false, as the history has at least one version.
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Returns the current version.
+    pub fn current(&self) -> &V {
+        &self.versions[&self.current].value
+    }
+
+    /// Returns the id of the current version.
+    pub fn current_id(&self) -> VersionId {
+        self.current
+    }
+
+    /// Returns the version with the given id, or None if it is not retained.
+    pub fn get(&self, id: VersionId) -> Option<&V> {
+        self.versions.get(&id).map(|version| &version.value)
+    }
+
+    /// Returns the id of the version the given one was committed on top of.
+    pub fn parent(&self, id: VersionId) -> Option<VersionId> {
+        self.versions.get(&id).and_then(|version| version.parent)
+    }
+
+    /// Returns the ids of versions committed on top of the given one,
+    /// in the order of their creation. Each of them starts a branch.
+    pub fn children(&self, id: VersionId) -> &[VersionId] {
+        self.versions
+            .get(&id)
+            .map_or(&[], |version| &version.children[..])
+    }
+
+    /// Records the value as a new version on top of the current
+    /// one, makes it current and returns its id.
+    pub fn commit(&mut self, value: V) -> VersionId {
+        let id = VersionId(self.next_id);
+        self.next_id += 1;
+
+        let current = self.versions.get_mut(&self.current).unwrap();
+        current.children.push(id);
+        current.redo = Some(id);
+
+        self.versions.insert(
+            id,
+            Version {
+                value,
+                parent: Some(self.current),
+                children: Vec::new(),
+                redo: None,
+            },
+        );
+
+        self.current = id;
+        self.enforce_limit();
+
+        id
+    }
+
+    /// Moves to the parent of the current version and returns it,
+    /// or None if the current version has no retained parent.
+    pub fn undo(&mut self) -> Option<&V> {
+        let parent = self.versions[&self.current].parent?;
+
+        self.versions.get_mut(&parent).unwrap().redo = Some(self.current);
+        self.current = parent;
+
+        Some(self.current())
+    }
+
+    /// Moves to the child of the current version visited last and
+    /// returns it, or None if the current version has no children.
+    pub fn redo(&mut self) -> Option<&V> {
+        self.current = self.versions[&self.current].redo?;
+
+        Some(self.current())
+    }
+
+    /// Makes the version with the given id current and returns it,
+    /// or None if it is not retained.
+    pub fn checkout(&mut self, id: VersionId) -> Option<&V> {
+        if !self.versions.contains_key(&id) {
+            return None;
+        }
+
+        let mut child = id;
+
+        while let Some(parent) = self.versions[&child].parent {
+            self.versions.get_mut(&parent).unwrap().redo = Some(child);
+            child = parent;
+        }
+
+        self.current = id;
+        Some(self.current())
+    }
+
+    /// Gives the current version a name, replacing the previous
+    /// version with the same name. Checkpoints are never discarded.
+    pub fn checkpoint<S: Into<String>>(&mut self, name: S) {
+        self.checkpoints.insert(name.into(), self.current);
+    }
+
+    /// Returns the id of the version with the given name.
+    pub fn checkpoint_id(&self, name: &str) -> Option<VersionId> {
+        self.checkpoints.get(name).cloned()
+    }
+
+    /// Makes the version with the given name current and returns it.
+    pub fn restore(&mut self, name: &str) -> Option<&V> {
+        let id = self.checkpoint_id(name)?;
+        self.checkout(id)
+    }
+
+    /// Removes the name, allowing the version to be discarded.
+    pub fn remove_checkpoint(&mut self, name: &str) -> Option<VersionId> {
+        let id = self.checkpoints.remove(name);
+        self.enforce_limit();
+        id
+    }
+
+    /// Returns the names of checkpoints along with the ids of their versions.
+    pub fn checkpoints(&self) -> impl Iterator<Item = (&str, VersionId)> {
+        self.checkpoints
+            .iter()
+            .map(|(name, id)| (name.as_str(), *id))
+    }
+
+    fn is_pinned(&self, id: VersionId) -> bool {
+        id == self.current || self.checkpoints.values().any(|pinned| *pinned == id)
+    }
+
+    fn enforce_limit(&mut self) {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        while self.versions.len() > limit {
+            let oldest = self
+                .versions
+                .keys()
+                .cloned()
+                .find(|id| !self.is_pinned(*id));
+
+            match oldest {
+                Some(id) => self.discard(id),
+                None => break,
+            }
+        }
+    }
+
+    /// Removes the version, attaching its children to its parent.
+    fn discard(&mut self, id: VersionId) {
+        let version = self.versions.remove(&id).unwrap();
+
+        for child in version.children.iter() {
+            self.versions.get_mut(child).unwrap().parent = version.parent;
+        }
+
+        if let Some(parent) = version.parent {
+            let parent = self.versions.get_mut(&parent).unwrap();
+
+            let position = parent.children.iter().position(|child| *child == id);
+            parent.children.splice(
+                position.unwrap()..position.unwrap() + 1,
+                version.children.iter().cloned(),
+            );
+
+            if parent.redo == Some(id) {
+                parent.redo = version.redo;
+            }
+        }
+    }
+}
+
+/// Counts the bytes of a value, visiting every tree node once.
+trait Footprint {
+    fn footprint(&self, visited: &mut HashSet<usize>, usage: &mut MemoryUsage);
+}
+
+fn tree_footprint<T: Clone + Debug>(
+    vec: &RrbVec<T>,
+    visited: &mut HashSet<usize>,
+    usage: &mut MemoryUsage,
+) {
+    usage.unique_bytes += mem::size_of::<RrbVec<T>>();
+
+    vec.visit_shared_nodes(visited, |node, is_shared| {
+        if is_shared {
+            usage.shared_bytes += node.bytes;
+        } else {
+            usage.unique_bytes += node.bytes;
+        }
+    });
+}
+
+impl<T: Clone + Debug> Footprint for RrbVec<T> {
+    fn footprint(&self, visited: &mut HashSet<usize>, usage: &mut MemoryUsage) {
+        tree_footprint(self, visited, usage);
+    }
+}
+
+impl<T: Clone + Debug> Footprint for PVec<T> {
+    fn footprint(&self, visited: &mut HashSet<usize>, usage: &mut MemoryUsage) {
+        match self.0 {
+            Representation::Flat(ref vec) => {
+                usage.unique_bytes += mem::size_of::<PVec<T>>();
+                usage.unique_bytes += vec.capacity() * mem::size_of::<T>();
+            }
+            Representation::Tree(ref vec) => tree_footprint(vec, visited, usage),
+        }
+    }
+}
+
+macro_rules! impl_memory_usage {
+    ($vec:ident) => {
+        impl<T: Clone + Debug> History<$vec<T>> {
+            /// Reports how many bytes of the retained versions are shared,
+            /// based on the reference counts of the tree nodes, and how many
+            /// belong to a single version. Heap memory owned by the elements
+            /// themselves is not taken into account.
+            pub fn memory_usage(&self) -> MemoryUsage {
+                let mut visited = HashSet::new();
+                let mut usage = MemoryUsage::default();
+
+                for version in self.versions.values() {
+                    version.value.footprint(&mut visited, &mut usage);
+                }
+
+                usage
+            }
+        }
+    };
+}
+
+impl_memory_usage!(RrbVec);
+impl_memory_usage!(PVec);
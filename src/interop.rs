@@ -0,0 +1,49 @@
+extern crate im;
+extern crate im_rc;
+
+use super::{PVec, Representation};
+use std::fmt::Debug;
+
+macro_rules! impl_interop {
+    ($im:ident) => {
+        impl<'a, T: Clone + Debug> From<&'a $im::Vector<T>> for PVec<T> {
+            fn from(vector: &$im::Vector<T>) -> Self {
+                let mut vec = Vec::with_capacity(vector.len());
+
+                for leaf in vector.leaves() {
+                    vec.extend_from_slice(leaf);
+                }
+
+                PVec(Representation::Flat(vec), false)
+            }
+        }
+
+        impl<T: Clone + Debug> From<$im::Vector<T>> for PVec<T> {
+            fn from(vector: $im::Vector<T>) -> Self {
+                // elements are moved out of the chunks which are not shared
+                PVec(Representation::Flat(vector.into_iter().collect()), false)
+            }
+        }
+
+        impl<'a, T: Clone + Debug> From<&'a PVec<T>> for $im::Vector<T> {
+            fn from(vec: &PVec<T>) -> Self {
+                match vec.0 {
+                    Representation::Flat(ref vec) => $im::Vector::from(vec),
+                    Representation::Tree(ref vec) => $im::Vector::from(vec),
+                }
+            }
+        }
+
+        impl<T: Clone + Debug> From<PVec<T>> for $im::Vector<T> {
+            fn from(vec: PVec<T>) -> Self {
+                match vec.0 {
+                    Representation::Flat(vec) => $im::Vector::from(vec),
+                    Representation::Tree(vec) => $im::Vector::<T>::from(vec),
+                }
+            }
+        }
+    };
+}
+
+impl_interop!(im);
+impl_interop!(im_rc);
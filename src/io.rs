@@ -0,0 +1,344 @@
+//! A module providing implementation of the standard
+//! I/O traits for the byte vectors.
+
+use crate::core::RrbVec;
+use crate::{PVec, Representation, BRANCH_FACTOR};
+use std::cmp;
+use std::io;
+use std::io::{BufRead, IoSlice, Read, Seek, SeekFrom, Write};
+
+/// The number of leaves passed to a single vectored write.
+const LEAVES_PER_WRITE: usize = 64;
+
+/// A cursor over a byte vector, which implements [Read](std::io::Read),
+/// [BufRead](std::io::BufRead), [Seek](std::io::Seek) and [Write](std::io::Write).
+///
+/// Elements of the tree-based vectors are stored as options, hence the
+/// bytes are copied out a leaf at a time into the buffer of the cursor.
+/// Writes overwrite the existing bytes a leaf at a time, copying each
+/// affected leaf once if it is shared with other vectors, and append the
+/// remaining ones through the tail of the vector.
+/// Similarly to [std::io::Cursor](std::io::Cursor), writing past the end
+/// fills the gap with zeroes.
+#[derive(Debug, Clone)]
+pub struct Cursor<V> {
+    inner: V,
+    pos: u64,
+    buf: [u8; BRANCH_FACTOR],
+    buf_pos: usize,
+    buf_len: usize,
+}
+
+trait Chunks {
+    fn bytes_len(&self) -> usize;
+
+    /// Copies the bytes of the leaf holding the given index, starting
+    /// from the byte at the index, and returns the number of copied bytes.
+    fn copy_chunk(&self, index: usize, buf: &mut [u8; BRANCH_FACTOR]) -> usize;
+
+    /// Writes the bytes starting from the given index, overwriting the
+    /// existing ones and appending the rest. The gap between the end of
+    /// the vector and the index is filled with zeroes.
+    fn write_bytes(&mut self, index: usize, data: &[u8]);
+}
+
+#[inline(always)]
+fn copy_options(chunk: &[Option<u8>], buf: &mut [u8; BRANCH_FACTOR]) -> usize {
+    for (byte, item) in buf.iter_mut().zip(chunk) {
+        *byte = item.unwrap();
+    }
+
+    chunk.len()
+}
+
+impl Chunks for RrbVec<u8> {
+    #[inline(always)]
+    fn bytes_len(&self) -> usize {
+        self.len()
+    }
+
+    #[inline(always)]
+    fn copy_chunk(&self, index: usize, buf: &mut [u8; BRANCH_FACTOR]) -> usize {
+        copy_options(self.chunk(index), buf)
+    }
+
+    fn write_bytes(&mut self, mut index: usize, mut data: &[u8]) {
+        while self.len() < index {
+            let len = cmp::min(index - self.len(), BRANCH_FACTOR);
+            self.extend_from_slice(&[0; BRANCH_FACTOR][..len]);
+        }
+
+        // every leaf is copied at most once, if it is shared
+        while index < self.len() && !data.is_empty() {
+            let chunk = self.chunk_mut(index);
+            let len = cmp::min(chunk.len(), data.len());
+
+            for (item, byte) in chunk.iter_mut().zip(&data[..len]) {
+                *item = Some(*byte);
+            }
+
+            index += len;
+            data = &data[len..];
+        }
+
+        self.extend_from_slice(data);
+    }
+}
+
+impl Chunks for PVec<u8> {
+    #[inline(always)]
+    fn bytes_len(&self) -> usize {
+        self.len()
+    }
+
+    #[inline(always)]
+    fn copy_chunk(&self, index: usize, buf: &mut [u8; BRANCH_FACTOR]) -> usize {
+        match self.0 {
+            Representation::Flat(ref vec) => {
+                let len = cmp::min(vec.len() - index, BRANCH_FACTOR);
+                buf[..len].copy_from_slice(&vec[index..index + len]);
+                len
+            }
+            Representation::Tree(ref vec) => copy_options(vec.chunk(index), buf),
+        }
+    }
+
+    fn write_bytes(&mut self, index: usize, data: &[u8]) {
+        match self.0 {
+            Representation::Flat(ref mut vec) => {
+                if vec.len() < index {
+                    vec.resize(index, 0);
+                }
+
+                let len = cmp::min(vec.len() - index, data.len());
+
+                vec[index..index + len].copy_from_slice(&data[..len]);
+                vec.extend_from_slice(&data[len..]);
+            }
+            Representation::Tree(ref mut vec) => vec.write_bytes(index, data),
+        }
+    }
+}
+
+/// Writes all slices, advancing through them on partial writes.
+fn write_all_vectored<W: Write>(writer: &mut W, mut slices: &mut [&[u8]]) -> io::Result<()> {
+    while !slices.is_empty() {
+        if slices[0].is_empty() {
+            slices = &mut slices[1..];
+            continue;
+        }
+
+        let io_slices: Vec<IoSlice> = slices.iter().map(|slice| IoSlice::new(slice)).collect();
+
+        match writer.write_vectored(&io_slices) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ));
+            }
+            Ok(mut written) => {
+                while written > 0 {
+                    if written >= slices[0].len() {
+                        written -= slices[0].len();
+                        slices = &mut slices[1..];
+                    } else {
+                        slices[0] = &slices[0][written..];
+                        written = 0;
+                    }
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes all bytes of the vector, passing a batch
+/// of leaves to every call of the vectored write.
+fn write_chunks_to<V: Chunks, W: Write>(vec: &V, writer: &mut W) -> io::Result<()> {
+    let len = vec.bytes_len();
+
+    let mut leaves = vec![[0; BRANCH_FACTOR]; cmp::min(LEAVES_PER_WRITE, len / BRANCH_FACTOR + 1)];
+    let mut lens = vec![0; leaves.len()];
+
+    let mut index = 0;
+
+    while index < len {
+        let mut count = 0;
+
+        while count < leaves.len() && index < len {
+            lens[count] = vec.copy_chunk(index, &mut leaves[count]);
+            index += lens[count];
+            count += 1;
+        }
+
+        let mut slices: Vec<&[u8]> = leaves
+            .iter()
+            .zip(lens.iter())
+            .take(count)
+            .map(|(leaf, len)| &leaf[..*len])
+            .collect();
+
+        write_all_vectored(writer, &mut slices)?;
+    }
+
+    Ok(())
+}
+
+impl<V> Cursor<V> {
+    /// Creates a new cursor over the given vector, positioned at its start.
+    pub fn new(inner: V) -> Self {
+        Cursor {
+            inner,
+            pos: 0,
+            buf: [0; BRANCH_FACTOR],
+            buf_pos: 0,
+            buf_len: 0,
+        }
+    }
+
+    /// Consumes the cursor, returning the underlying vector.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    /// Returns a reference to the underlying vector.
+    pub fn get_ref(&self) -> &V {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying vector.
+    pub fn get_mut(&mut self) -> &mut V {
+        self.discard_buf();
+        &mut self.inner
+    }
+
+    /// Returns the current position of the cursor.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the position of the cursor.
+    pub fn set_position(&mut self, pos: u64) {
+        self.discard_buf();
+        self.pos = pos;
+    }
+
+    #[inline(always)]
+    fn discard_buf(&mut self) {
+        self.buf_pos = 0;
+        self.buf_len = 0;
+    }
+}
+
+macro_rules! impl_io {
+    ($vec:ident) => {
+        impl $vec<u8> {
+            /// Writes all bytes of the vector to the writer. The bytes are
+            /// copied out a leaf at a time, and leaves are written in batches
+            /// through [write_vectored](std::io::Write::write_vectored).
+            pub fn write_all_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                write_chunks_to(self, writer)
+            }
+        }
+
+        impl Read for Cursor<$vec<u8>> {
+            fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+                let mut read = 0;
+
+                while read < out.len() {
+                    let len = {
+                        let available = self.fill_buf()?;
+                        let len = cmp::min(available.len(), out.len() - read);
+
+                        out[read..read + len].copy_from_slice(&available[..len]);
+                        len
+                    };
+
+                    if len == 0 {
+                        break;
+                    }
+
+                    self.consume(len);
+                    read += len;
+                }
+
+                Ok(read)
+            }
+        }
+
+        impl BufRead for Cursor<$vec<u8>> {
+            /// Returns the rest of the leaf at the current position.
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                if self.buf_pos == self.buf_len {
+                    self.buf_pos = 0;
+                    self.buf_len = if self.pos < self.inner.len() as u64 {
+                        self.inner.copy_chunk(self.pos as usize, &mut self.buf)
+                    } else {
+                        0
+                    };
+                }
+
+                Ok(&self.buf[self.buf_pos..self.buf_len])
+            }
+
+            fn consume(&mut self, amt: usize) {
+                let amt = cmp::min(amt, self.buf_len - self.buf_pos);
+
+                self.buf_pos += amt;
+                self.pos += amt as u64;
+            }
+        }
+
+        impl Seek for Cursor<$vec<u8>> {
+            fn seek(&mut self, style: SeekFrom) -> io::Result<u64> {
+                let (base, offset) = match style {
+                    SeekFrom::Start(pos) => {
+                        self.set_position(pos);
+                        return Ok(pos);
+                    }
+                    SeekFrom::End(offset) => (self.inner.len() as u64, offset),
+                    SeekFrom::Current(offset) => (self.pos, offset),
+                };
+
+                let pos = if offset >= 0 {
+                    base.checked_add(offset as u64)
+                } else {
+                    base.checked_sub(offset.wrapping_neg() as u64)
+                };
+
+                match pos {
+                    Some(pos) => {
+                        self.set_position(pos);
+                        Ok(pos)
+                    }
+                    None => Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )),
+                }
+            }
+        }
+
+        impl Write for Cursor<$vec<u8>> {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                self.discard_buf();
+
+                self.inner.write_bytes(self.pos as usize, data);
+
+                self.pos += data.len() as u64;
+                Ok(data.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_io!(RrbVec);
+impl_io!(PVec);
@@ -6,7 +6,7 @@
 use super::PVec;
 use super::Representation;
 
-use std::fmt::Debug;
+use core::fmt::Debug;
 
 #[cfg(all(test, not(feature = "small_branch")))]
 pub const BRANCH_FACTOR: usize = 32;
@@ -14,10 +14,12 @@ pub const BRANCH_FACTOR: usize = 32;
 #[cfg(all(test, feature = "small_branch"))]
 pub const BRANCH_FACTOR: usize = 4;
 
-use crate::core::iter::RrbVecIter;
+use crate::core::iter::{RrbVecIter, RrbVecRefIter};
 use crate::core::RrbVec;
-use std::iter::FromIterator;
-use std::vec::IntoIter as VecIter;
+use alloc::vec::IntoIter as VecIter;
+use alloc::vec::Vec;
+use core::iter::{FromIterator, Sum};
+use core::slice;
 
 #[cfg(all(feature = "arc", feature = "rayon_iter"))]
 use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
@@ -113,6 +115,65 @@ impl<T: Clone + Debug> IntoIterator for PVec<T> {
     }
 }
 
+/// This struct owns an iterator over references to the
+/// elements of either the standard vector or RrbVec.
+#[derive(Debug, Clone)]
+pub struct PVecRefIter<'a, T: Clone + Debug> {
+    iter_vec: Option<slice::Iter<'a, T>>,
+    iter_rrbvec: Option<RrbVecRefIter<'a, T>>,
+}
+
+impl<T: Clone + Debug> PVec<T> {
+    /// Returns an iterator over references to the elements.
+    pub fn iter(&self) -> PVecRefIter<'_, T> {
+        match self.0 {
+            Representation::Flat(ref vec) => PVecRefIter {
+                iter_vec: Some(vec.iter()),
+                iter_rrbvec: None,
+            },
+            Representation::Tree(ref vec) => PVecRefIter {
+                iter_vec: None,
+                iter_rrbvec: Some(vec.iter()),
+            },
+        }
+    }
+}
+
+impl<'a, T: Clone + Debug> Iterator for PVecRefIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(iter_vec) = self.iter_vec.as_mut() {
+            iter_vec.next()
+        } else if let Some(iter_rrbvec) = self.iter_rrbvec.as_mut() {
+            iter_rrbvec.next()
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if let Some(iter_vec) = self.iter_vec.as_ref() {
+            iter_vec.size_hint()
+        } else if let Some(iter_rrbvec) = self.iter_rrbvec.as_ref() {
+            iter_rrbvec.size_hint()
+        } else {
+            (0, None)
+        }
+    }
+}
+
+impl<'a, T: Clone + Debug> ExactSizeIterator for PVecRefIter<'a, T> {}
+
+impl<'a, T: Clone + Debug> IntoIterator for &'a PVec<T> {
+    type Item = &'a T;
+    type IntoIter = PVecRefIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 /// This struct is used to implement the
 /// [parallel iterator](https://docs.rs/rayon/1.3.0/rayon/iter/trait.ParallelIterator.html)
 #[derive(Debug, Clone)]
@@ -179,11 +240,11 @@ impl<T: Send + Sync + Debug + Clone> Producer for VecProducer<T> {
     type IntoIter = PVecIter<T>;
 
     fn into_iter(mut self) -> Self::IntoIter {
-        std::mem::replace(&mut self.vec, PVec::new()).into_iter()
+        core::mem::replace(&mut self.vec, PVec::new()).into_iter()
     }
 
     fn split_at(mut self, index: usize) -> (Self, Self) {
-        let mut vec = std::mem::replace(&mut self.vec, PVec::new());
+        let mut vec = core::mem::replace(&mut self.vec, PVec::new());
 
         let right = vec.split_off(index);
         let left = vec;
@@ -201,16 +262,15 @@ where
     where
         I: IntoParallelIterator<Item = T>,
     {
-        par_iter
+        let vecs: Vec<PVec<T>> = par_iter
             .into_par_iter()
             .fold(PVec::new, |mut vec, elem| {
                 vec.push(elem);
                 vec
             })
-            .reduce(PVec::new, |mut list1, mut list2| {
-                list1.append(&mut list2);
-                list1
-            })
+            .collect();
+
+        PVec::concat(vecs)
     }
 }
 
@@ -223,3 +283,15 @@ impl<T: Clone + Debug> FromIterator<T> for PVec<T> {
         vec
     }
 }
+
+impl<T: Clone + Debug> Sum<PVec<T>> for PVec<T> {
+    fn sum<I: Iterator<Item = PVec<T>>>(iter: I) -> Self {
+        PVec::concat(iter)
+    }
+}
+
+impl<'a, T: 'a + Clone + Debug> Sum<&'a PVec<T>> for PVec<T> {
+    fn sum<I: Iterator<Item = &'a PVec<T>>>(iter: I) -> Self {
+        PVec::concat(iter.cloned())
+    }
+}
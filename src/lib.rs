@@ -16,7 +16,36 @@
 //!
 //! All vector types in the list expose exactly the same set of
 //! operations with identical API. The difference is only in the
-//! cost of operations.
+//! cost of operations. The [Vector](crate::Vector) trait captures the
+//! core of that API, so that code can be generic over the vector type,
+//! including the standard [vec](std::vec::Vec).
+//!
+//! On top of that, [Rope](crate::rope::Rope) provides a persistent
+//! string with cheap snapshots, which is backed by the same tree,
+//! [PBitVec](crate::bitvec::PBitVec) packs bits into words stored in its leaves,
+//! while [Cursor](crate::io::Cursor) exposes byte vectors through
+//! the standard I/O traits. [History](crate::history::History) records
+//! versions of a vector for undo and redo, relying on cheap clones, and
+//! [MerkleStore](crate::core::merkle::MerkleStore) replicates vectors
+//! by exchanging only the nodes missing on the other side.
+//! [DiskRrbVec](crate::core::disk::DiskRrbVec) keeps its nodes in a
+//! file or another [NodeStore](crate::core::disk::NodeStore), and loads
+//! them on demand, for vectors which do not fit into memory.
+//! [Deduplicator](crate::core::dedup::Deduplicator) makes vectors
+//! built independently share the nodes with equal content, while
+//! [render](crate::core::render) draws the trees of many versions,
+//! showing the nodes they share.
+//!
+//! # Panic safety
+//! Cloning an element, which happens when a node shared with other vectors is
+//! copied on write, and dropping an element may panic. If that happens in the
+//! middle of an operation, the vector stays valid and can be used further, yet
+//! it might lose some of its elements: operations that restructure the tree,
+//! namely `pop`, `split_off`, `append`, `compact` and the ones built on top of
+//! them, leave the vectors they modify empty, while the other ones keep the
+//! elements written before the panic. The vectors sharing nodes with the
+//! modified one are never affected. As usual, a panic while dropping the
+//! elements during unwinding aborts the process.
 //!
 //! # Features
 //! [RbVec](crate::core::RbVec) and [RrbVec](crate::core::RrbVec)
@@ -25,7 +54,9 @@
 //! compile all vectors using [Arc](https://doc.rust-lang.org/std/sync/struct.Arc.html)
 //! if needed, especially when passing instances between threads. To compile the
 //! library with [Arc](https://doc.rust-lang.org/std/sync/struct.Arc.html), use
-//! the `arc` feature flag.
+//! the `arc` feature flag. The same flag enables
+//! [AtomicRrbVec](crate::core::atomic::AtomicRrbVec), a cell which lets
+//! many threads read snapshots of a vector while it is being updated.
 //!
 //! All types implement [Rayon's IntoParallelIterator trait](https://docs.rs/rayon/1.3.0/rayon/iter/trait.IntoParallelIterator.html),
 //! that enables the conversion into a parallel iterator. As dependency on
@@ -33,14 +64,33 @@
 //! iterator implementation by passing both the `arc` and `rayon_iter`
 //! feature flags.
 //!
+//! With the `im_interop` feature flag, [RrbVec](crate::core::RrbVec) and
+//! [PVec](crate::PVec) can be converted to and from the vectors of the
+//! [im](https://docs.rs/im) and [im-rc](https://docs.rs/im-rc) crates
+//! through the `From` trait, which eases migrating code between the libraries.
+//! The `arbitrary` feature flag implements the [Arbitrary](https://docs.rs/arbitrary)
+//! trait for all vector types, which generates trees of random shapes, and
+//! enables the [fuzz](crate::fuzz) harness.
+//!
 //! By default, the tree-based vectors have nodes that are 32 elements wide. The
 //! maximum number of child nodes is also referred to as the branching factor.
 //! This value can be changed to 4 if necessary, by specifying the `small_branch`
 //! feature flag. Though, the default value of 32 is recommended
 //! for optimal performance.
-
+//!
+//! The library depends on the standard library through the `std` feature flag,
+//! which is enabled by default. Disabling default features builds the vectors,
+//! iterators, [Rope](crate::rope::Rope), [PBitVec](crate::bitvec::PBitVec) and
+//! the [Vector](crate::Vector) trait on top of `core` and `alloc` only, for
+//! targets without an operating system. The modules relying on I/O, hashing or
+//! threads, as well as the `serde_serializer`, `rayon_iter` and `im_interop`
+//! flags, require `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
 #[cfg(all(feature = "arc", feature = "rayon_iter"))]
 extern crate rayon;
 
@@ -48,12 +98,39 @@ extern crate rayon;
 #[cfg(feature = "serde_serializer")]
 extern crate serde_json;
 
-use std::fmt::Debug;
-use std::ops;
+use ::core::cmp;
+use ::core::fmt;
+use ::core::fmt::Debug;
+use ::core::ops;
+use alloc::vec::Vec;
 
+pub mod bitvec;
+#[macro_use]
 pub mod core;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod io;
 pub mod iter;
+pub mod rope;
+pub mod vector;
+
+#[cfg(feature = "serde_serializer")]
+mod serializer;
+
+#[cfg(feature = "im_interop")]
+mod interop;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 
+pub use crate::vector::Vector;
+
+#[cfg(feature = "std")]
+use crate::core::stats::VecStats;
+use crate::core::DebugStructure;
 use crate::core::RrbVec;
 
 #[cfg(not(feature = "small_branch"))]
@@ -62,7 +139,7 @@ const BRANCH_FACTOR: usize = 32;
 #[cfg(feature = "small_branch")]
 const BRANCH_FACTOR: usize = 4;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Representation<T> {
     Flat(Vec<T>),
     Tree(RrbVec<T>),
@@ -107,23 +184,53 @@ impl<T: Clone + Debug> Representation<T> {
     }
 }
 
+/// The representation currently backing a [PVec](crate::PVec).
+///
+/// A vector spills from the flat representation into the tree when it is
+/// cloned, or when a tree-based vector is appended to it, and it never
+/// returns to the flat one. The clone produced by the spill reports
+/// `Spilled`, while the cloned vector keeps its buffer and reports `Flat`
+/// until it is cloned again, which spills once more. The clones and the
+/// pieces of a spilled vector report `Spilled` as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backing {
+    /// The standard vector, which spills into the tree on every clone.
+    Flat,
+    /// The [RrbVec](crate::core::RrbVec), constructed as a tree.
+    Tree,
+    /// The [RrbVec](crate::core::RrbVec), spilled from the flat representation.
+    Spilled,
+}
+
 /// A persistent vector that is backed by the flat
 /// representation - the standard vector, or the
 /// tree-based vector when cloned.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct PVec<T>(Representation<T>);
+///
+/// The second field tells whether the tree has spilled from the
+/// flat representation, and it is ignored by the comparisons.
+pub struct PVec<T>(Representation<T>, bool);
 
 impl<T: Clone + Debug> PVec<T> {
     /// Constructs a new, empty vector backed by the
     /// standard vector internally.
     pub fn new() -> Self {
-        PVec(Representation::Flat(Vec::with_capacity(BRANCH_FACTOR)))
+        PVec(
+            Representation::Flat(Vec::with_capacity(BRANCH_FACTOR)),
+            false,
+        )
     }
 
     /// Constructs a new, empty vector backed by the
     /// [RrbVec](crate::core::RrbVec).
     pub fn new_with_tree() -> Self {
-        PVec(Representation::Tree(RrbVec::new()))
+        PVec(Representation::Tree(RrbVec::new()), false)
+    }
+
+    /// Constructs a vector of `n` clones of the value backed by the
+    /// [RrbVec](crate::core::RrbVec), whose nodes are shared by all
+    /// positions until they are modified.
+    pub fn repeat(value: T, n: usize) -> Self {
+        PVec(Representation::Tree(RrbVec::from_elem(value, n)), false)
     }
 
     /// Adds an element to the back of a collection.
@@ -161,6 +268,66 @@ impl<T: Clone + Debug> PVec<T> {
         }
     }
 
+    /// Returns a mutable reference to an element at the given position
+    /// or None if out of bounds, along with true if any of the nodes on
+    /// the path to the element was shared, and had to be copied.
+    pub fn get_mut_cow(&mut self, index: usize) -> Option<(&mut T, bool)> {
+        match self.0 {
+            Representation::Flat(ref mut vec) => vec.get_mut(index).map(|item| (item, false)),
+            Representation::Tree(ref mut vec) => vec.get_mut_cow(index),
+        }
+    }
+
+    /// Returns true if none of the elements of the vector is shared with
+    /// other vectors. The flat representation is never shared.
+    pub fn is_unique(&self) -> bool {
+        match self.0 {
+            Representation::Flat(..) => true,
+            Representation::Tree(ref vec) => vec.is_unique(),
+        }
+    }
+
+    /// Returns true if both vectors refer to the same tree, as described at
+    /// [RrbVec::ptr_eq](crate::core::RrbVec::ptr_eq). The flat representation
+    /// is never shared, thus it is only equal to itself.
+    pub fn ptr_eq(&self, that: &PVec<T>) -> bool {
+        match (&self.0, &that.0) {
+            (Representation::Tree(ref a), Representation::Tree(ref b)) => a.ptr_eq(b),
+            _ => ::core::ptr::eq(self, that),
+        }
+    }
+
+    /// Copies all of the nodes shared with other vectors up front,
+    /// so that subsequent writes do not pay for copy-on-write.
+    pub fn make_unique(&mut self) {
+        if let Representation::Tree(ref mut vec) = self.0 {
+            vec.make_unique();
+        }
+    }
+
+    /// Copies all of the nodes shared with other vectors
+    /// up front, unsharing the subtrees in parallel.
+    #[cfg(all(feature = "arc", feature = "rayon_iter"))]
+    pub fn par_make_unique(&mut self)
+    where
+        T: Send + Sync,
+    {
+        if let Representation::Tree(ref mut vec) = self.0 {
+            vec.par_make_unique();
+        }
+    }
+
+    /// Copies the nodes shared with other vectors on the paths
+    /// to the elements in the range, leaving the rest shared.
+    pub fn make_unique_range(&mut self, range: ops::Range<usize>) {
+        match self.0 {
+            Representation::Flat(ref vec) => {
+                assert!(range.start <= range.end && range.end <= vec.len())
+            }
+            Representation::Tree(ref mut vec) => vec.make_unique_range(range),
+        }
+    }
+
     /// Returns the number of elements in the vector.
     pub fn len(&self) -> usize {
         match self.0 {
@@ -174,6 +341,15 @@ impl<T: Clone + Debug> PVec<T> {
         self.len() == 0
     }
 
+    /// Resizes the vector in-place, so that its length is equal to `n`,
+    /// either by truncating it, or by extending it with clones of the value.
+    pub fn resize(&mut self, n: usize, value: T) {
+        match self.0 {
+            Representation::Flat(ref mut vec) => vec.resize(n, value),
+            Representation::Tree(ref mut vec) => vec.resize(n, value),
+        }
+    }
+
     /// Moves all the elements of `that` into `Self` by concatenating
     /// the underlying tree structures, leaving `other` empty.
     /// Note, if either of vectors is tree-based, the resulting
@@ -192,8 +368,11 @@ impl<T: Clone + Debug> PVec<T> {
             vec.append(that.as_mut_tree());
 
             *this = Representation::Tree(vec);
+            self.1 = true;
         } else if that_is_flat {
             let mut vec = RrbVec::from(that.as_flat());
+            that.as_mut_flat().clear();
+
             this.as_mut_tree().append(&mut vec);
         } else {
             this.as_mut_tree().append(that.as_mut_tree());
@@ -211,7 +390,93 @@ impl<T: Clone + Debug> PVec<T> {
             Representation::Tree(ref mut vec) => Representation::Tree(vec.split_off(mid)),
         };
 
-        PVec(representation)
+        PVec(representation, self.1)
+    }
+
+    /// Splits the vector into `indices.len() + 1` pieces at the given
+    /// positions, which must be sorted and must not exceed the length.
+    /// The pieces keep the representation of the vector.
+    pub fn split_at_many(self, indices: &[usize]) -> Vec<Self> {
+        let spilled = self.1;
+
+        match self.0 {
+            Representation::Flat(vec) => {
+                let mut elements = vec.into_iter();
+
+                crate::core::split_ranges(indices, elements.len())
+                    .into_iter()
+                    .map(|range| {
+                        let piece = elements.by_ref().take(range.len()).collect();
+                        PVec(Representation::Flat(piece), false)
+                    })
+                    .collect()
+            }
+            Representation::Tree(vec) => vec
+                .split_at_many(indices)
+                .into_iter()
+                .map(|piece| PVec(Representation::Tree(piece), spilled))
+                .collect(),
+        }
+    }
+
+    /// Shrinks the memory used by the vector. The flat representation
+    /// shrinks its capacity, while the tree-based one is compacted into
+    /// fully dense leaves and regular branches.
+    pub fn shrink_to_fit(&mut self) {
+        match self.0 {
+            Representation::Flat(ref mut vec) => vec.shrink_to_fit(),
+            Representation::Tree(ref mut vec) => vec.compact(),
+        }
+    }
+
+    /// Returns a wrapper, which prints the representation
+    /// backing the vector with `Debug`, instead of its elements.
+    pub fn debug_structure(&self) -> DebugStructure<'_, Self> {
+        DebugStructure(self)
+    }
+
+    /// Returns the representation backing the vector.
+    pub fn backing(&self) -> Backing {
+        match self.0 {
+            Representation::Flat(..) => Backing::Flat,
+            Representation::Tree(..) if self.1 => Backing::Spilled,
+            Representation::Tree(..) => Backing::Tree,
+        }
+    }
+
+    /// Returns the shape and the memory usage of the vector. The flat
+    /// representation reports only the capacity of its buffer in bytes.
+    #[cfg(feature = "std")]
+    pub fn stats(&self) -> VecStats {
+        match self.0 {
+            Representation::Flat(ref vec) => VecStats {
+                heap_bytes: vec.capacity() * ::core::mem::size_of::<T>(),
+                ..VecStats::default()
+            },
+            Representation::Tree(ref vec) => vec.stats(),
+        }
+    }
+}
+
+impl_split_vec!(balanced [T: Clone + Debug] PVec<T>);
+
+impl<T: Clone + Debug> Debug for PVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Representation::Flat(ref vec) => crate::core::debug_list(f, vec.len(), |i| &vec[i]),
+            Representation::Tree(ref vec) => vec.fmt(f),
+        }
+    }
+}
+
+impl<'a, T: Clone + Debug> Debug for DebugStructure<'a, PVec<T>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 .0 {
+            Representation::Flat(ref vec) => f.debug_tuple("Flat").field(vec).finish(),
+            Representation::Tree(ref vec) => {
+                f.debug_tuple("Tree").field(&vec.debug_structure()).finish()
+            }
+        }
     }
 }
 
@@ -223,12 +488,30 @@ impl<T: Clone + Debug> Default for PVec<T> {
 
 impl<T: Clone + Debug> Clone for PVec<T> {
     fn clone(&self) -> Self {
-        let representation = match self.0 {
-            Representation::Flat(ref vec) => Representation::spill(vec),
-            Representation::Tree(ref vec) => Representation::Tree(vec.clone()),
-        };
+        match self.0 {
+            Representation::Flat(ref vec) => PVec(Representation::spill(vec), true),
+            Representation::Tree(ref vec) => PVec(Representation::Tree(vec.clone()), self.1),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for PVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq> Eq for PVec<T> {}
+
+impl<T: PartialOrd> PartialOrd for PVec<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
 
-        PVec(representation)
+impl<T: Ord> Ord for PVec<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
     }
 }
 
@@ -0,0 +1,407 @@
+//! A module providing a persistent text rope based on RrbTree.
+
+use crate::core::measure::{self, Measure, MeasuredRrbVec};
+use alloc::string::ToString;
+use core::convert::Infallible;
+use core::fmt;
+use core::fmt::Write;
+use core::ops;
+use core::str;
+use core::str::FromStr;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct TextSummary {
+    chars: usize,
+    lines: usize,
+}
+
+#[derive(Clone, Debug)]
+struct TextMeasure;
+
+#[inline(always)]
+fn is_char_start(byte: u8) -> bool {
+    (byte & 0xC0) != 0x80
+}
+
+impl Measure<u8> for TextMeasure {
+    type Summary = TextSummary;
+
+    #[inline(always)]
+    fn identity() -> TextSummary {
+        TextSummary::default()
+    }
+
+    #[inline(always)]
+    fn measure(byte: &u8) -> TextSummary {
+        TextSummary {
+            chars: is_char_start(*byte) as usize,
+            lines: (*byte == b'\n') as usize,
+        }
+    }
+
+    #[inline(always)]
+    fn combine(left: &TextSummary, right: &TextSummary) -> TextSummary {
+        TextSummary {
+            chars: left.chars + right.chars,
+            lines: left.lines + right.lines,
+        }
+    }
+}
+
+/// A persistent string, which stores UTF-8 bytes in the leaves of a
+/// relaxed RrbTree. Every node of the tree caches the number of chars and
+/// line breaks in its subtree, so that conversions between byte, char and
+/// line indices take logarithmic time. Clones are cheap, as the nodes are
+/// shared between them, while insertions and removals are implemented
+/// through the split and append operations of the tree.
+///
+/// All indices accepted by the methods are byte indices, unless stated
+/// otherwise. Methods panic if an index does not lie on a char boundary.
+#[derive(Clone, Default)]
+pub struct Rope {
+    bytes: MeasuredRrbVec<u8, TextMeasure>,
+}
+
+/// An iterator over the bytes of a [Rope](crate::rope::Rope).
+#[derive(Clone, Debug)]
+pub struct Bytes<'a> {
+    iter: measure::Iter<'a, u8, TextMeasure>,
+}
+
+/// An iterator over the chars of a [Rope](crate::rope::Rope).
+#[derive(Clone, Debug)]
+pub struct Chars<'a> {
+    bytes: Bytes<'a>,
+}
+
+/// An iterator over the lines of a [Rope](crate::rope::Rope). Lines are
+/// returned as ropes sharing the nodes with the original one, and do not
+/// include the line terminators, which are either `\n` or `\r\n`.
+#[derive(Clone, Debug)]
+pub struct Lines<'a> {
+    rope: &'a Rope,
+    line: usize,
+    lines: usize,
+}
+
+impl Rope {
+    /// Constructs a new, empty rope.
+    pub fn new() -> Self {
+        Rope {
+            bytes: MeasuredRrbVec::new(),
+        }
+    }
+
+    /// Returns the length of the rope in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns true if the rope has a length of 0.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns the number of chars in the rope.
+    pub fn len_chars(&self) -> usize {
+        self.bytes.summary().chars
+    }
+
+    /// Returns the number of lines in the rope, which is one
+    /// more than the number of line breaks in the text.
+    pub fn len_lines(&self) -> usize {
+        self.bytes.summary().lines + 1
+    }
+
+    /// Checks that the byte at the given index is the first byte
+    /// of a char. The start and the end of the rope are boundaries.
+    pub fn is_char_boundary(&self, index: usize) -> bool {
+        if index < self.len() {
+            is_char_start(self.bytes[index])
+        } else {
+            index == self.len()
+        }
+    }
+
+    #[inline(always)]
+    fn assert_char_boundary(&self, index: usize) {
+        assert!(
+            self.is_char_boundary(index),
+            "byte index {} is not a char boundary in Rope of length {}",
+            index,
+            self.len()
+        );
+    }
+
+    #[inline(always)]
+    fn assert_range(&self, range: &ops::Range<usize>) {
+        assert!(
+            range.start <= range.end,
+            "range start {} is greater than its end {}",
+            range.start,
+            range.end
+        );
+
+        self.assert_char_boundary(range.start);
+        self.assert_char_boundary(range.end);
+    }
+
+    /// Appends the string to the end of the rope.
+    pub fn push_str(&mut self, string: &str) {
+        for byte in string.bytes() {
+            self.bytes.push(byte);
+        }
+    }
+
+    /// Inserts the string at the given byte index.
+    pub fn insert_str(&mut self, index: usize, string: &str) {
+        self.assert_char_boundary(index);
+
+        let mut right = self.bytes.split_off(index);
+
+        self.push_str(string);
+        self.bytes.append(&mut right);
+    }
+
+    /// Removes the bytes in the given range from the rope.
+    pub fn remove(&mut self, range: ops::Range<usize>) {
+        self.assert_range(&range);
+
+        let mut right = self.bytes.split_off(range.end);
+
+        self.bytes.split_off(range.start);
+        self.bytes.append(&mut right);
+    }
+
+    /// Returns a rope containing the bytes in the given range.
+    /// The returned rope shares nodes with the original one.
+    pub fn slice(&self, range: ops::Range<usize>) -> Rope {
+        self.assert_range(&range);
+
+        let mut bytes = self.bytes.clone();
+        bytes.split_off(range.end);
+
+        Rope {
+            bytes: bytes.split_off(range.start),
+        }
+    }
+
+    /// Splits the rope into two at the given byte index. Returns a rope
+    /// containing the bytes in the range [at, len), while the original
+    /// rope is left containing the bytes [0, at).
+    pub fn split_off(&mut self, at: usize) -> Rope {
+        self.assert_char_boundary(at);
+
+        Rope {
+            bytes: self.bytes.split_off(at),
+        }
+    }
+
+    /// Moves all the text of `that` to the end of `Self`, leaving `that` empty.
+    pub fn append(&mut self, that: &mut Rope) {
+        self.bytes.append(&mut that.bytes);
+    }
+
+    /// Returns the number of chars that start before the given byte index.
+    pub fn byte_to_char(&self, index: usize) -> usize {
+        self.bytes.range_summary(0..index).chars
+    }
+
+    /// Returns the byte index at which the char with the given index
+    /// starts, or the length of the rope for the index past the last char.
+    pub fn char_to_byte(&self, index: usize) -> usize {
+        assert!(
+            index <= self.len_chars(),
+            "char index {} is out of bounds in Rope of {} chars",
+            index,
+            self.len_chars()
+        );
+
+        self.bytes
+            .find_by_measure(|summary| summary.chars > index)
+            .unwrap_or_else(|| self.len())
+    }
+
+    /// Returns the index of the line the given byte index belongs to.
+    pub fn byte_to_line(&self, index: usize) -> usize {
+        self.bytes.range_summary(0..index).lines
+    }
+
+    /// Returns the byte index at which the line with the given index starts.
+    pub fn line_to_byte(&self, index: usize) -> usize {
+        assert!(
+            index < self.len_lines(),
+            "line index {} is out of bounds in Rope of {} lines",
+            index,
+            self.len_lines()
+        );
+
+        if index == 0 {
+            0
+        } else {
+            self.bytes
+                .find_by_measure(|summary| summary.lines >= index)
+                .unwrap()
+                + 1
+        }
+    }
+
+    /// Returns the index of the line the char with the given index belongs to.
+    pub fn char_to_line(&self, index: usize) -> usize {
+        self.byte_to_line(self.char_to_byte(index))
+    }
+
+    /// Returns the index of the first char of the line with the given index.
+    pub fn line_to_char(&self, index: usize) -> usize {
+        self.byte_to_char(self.line_to_byte(index))
+    }
+
+    /// Returns an iterator over the bytes of the rope.
+    pub fn bytes(&self) -> Bytes<'_> {
+        Bytes {
+            iter: self.bytes.iter(),
+        }
+    }
+
+    /// Returns an iterator over the chars of the rope.
+    pub fn chars(&self) -> Chars<'_> {
+        Chars {
+            bytes: self.bytes(),
+        }
+    }
+
+    /// Returns an iterator over the lines of the rope. Similarly to
+    /// [str::lines](https://doc.rust-lang.org/std/primitive.str.html#method.lines),
+    /// the final line ending does not produce an empty line.
+    pub fn lines(&self) -> Lines<'_> {
+        let lines = if self.is_empty() || self.bytes[self.len() - 1] == b'\n' {
+            self.len_lines() - 1
+        } else {
+            self.len_lines()
+        };
+
+        Lines {
+            rope: self,
+            line: 0,
+            lines,
+        }
+    }
+}
+
+impl<'a> Iterator for Bytes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        self.iter.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Bytes<'a> {}
+
+impl<'a> Iterator for Chars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let first = self.bytes.next()?;
+
+        let width = match first {
+            0x00..=0x7F => 1,
+            0xF0..=0xFF => 4,
+            0xE0..=0xEF => 3,
+            _ => 2,
+        };
+
+        let mut buf = [first, 0, 0, 0];
+        for byte in buf.iter_mut().take(width).skip(1) {
+            *byte = self.bytes.next().unwrap();
+        }
+
+        str::from_utf8(&buf[..width]).unwrap().chars().next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bytes.len();
+        (len / 4, Some(len))
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = Rope;
+
+    fn next(&mut self) -> Option<Rope> {
+        if self.line == self.lines {
+            return None;
+        }
+
+        let start = self.rope.line_to_byte(self.line);
+        let mut end = if self.line + 1 < self.rope.len_lines() {
+            self.rope.line_to_byte(self.line + 1) - 1
+        } else {
+            self.rope.len()
+        };
+
+        if end > start && self.rope.bytes[end - 1] == b'\r' {
+            end -= 1;
+        }
+
+        self.line += 1;
+        Some(self.rope.slice(start..end))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.lines - self.line;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Lines<'a> {}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ch in self.chars() {
+            f.write_char(ch)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl FromStr for Rope {
+    type Err = Infallible;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(Rope::from(string))
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(string: &str) -> Self {
+        let mut rope = Rope::new();
+        rope.push_str(string);
+        rope
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        self.len() == other.len() && self.bytes().eq(other.bytes())
+    }
+}
+
+impl Eq for Rope {}
+
+impl<'a> PartialEq<&'a str> for Rope {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.len() == other.len() && self.bytes().eq(other.bytes())
+    }
+}
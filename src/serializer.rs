@@ -0,0 +1,23 @@
+extern crate serde;
+
+use self::serde::ser::{Serialize, Serializer};
+use super::{PVec, Representation};
+
+impl<T> Serialize for PVec<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<<S>::Ok, <S>::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Representation::Flat(ref vec) => {
+                serializer.serialize_newtype_variant("PVec", 0, "flat", vec)
+            }
+            Representation::Tree(ref vec) => {
+                serializer.serialize_newtype_variant("PVec", 1, "tree", vec)
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! A module providing the [Vector](crate::Vector) trait, which
+//! captures the operations shared by all vector types, so that
+//! code can be written once and used with any of them.
+
+use super::PVec;
+use crate::core::{RbVec, RrbVec};
+
+#[cfg(feature = "std")]
+use crate::core::disk::{self, DiskRef, DiskRrbVec, NodeStore};
+#[cfg(feature = "std")]
+use crate::core::merkle::Element;
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::iter::FromIterator;
+use core::ops::Deref;
+use core::slice;
+
+/// The operations shared by [PVec](crate::PVec), [RrbVec](crate::core::RrbVec),
+/// [RbVec](crate::core::RbVec), [DiskRrbVec](crate::core::disk::DiskRrbVec)
+/// and the standard [vec](std::vec::Vec). Owning iteration is provided by the
+/// [IntoIterator](https://doc.rust-lang.org/std/iter/trait.IntoIterator.html)
+/// and [FromIterator](https://doc.rust-lang.org/std/iter/trait.FromIterator.html)
+/// traits, which all of the types implement.
+pub trait Vector<T>: Clone + IntoIterator<Item = T> + FromIterator<T> {
+    /// A reference to an element, which is a plain reference for the
+    /// vectors held in memory, and a guard keeping the leaf of the element
+    /// loaded for [DiskRrbVec](crate::core::disk::DiskRrbVec).
+    type Ref<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+
+    /// An iterator over references to the elements.
+    type Iter<'a>: Iterator<Item = Self::Ref<'a>>
+    where
+        Self: 'a;
+
+    /// Constructs a new, empty vector.
+    fn new() -> Self;
+
+    /// Adds an element to the back of the vector.
+    fn push(&mut self, item: T);
+
+    /// Removes the last element from the vector and
+    /// returns it, or None if the vector is empty.
+    fn pop(&mut self) -> Option<T>;
+
+    /// Returns a reference to an element at the given
+    /// position or None if out of bounds.
+    fn get(&self, index: usize) -> Option<Self::Ref<'_>>;
+
+    /// Returns a mutable reference to an element at the
+    /// given position or None if out of bounds.
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+
+    /// Returns the number of elements in the vector.
+    fn len(&self) -> usize;
+
+    /// Returns true if the vector has a length of 0.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Moves all the elements of `that` into `Self`, leaving `that` empty.
+    fn append(&mut self, that: &mut Self);
+
+    /// Splits the vector into two at the given index, returning
+    /// a vector with the elements in the range [mid, len).
+    fn split_off(&mut self, mid: usize) -> Self;
+
+    /// Returns an iterator over references to the elements.
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+macro_rules! impl_vector {
+    ($vec:ident, $ref_iter:ty) => {
+        impl<T: Clone + Debug> Vector<T> for $vec<T> {
+            type Ref<'a>
+                = &'a T
+            where
+                T: 'a;
+            type Iter<'a>
+                = $ref_iter
+            where
+                T: 'a;
+
+            #[inline(always)]
+            fn new() -> Self {
+                $vec::new()
+            }
+
+            #[inline(always)]
+            fn push(&mut self, item: T) {
+                $vec::push(self, item)
+            }
+
+            #[inline(always)]
+            fn pop(&mut self) -> Option<T> {
+                $vec::pop(self)
+            }
+
+            #[inline(always)]
+            fn get(&self, index: usize) -> Option<&T> {
+                $vec::get(self, index)
+            }
+
+            #[inline(always)]
+            fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+                $vec::get_mut(self, index)
+            }
+
+            #[inline(always)]
+            fn len(&self) -> usize {
+                $vec::len(self)
+            }
+
+            #[inline(always)]
+            fn append(&mut self, that: &mut Self) {
+                $vec::append(self, that)
+            }
+
+            #[inline(always)]
+            fn split_off(&mut self, mid: usize) -> Self {
+                $vec::split_off(self, mid)
+            }
+
+            #[inline(always)]
+            fn iter(&self) -> Self::Iter<'_> {
+                $vec::iter(self)
+            }
+        }
+    };
+}
+
+impl_vector!(PVec, crate::iter::PVecRefIter<'a, T>);
+impl_vector!(RrbVec, crate::core::iter::RrbVecRefIter<'a, T>);
+impl_vector!(RbVec, crate::core::iter::RbVecRefIter<'a, T>);
+
+impl<T: Clone> Vector<T> for Vec<T> {
+    type Ref<'a>
+        = &'a T
+    where
+        T: 'a;
+    type Iter<'a>
+        = slice::Iter<'a, T>
+    where
+        T: 'a;
+
+    #[inline(always)]
+    fn new() -> Self {
+        Vec::new()
+    }
+
+    #[inline(always)]
+    fn push(&mut self, item: T) {
+        Vec::push(self, item)
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+
+    #[inline(always)]
+    fn get(&self, index: usize) -> Option<&T> {
+        <[T]>::get(self, index)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        <[T]>::get_mut(self, index)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    #[inline(always)]
+    fn append(&mut self, that: &mut Self) {
+        Vec::append(self, that)
+    }
+
+    #[inline(always)]
+    fn split_off(&mut self, mid: usize) -> Self {
+        Vec::split_off(self, mid)
+    }
+    #[inline(always)]
+    fn iter(&self) -> Self::Iter<'_> {
+        <[T]>::iter(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Clone + Debug + Element, S: NodeStore + Default> Vector<T> for DiskRrbVec<T, S> {
+    type Ref<'a>
+        = DiskRef<T>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = disk::Iter<'a, T, S>
+    where
+        Self: 'a;
+
+    #[inline(always)]
+    fn new() -> Self {
+        DiskRrbVec::new()
+    }
+
+    #[inline(always)]
+    fn push(&mut self, item: T) {
+        DiskRrbVec::push(self, item)
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Option<T> {
+        DiskRrbVec::pop(self)
+    }
+
+    #[inline(always)]
+    fn get(&self, index: usize) -> Option<DiskRef<T>> {
+        DiskRrbVec::get(self, index)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        DiskRrbVec::get_mut(self, index)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        DiskRrbVec::len(self)
+    }
+
+    #[inline(always)]
+    fn append(&mut self, that: &mut Self) {
+        DiskRrbVec::append(self, that)
+    }
+
+    #[inline(always)]
+    fn split_off(&mut self, mid: usize) -> Self {
+        DiskRrbVec::split_off(self, mid)
+    }
+
+    #[inline(always)]
+    fn iter(&self) -> Self::Iter<'_> {
+        DiskRrbVec::iter(self)
+    }
+}
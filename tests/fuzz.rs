@@ -0,0 +1,71 @@
+//! Feeds the fuzz harness with random bytes. By default, only a few iterations
+//! are run, which can be raised for a long-running session, for example:
+//!
+//! ```sh
+//! PVEC_FUZZ_ITERATIONS=1000000 cargo test --release --features arbitrary --test fuzz -- --nocapture
+//! ```
+//!
+//! The seed of every iteration is derived from the seed printed at the start,
+//! and the seed of a failing iteration is printed as well. A failure can be
+//! reproduced by passing it through `PVEC_FUZZ_SEED` with a single iteration.
+
+use pvec::core::{RbVec, RrbVec};
+use pvec::fuzz::replay;
+use pvec::PVec;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use std::env;
+use std::panic;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_ITERATIONS: u64 = 200;
+const MAX_INPUT_LEN: usize = 4096;
+
+fn env_u64(name: &str) -> Option<u64> {
+    env::var(name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a number, got {}", name, value))
+    })
+}
+
+fn fuzz(name: &str, target: fn(&[u8])) {
+    let iterations = env_u64("PVEC_FUZZ_ITERATIONS").unwrap_or(DEFAULT_ITERATIONS);
+    let seed = env_u64("PVEC_FUZZ_SEED").unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        now.as_secs() ^ u64::from(now.subsec_nanos())
+    });
+
+    println!(
+        "{}: {} iterations from PVEC_FUZZ_SEED={}",
+        name, iterations, seed
+    );
+
+    for i in 0..iterations {
+        let iteration_seed = seed.wrapping_add(i);
+        let mut rng = XorShiftRng::seed_from_u64(iteration_seed);
+
+        let mut data = vec![0; rng.gen_range(0, MAX_INPUT_LEN)];
+        rng.fill_bytes(&mut data);
+
+        if let Err(err) = panic::catch_unwind(|| target(&data)) {
+            println!("{}: failed with PVEC_FUZZ_SEED={}", name, iteration_seed);
+            panic::resume_unwind(err);
+        }
+    }
+}
+
+#[test]
+fn fuzz_rbvec() {
+    fuzz("rbvec", replay::<RbVec<u32>>);
+}
+
+#[test]
+fn fuzz_rrbvec() {
+    fuzz("rrbvec", replay::<RrbVec<u32>>);
+}
+
+#[test]
+fn fuzz_pvec() {
+    fuzz("pvec", replay::<PVec<u32>>);
+}
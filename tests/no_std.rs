@@ -0,0 +1,117 @@
+//! Exercises the vectors with only `core` and `alloc` in scope,
+//! which is how they are used when the `std` feature is disabled.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use pvec::core::{RbVec, RrbVec};
+use pvec::{PVec, Vector};
+
+#[cfg(not(feature = "small_branch"))]
+const BRANCH_FACTOR: usize = 32;
+
+#[cfg(feature = "small_branch")]
+const BRANCH_FACTOR: usize = 4;
+
+macro_rules! make_no_std_tests {
+    ($vec:ident, $module:ident) => {
+        mod $module {
+            use super::*;
+
+            #[test]
+            fn push_get_and_pop() {
+                let n = BRANCH_FACTOR * BRANCH_FACTOR * 3 + 5;
+                let mut vec = $vec::new();
+
+                for i in 0..n {
+                    vec.push(i);
+                }
+
+                assert_eq!(vec.len(), n);
+
+                for i in 0..n {
+                    assert_eq!(vec.get(i), Some(&i));
+                }
+
+                for i in (0..n).rev() {
+                    assert_eq!(vec.pop(), Some(i));
+                }
+
+                assert!(vec.is_empty());
+            }
+
+            #[test]
+            fn clone_and_update() {
+                let n = BRANCH_FACTOR * BRANCH_FACTOR + 1;
+                let vec: $vec<usize> = (0..n).collect();
+                let mut vec_clone = vec.clone();
+
+                for i in 0..n {
+                    *vec_clone.get_mut(i).unwrap() += 1;
+                }
+
+                for i in 0..n {
+                    assert_eq!(vec.get(i), Some(&i));
+                    assert_eq!(vec_clone.get(i), Some(&(i + 1)));
+                }
+            }
+
+            #[test]
+            fn append_and_split_off() {
+                let n = BRANCH_FACTOR * 7 + 3;
+                let mut vec: $vec<usize> = (0..n).collect();
+                let mut that: $vec<usize> = (n..n * 2).collect();
+
+                vec.append(&mut that);
+
+                assert_eq!(vec.len(), n * 2);
+                assert!(that.is_empty());
+
+                let tail = vec.split_off(n + 1);
+
+                assert_eq!(
+                    vec.into_iter().collect::<Vec<_>>(),
+                    (0..n + 1).collect::<Vec<_>>()
+                );
+                assert_eq!(
+                    tail.into_iter().collect::<Vec<_>>(),
+                    (n + 1..n * 2).collect::<Vec<_>>()
+                );
+            }
+
+            #[test]
+            fn iterates_in_both_directions() {
+                let n = BRANCH_FACTOR * BRANCH_FACTOR + 7;
+                let vec: $vec<usize> = (0..n).collect();
+
+                assert!(vec.clone().into_iter().eq(0..n));
+                assert!(vec.into_iter().rev().eq((0..n).rev()));
+            }
+
+            #[test]
+            fn works_through_vector_trait() {
+                fn fill<V: Vector<usize>>(n: usize) -> V {
+                    let mut vec = V::new();
+
+                    for i in 0..n {
+                        vec.push(i);
+                    }
+
+                    vec
+                }
+
+                let n = BRANCH_FACTOR * 5 + 2;
+                let vec: $vec<usize> = fill(n);
+
+                assert_eq!(Vector::len(&vec), n);
+                assert_eq!(Vector::get(&vec, n - 1), Some(&(n - 1)));
+            }
+        }
+    };
+}
+
+make_no_std_tests!(RbVec, test_no_std_rbvec);
+make_no_std_tests!(RrbVec, test_no_std_rrbvec);
+make_no_std_tests!(PVec, test_no_std_pvec);
@@ -1,5 +1,7 @@
+use pvec::core::disk::{DiskRrbVec, FileStore, MemoryStore, NodeStore, RecordId};
 use pvec::core::{RbVec, RrbVec};
 use pvec::PVec;
+use std::io;
 
 #[cfg(not(feature = "small_branch"))]
 const BRANCH_FACTOR: usize = 32;
@@ -7,449 +9,3178 @@ const BRANCH_FACTOR: usize = 32;
 #[cfg(feature = "small_branch")]
 const BRANCH_FACTOR: usize = 4;
 
-macro_rules! make_tests {
-    ($vec:ident, $module:ident) => {
+/// Tests written against the [Vector](pvec::Vector) trait, and
+/// run by `make_tests` for every type implementing it, with the
+/// standard vector serving as a reference.
+mod suite {
+    use super::BRANCH_FACTOR;
+    use pvec::Vector;
+    use std::panic::{self, AssertUnwindSafe};
 
-        mod $module {
-            use super::$vec;
-            use super::BRANCH_FACTOR;
+    fn collect<V: Vector<usize>>(vec: &V) -> Vec<usize> {
+        (0..vec.len()).map(|i| *vec.get(i).unwrap()).collect()
+    }
 
-            #[test]
-            fn len_matches_actual_size() {
-                const N: usize = 5000;
+    fn make<V: Vector<usize>>(range: std::ops::Range<usize>) -> V {
+        let mut vec = V::new();
 
-                let mut vec = $vec::new();
+        for i in range {
+            vec.push(i);
+        }
 
-                for i in 0..N {
-                    vec.push(i);
-                }
+        vec
+    }
 
-                assert_eq!(vec.len(), N);
+    pub fn len_matches_actual_size<V: Vector<usize>>() {
+        const N: usize = 5000;
 
-                for i in 0..N {
-                    assert_eq!(*vec.get(i).unwrap(), i);
-                }
-            }
+        let mut vec = V::new();
 
-            #[test]
-            fn len_matches_len_cloned() {
-                const N: usize = 5000;
+        for i in 0..N {
+            vec.push(i);
+        }
 
-                let mut vec = $vec::new();
+        assert_eq!(vec.len(), N);
 
-                for i in 0..N {
-                    vec.push(i);
-                }
+        for i in 0..N {
+            assert_eq!(*vec.get(i).unwrap(), i);
+        }
+    }
 
-                let vec_0 = vec.clone();
-                assert_eq!(vec.len(), N);
-                assert_eq!(vec_0.len(), N);
+    pub fn len_matches_len_cloned<V: Vector<usize>>() {
+        const N: usize = 5000;
 
-                for i in 0..N {
-                    vec.push(i);
-                }
+        let mut vec = V::new();
 
-                assert_eq!(vec.len(), 2 * N);
-                assert_eq!(vec_0.len(), N);
+        for i in 0..N {
+            vec.push(i);
+        }
 
-                for i in 0..N {
-                    assert_eq!(*vec.get(i).unwrap(), i);
-                    assert_eq!(*vec_0.get(i).unwrap(), i);
-                }
+        let vec_0 = vec.clone();
+        assert_eq!(vec.len(), N);
+        assert_eq!(vec_0.len(), N);
 
-                for i in 0..N {
-                    assert_eq!(*vec.get(i + N).unwrap(), i);
-                }
-            }
+        for i in 0..N {
+            vec.push(i);
+        }
 
-            #[test]
-            fn mutate_in_place_must_not_mutate_cloned_vec() {
-                const N: usize = 32 * 4;
+        assert_eq!(vec.len(), 2 * N);
+        assert_eq!(vec_0.len(), N);
 
-                let mut vec = $vec::new();
+        for i in 0..N {
+            assert_eq!(*vec.get(i).unwrap(), i);
+            assert_eq!(*vec_0.get(i).unwrap(), i);
+        }
 
-                for i in 0..N {
-                    vec.push(i);
-                }
+        for i in 0..N {
+            assert_eq!(*vec.get(i + N).unwrap(), i);
+        }
+    }
 
-                let vec_0 = vec.clone();
-                assert_eq!(vec.len(), N);
-                assert_eq!(vec_0.len(), N);
+    pub fn push_and_get<V: Vector<usize>>() {
+        const N: usize = 5000;
 
-                for i in 0..(N / 2) {
-                    *vec.get_mut(i).unwrap() += 1;
-                }
+        let mut vec = V::new();
+        assert!(vec.is_empty());
 
-                assert_eq!(vec.len(), N);
-                assert_eq!(vec_0.len(), N);
+        for i in 0..N {
+            vec.push(i);
+            assert_eq!(vec.len(), i + 1);
+        }
 
-                for i in 0..(N / 2) {
-                    assert_eq!(*vec.get(i).unwrap(), i + 1);
-                    assert_eq!(*vec_0.get(i).unwrap(), i);
-                }
+        for i in 0..N {
+            assert_eq!(vec.get(i).as_deref(), Some(&i));
+        }
 
-                // the second half ought to be untouched
-                for i in N / 2..N {
-                    assert_eq!(*vec.get(i).unwrap(), i);
-                    assert_eq!(*vec_0.get(i).unwrap(), i);
-                    assert_eq!(
-                        vec.get(i).unwrap() as *const usize,
-                        vec_0.get(i).unwrap() as *const usize
-                    );
-                }
-            }
+        assert!(vec.get(N).is_none());
+        assert!(vec.get(N + BRANCH_FACTOR * 2).is_none());
+        assert_eq!(vec.get_mut(N + BRANCH_FACTOR * 2), None);
+    }
 
-            #[test]
-            fn pop_must_not_mutate_cloned_vec() {
-                const N: usize = 32 * 4;
+    pub fn mutate_in_place_must_not_mutate_cloned_vec<V: Vector<usize>>() {
+        const N: usize = 32 * 4;
 
-                let mut vec = $vec::new();
+        let mut vec = V::new();
 
-                for i in 0..N {
-                    vec.push(i);
-                }
+        for i in 0..N {
+            vec.push(i);
+        }
 
-                let vec_0 = vec.clone();
-                assert_eq!(vec.len(), N);
-                assert_eq!(vec_0.len(), N);
+        let vec_0 = vec.clone();
+        assert_eq!(vec.len(), N);
+        assert_eq!(vec_0.len(), N);
 
-                for _ in 0..(N / 2) {
-                    vec.pop();
-                }
+        for i in 0..(N / 2) {
+            *vec.get_mut(i).unwrap() += 1;
+        }
 
-                assert_eq!(vec.len(), N / 2);
-                assert_eq!(vec_0.len(), N);
+        assert_eq!(vec.len(), N);
+        assert_eq!(vec_0.len(), N);
 
-                for i in 0..(N / 2) {
-                    assert_eq!(*vec.get(i).unwrap(), i);
-                    assert_eq!(*vec_0.get(i).unwrap(), i);
-                }
+        for i in 0..(N / 2) {
+            assert_eq!(*vec.get(i).unwrap(), i + 1);
+            assert_eq!(*vec_0.get(i).unwrap(), i);
+        }
 
-                for i in N / 2..N {
-                    assert_eq!(*vec_0.get(i).unwrap(), i);
-                }
-            }
+        // the second half ought to be untouched
+        for i in N / 2..N {
+            assert_eq!(*vec.get(i).unwrap(), i);
+            assert_eq!(*vec_0.get(i).unwrap(), i);
+        }
+    }
 
-            #[test]
-            fn push_pop_must_return_expected_values() {
-                const N: usize = 32 * 4;
+    pub fn get_mut_does_not_mutate_clone<V: Vector<usize>>() {
+        const N: usize = 1000;
 
-                let mut vec = $vec::new();
+        let mut vec: V = make(0..N);
+        let vec_0 = vec.clone();
 
-                for i in 0..N {
-                    vec.push(i)
-                }
+        for i in 0..N {
+            *vec.get_mut(i).unwrap() += 1;
+        }
 
-                assert_eq!(vec.len(), N);
+        assert_eq!(collect(&vec), (1..N + 1).collect::<Vec<_>>());
+        assert_eq!(collect(&vec_0), (0..N).collect::<Vec<_>>());
+    }
 
-                for i in (0..N).rev() {
-                    assert_eq!(vec.pop().unwrap(), i);
-                }
+    pub fn pop_must_not_mutate_cloned_vec<V: Vector<usize>>() {
+        const N: usize = 32 * 4;
 
-                for i in 0..N {
-                    vec.push(i)
-                }
+        let mut vec = V::new();
 
-                assert_eq!(vec.len(), N);
+        for i in 0..N {
+            vec.push(i);
+        }
 
-                for i in (0..N).rev() {
-                    assert_eq!(vec.pop().unwrap(), i);
-                }
+        let vec_0 = vec.clone();
+        assert_eq!(vec.len(), N);
+        assert_eq!(vec_0.len(), N);
 
-                assert_eq!(vec.len(), 0);
-            }
+        for _ in 0..(N / 2) {
+            vec.pop();
+        }
 
-            #[test]
-            fn append_must_maintain_vectors_in_correct_state_after_clone() {
-                let mut vec_l = $vec::new();
-                let mut vec_c = $vec::new();
-                let mut vec_r = $vec::new();
+        assert_eq!(vec.len(), N / 2);
+        assert_eq!(vec_0.len(), N);
 
-                let mut branch_value = 0;
+        for i in 0..(N / 2) {
+            assert_eq!(*vec.get(i).unwrap(), i);
+            assert_eq!(*vec_0.get(i).unwrap(), i);
+        }
 
-                for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR {
-                    vec_l.push(branch_value);
-                    branch_value += 1;
-                }
+        for i in N / 2..N {
+            assert_eq!(*vec_0.get(i).unwrap(), i);
+        }
+    }
 
-                for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR {
-                    vec_c.push(branch_value);
-                    branch_value += 1;
-                }
+    pub fn pop_until_empty<V: Vector<usize>>() {
+        const N: usize = 5000;
 
-                for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR {
-                    vec_r.push(branch_value);
-                    branch_value += 1;
-                }
+        let mut vec: V = make(0..N);
 
-                let vec_l_clone = vec_l.clone();
-                let vec_c_clone = vec_c.clone();
-                let vec_r_clone = vec_r.clone();
+        for i in (0..N).rev() {
+            assert_eq!(vec.pop(), Some(i));
+            assert_eq!(vec.len(), i);
+        }
 
-                vec_l.append(&mut vec_c);
-                vec_l.append(&mut vec_r);
+        assert_eq!(vec.pop(), None);
+        assert!(vec.is_empty());
+    }
 
-                assert_eq!(
-                    vec_l.len(),
-                    vec_l_clone.len() + vec_c_clone.len() + vec_r_clone.len()
-                );
+    pub fn push_pop_must_return_expected_values<V: Vector<usize>>() {
+        const N: usize = 32 * 4;
 
-                let mut branch_test_value = 0;
+        let mut vec = V::new();
 
-                for i in 0..vec_l_clone.len() {
-                    assert_eq!(*vec_l_clone.get(i).unwrap(), branch_test_value);
-                    branch_test_value += 1;
-                }
+        for i in 0..N {
+            vec.push(i)
+        }
 
-                for i in 0..vec_c_clone.len() {
-                    assert_eq!(*vec_c_clone.get(i).unwrap(), branch_test_value);
-                    branch_test_value += 1;
-                }
+        assert_eq!(vec.len(), N);
 
-                for i in 0..vec_r_clone.len() {
-                    assert_eq!(*vec_r_clone.get(i).unwrap(), branch_test_value);
-                    branch_test_value += 1;
-                }
-            }
+        for i in (0..N).rev() {
+            assert_eq!(vec.pop().unwrap(), i);
+        }
 
-            fn interleaving_different_operations_must_maintain_correct_internal_state(vec_size: usize) {
-                let mut vec = $vec::new();
-                let mut vec_item = 0;
+        for i in 0..N {
+            vec.push(i)
+        }
 
-                for i in 0..128 {
-                    if i % 2 == 0 {
-                        let mut vec_temp = $vec::new();
+        assert_eq!(vec.len(), N);
 
-                        for _ in 0..vec_size {
-                            vec_temp.push(vec_item);
-                            vec_item += 1;
-                        }
+        for i in (0..N).rev() {
+            assert_eq!(vec.pop().unwrap(), i);
+        }
 
-                        assert_eq!(vec_temp.len(), vec_size);
+        assert_eq!(vec.len(), 0);
+    }
 
-                        vec.append(&mut vec_temp);
+    pub fn append_must_maintain_vectors_in_correct_state_after_clone<V: Vector<usize>>() {
+        let mut vec_l = V::new();
+        let mut vec_c = V::new();
+        let mut vec_r = V::new();
 
-                        assert_eq!(vec_temp.len(), 0);
-                    } else {
-                        for _ in 0..(vec_size + vec_size) {
-                            vec.push(vec_item);
-                            vec_item += 1;
-                        }
-                    }
+        let mut branch_value = 0;
 
-                    assert_eq!(vec.len(), vec_item);
+        for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR {
+            vec_l.push(branch_value);
+            branch_value += 1;
+        }
 
-                    for i in 0..vec.len() {
-                        assert_eq!(*vec.get(i).unwrap(), i);
-                        assert_eq!(*vec.get_mut(i).unwrap(), i);
-                    }
+        for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR {
+            vec_c.push(branch_value);
+            branch_value += 1;
+        }
 
-                    let mut vec_one_clone = vec.clone();
-                    for i in (0..vec_item).rev() {
-                        assert_eq!(vec_one_clone.pop().unwrap(), i);
-                    }
+        for _ in 0..BRANCH_FACTOR * BRANCH_FACTOR {
+            vec_r.push(branch_value);
+            branch_value += 1;
+        }
 
-                    assert_eq!(vec_one_clone.len(), 0);
-                }
+        let vec_l_clone = vec_l.clone();
+        let vec_c_clone = vec_c.clone();
+        let vec_r_clone = vec_r.clone();
 
-                assert_eq!(vec.len(), vec_item);
+        vec_l.append(&mut vec_c);
+        vec_l.append(&mut vec_r);
 
-                let mut vec_clone = vec.clone();
-                for i in (0..vec_item).rev() {
-                    assert_eq!(vec_clone.pop().unwrap(), i);
+        assert_eq!(
+            vec_l.len(),
+            vec_l_clone.len() + vec_c_clone.len() + vec_r_clone.len()
+        );
 
-                    for j in 0..vec_clone.len() {
-                        assert_eq!(*vec_clone.get(j).unwrap(), j);
-                        assert_eq!(*vec_clone.get_mut(j).unwrap(), j);
-                    }
+        let mut branch_test_value = 0;
+
+        for i in 0..vec_l_clone.len() {
+            assert_eq!(*vec_l_clone.get(i).unwrap(), branch_test_value);
+            branch_test_value += 1;
+        }
+
+        for i in 0..vec_c_clone.len() {
+            assert_eq!(*vec_c_clone.get(i).unwrap(), branch_test_value);
+            branch_test_value += 1;
+        }
+
+        for i in 0..vec_r_clone.len() {
+            assert_eq!(*vec_r_clone.get(i).unwrap(), branch_test_value);
+            branch_test_value += 1;
+        }
+    }
+
+    pub fn append_and_split_off<V: Vector<usize>>() {
+        let sizes = [0, 1, BRANCH_FACTOR, BRANCH_FACTOR * BRANCH_FACTOR + 3, 2000];
+
+        for &left in sizes.iter() {
+            for &right in sizes.iter() {
+                let mut vec: V = make(0..left);
+                let mut that: V = make(left..left + right);
+                let mut expected: Vec<usize> = make(0..left);
+                let mut expected_that: Vec<usize> = make(left..left + right);
+
+                vec.append(&mut that);
+                expected.append(&mut expected_that);
+
+                assert!(that.is_empty());
+                assert_eq!(collect(&vec), expected);
+
+                for &mid in [0, left / 2, left, left + right].iter() {
+                    let mut vec_0 = vec.clone();
+                    let mut expected_0 = expected.clone();
+
+                    let split = vec_0.split_off(mid);
+                    let expected_split = expected_0.split_off(mid);
+
+                    assert_eq!(collect(&vec_0), expected_0);
+                    assert_eq!(collect(&split), expected_split);
                 }
             }
+        }
+    }
 
-            #[test]
-            fn interleaving_push_split_off_push_operations() {
-                let mut vec_one = $vec::new();
+    pub fn interleaving_different_operations_must_maintain_correct_internal_state<
+        V: Vector<usize>,
+    >(
+        vec_size: usize,
+    ) {
+        let mut vec = V::new();
+        let mut vec_item = 0;
 
-                for i in 0..(BRANCH_FACTOR * BRANCH_FACTOR) {
-                    vec_one.push(i);
+        for i in 0..128 {
+            if i % 2 == 0 {
+                let mut vec_temp = V::new();
+
+                for _ in 0..vec_size {
+                    vec_temp.push(vec_item);
+                    vec_item += 1;
                 }
 
-                let mut vec_two = vec_one.split_off(BRANCH_FACTOR * BRANCH_FACTOR - BRANCH_FACTOR);
-                vec_two.push(0xbeef);
+                assert_eq!(vec_temp.len(), vec_size);
+
+                vec.append(&mut vec_temp);
 
-                assert_eq!(vec_one.len(), BRANCH_FACTOR * BRANCH_FACTOR - BRANCH_FACTOR);
-                assert_eq!(vec_two.len(), BRANCH_FACTOR + 1);
+                assert_eq!(vec_temp.len(), 0);
+            } else {
+                for _ in 0..(vec_size + vec_size) {
+                    vec.push(vec_item);
+                    vec_item += 1;
+                }
             }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_4() {
-                interleaving_different_operations_must_maintain_correct_internal_state(4);
+            assert_eq!(vec.len(), vec_item);
+
+            for i in 0..vec.len() {
+                assert_eq!(*vec.get(i).unwrap(), i);
+                assert_eq!(*vec.get_mut(i).unwrap(), i);
             }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_5() {
-                interleaving_different_operations_must_maintain_correct_internal_state(5);
+            let mut vec_one_clone = vec.clone();
+            for i in (0..vec_item).rev() {
+                assert_eq!(vec_one_clone.pop().unwrap(), i);
             }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_16() {
-                interleaving_different_operations_must_maintain_correct_internal_state(16);
+            assert_eq!(vec_one_clone.len(), 0);
+        }
+
+        assert_eq!(vec.len(), vec_item);
+
+        let mut vec_clone = vec.clone();
+        for i in (0..vec_item).rev() {
+            assert_eq!(vec_clone.pop().unwrap(), i);
+
+            for j in 0..vec_clone.len() {
+                assert_eq!(*vec_clone.get(j).unwrap(), j);
+                assert_eq!(*vec_clone.get_mut(j).unwrap(), j);
             }
+        }
+    }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_17() {
-                interleaving_different_operations_must_maintain_correct_internal_state(17);
+    pub fn interleaving_push_split_off_push_operations<V: Vector<usize>>() {
+        let mut vec_one = V::new();
+
+        for i in 0..(BRANCH_FACTOR * BRANCH_FACTOR) {
+            vec_one.push(i);
+        }
+
+        let mut vec_two = vec_one.split_off(BRANCH_FACTOR * BRANCH_FACTOR - BRANCH_FACTOR);
+        vec_two.push(0xbeef);
+
+        assert_eq!(vec_one.len(), BRANCH_FACTOR * BRANCH_FACTOR - BRANCH_FACTOR);
+        assert_eq!(vec_two.len(), BRANCH_FACTOR + 1);
+    }
+
+    pub fn interleaving_push_and_append_operations_must_maintain_correct_internal_state<
+        V: Vector<usize>,
+    >() {
+        let mut vec_one = V::new();
+
+        for i in 0..32 {
+            vec_one.push(i);
+        }
+
+        let mut vec_two = V::new();
+
+        for i in 0..1024 {
+            if i % 2 == 0 {
+                vec_two.push(i);
+            } else {
+                vec_two.append(&mut vec_one.clone());
             }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_32() {
-                interleaving_different_operations_must_maintain_correct_internal_state(32);
+            for k in 0..vec_two.len() {
+                vec_two.get(k).unwrap();
+            }
+        }
+    }
+
+    pub fn zero_sized_values<V: Vector<()>>() {
+        let mut v = V::new();
+        assert_eq!(v.len(), 0);
+
+        v.push(());
+        assert_eq!(v.len(), 1);
+
+        v.push(());
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.pop(), Some(()));
+        assert_eq!(v.pop(), Some(()));
+        assert_eq!(v.pop(), None);
+
+        assert_eq!(v.len(), 0);
+
+        v.push(());
+        assert_eq!(v.len(), 1);
+
+        v.push(());
+        assert_eq!(v.len(), 2);
+
+        for i in 0..v.len() {
+            v.get(i);
+        }
+        assert_eq!(v.len(), 2);
+
+        v.push(());
+        assert_eq!(v.len(), 3);
+
+        v.push(());
+        assert_eq!(v.len(), 4);
+
+        for i in 0..v.len() {
+            v.get_mut(i);
+        }
+        assert_eq!(v.len(), 4);
+    }
+
+    pub fn interleaving_append_split_off_operations<V: Vector<usize>>() {
+        let mut vec = V::new();
+        let mut value = 0;
+
+        for size in 1..(BRANCH_FACTOR * 8 + BRANCH_FACTOR) {
+            let mut another_vec = V::new();
+            for _ in 0..size {
+                another_vec.push(value);
+                value += 1;
             }
 
-            #[test]
-            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_33() {
-                interleaving_different_operations_must_maintain_correct_internal_state(33);
+            vec.append(&mut another_vec);
+
+            let mid = vec.len() / 2;
+            let mut right = vec.split_off(mid);
+
+            vec.append(&mut right);
+            value = vec.len();
+        }
+
+        for i in 0..value {
+            assert_eq!(vec.get(i).as_deref().cloned(), Some(i));
+        }
+    }
+
+    pub fn splice_and_pop_must_keep_elements<V: Vector<usize>>() {
+        let mut vec = V::new();
+        let mut expected = Vec::new();
+
+        for i in 0..BRANCH_FACTOR * BRANCH_FACTOR * 2 {
+            vec.push(i);
+            expected.push(i);
+        }
+
+        for i in 0..BRANCH_FACTOR * 2 {
+            let index = (i * 37) % expected.len();
+
+            let mut right = vec.split_off(index);
+            let mut expected_right = expected.split_off(index);
+
+            for j in 0..BRANCH_FACTOR / 2 + i {
+                vec.push(j);
+                expected.push(j);
+            }
+
+            vec.append(&mut right);
+            expected.append(&mut expected_right);
+
+            let end = std::cmp::min(index + i + 1, expected.len());
+
+            let mut right = vec.split_off(end);
+            let mut expected_right = expected.split_off(end);
+
+            vec.split_off(index);
+            expected.truncate(index);
+
+            vec.append(&mut right);
+            expected.append(&mut expected_right);
+
+            assert_eq!(vec.len(), expected.len());
+            for (j, item) in expected.iter().enumerate() {
+                assert_eq!(vec.get(j).as_deref(), Some(item));
+            }
+        }
+
+        while let Some(item) = expected.pop() {
+            assert_eq!(vec.pop(), Some(item));
+
+            if expected.len() % (BRANCH_FACTOR * 3 + 1) == 0 {
+                vec.push(item);
+                assert_eq!(vec.pop(), Some(item));
+            }
+        }
+
+        assert!(vec.is_empty());
+    }
+
+    pub fn split_off_by_one<V: Vector<usize>>() {
+        let mut vec = V::new();
+
+        for i in 0..(BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + (BRANCH_FACTOR / 2)) {
+            vec.push(i);
+        }
+
+        for i in (0..BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + (BRANCH_FACTOR / 2)).rev() {
+            let mut other = vec.split_off(i);
+            assert_eq!(other.pop(), Some(i));
+        }
+
+        assert!(vec.is_empty());
+    }
+
+    pub fn split_off_out_of_bounds_must_leave_vec_intact<V: Vector<usize>>() {
+        for &len in [0, 3, BRANCH_FACTOR * BRANCH_FACTOR + 3].iter() {
+            let mut vec: V = make(0..len);
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| vec.split_off(len + 1)));
+            assert!(result.is_err());
+
+            assert_eq!(collect(&vec), (0..len).collect::<Vec<_>>());
+        }
+    }
+
+    pub fn iterate<V: Vector<usize>>() {
+        const N: usize = 5000;
+
+        let vec: V = (0..N).collect();
+        assert_eq!(vec.len(), N);
+
+        let items = vec.clone().into_iter().collect::<Vec<_>>();
+        assert_eq!(items, (0..N).collect::<Vec<_>>());
+        assert_eq!(collect(&vec), items);
+        assert_eq!(vec.iter().map(|item| *item).collect::<Vec<_>>(), items);
+
+        let mut vec: V = make(0..BRANCH_FACTOR + 3);
+        for size in 1..BRANCH_FACTOR * 2 {
+            let start = vec.len();
+            vec.append(&mut make(start..start + size * 7));
+        }
+
+        let items = vec.iter().map(|item| *item).collect::<Vec<_>>();
+        assert_eq!(items, (0..vec.len()).collect::<Vec<_>>());
+        assert_eq!(V::new().iter().count(), 0);
+    }
+}
+
+macro_rules! make_tests {
+    ($vec:ident, $module:ident) => {
+        mod $module {
+            use super::*;
+
+            #[test]
+            fn len_matches_actual_size() {
+                suite::len_matches_actual_size::<$vec<usize>>();
+            }
+
+            #[test]
+            fn len_matches_len_cloned() {
+                suite::len_matches_len_cloned::<$vec<usize>>();
+            }
+
+            #[test]
+            fn push_and_get() {
+                suite::push_and_get::<$vec<usize>>();
+            }
+
+            #[test]
+            fn mutate_in_place_must_not_mutate_cloned_vec() {
+                suite::mutate_in_place_must_not_mutate_cloned_vec::<$vec<usize>>();
+            }
+
+            #[test]
+            fn get_mut_does_not_mutate_clone() {
+                suite::get_mut_does_not_mutate_clone::<$vec<usize>>();
+            }
+
+            #[test]
+            fn pop_must_not_mutate_cloned_vec() {
+                suite::pop_must_not_mutate_cloned_vec::<$vec<usize>>();
+            }
+
+            #[test]
+            fn pop_until_empty() {
+                suite::pop_until_empty::<$vec<usize>>();
+            }
+
+            #[test]
+            fn push_pop_must_return_expected_values() {
+                suite::push_pop_must_return_expected_values::<$vec<usize>>();
+            }
+
+            #[test]
+            fn append_must_maintain_vectors_in_correct_state_after_clone() {
+                suite::append_must_maintain_vectors_in_correct_state_after_clone::<$vec<usize>>();
+            }
+
+            #[test]
+            fn append_and_split_off() {
+                suite::append_and_split_off::<$vec<usize>>();
+            }
+
+            #[test]
+            fn interleaving_push_split_off_push_operations() {
+                suite::interleaving_push_split_off_push_operations::<$vec<usize>>();
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_4() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(4);
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_5() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(5);
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_16() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(16);
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_17() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(17);
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_32() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(32);
+            }
+
+            #[test]
+            fn interleaving_different_operations_must_maintain_correct_internal_state_for_var_sizes_33() {
+                suite::interleaving_different_operations_must_maintain_correct_internal_state::<$vec<usize>>(33);
+            }
+
+            #[test]
+            fn interleaving_push_and_append_operations_must_maintain_correct_internal_state_for_var_sizes_32() {
+                suite::interleaving_push_and_append_operations_must_maintain_correct_internal_state::<$vec<usize>>();
+            }
+
+            #[test]
+            fn zero_sized_values() {
+                suite::zero_sized_values::<$vec<()>>();
+            }
+
+            #[test]
+            fn interleaving_append_split_off_operations() {
+                suite::interleaving_append_split_off_operations::<$vec<usize>>();
+            }
+
+            #[test]
+            fn splice_and_pop_must_keep_elements() {
+                suite::splice_and_pop_must_keep_elements::<$vec<usize>>();
+            }
+
+            #[test]
+            fn split_off_by_one() {
+                suite::split_off_by_one::<$vec<usize>>();
+            }
+
+            #[test]
+            fn split_off_out_of_bounds_must_leave_vec_intact() {
+                suite::split_off_out_of_bounds_must_leave_vec_intact::<$vec<usize>>();
+            }
+
+            #[test]
+            fn iterate() {
+                suite::iterate::<$vec<usize>>();
+            }
+        }
+    };
+}
+
+macro_rules! make_tree_tests {
+    ($vec:ident, $module:ident) => {
+        mod $module {
+            use super::$vec;
+            use super::BRANCH_FACTOR;
+
+            #[test]
+            fn mutate_in_place_must_share_untouched_elements() {
+                const N: usize = 32 * 4;
+
+                let mut vec = $vec::new();
+
+                for i in 0..N {
+                    vec.push(i);
+                }
+
+                let vec_0 = vec.clone();
+
+                for i in 0..(N / 2) {
+                    *vec.get_mut(i).unwrap() += 1;
+                }
+
+                for i in N / 2..N {
+                    assert_eq!(
+                        &*vec.get(i).unwrap() as *const usize,
+                        &*vec_0.get(i).unwrap() as *const usize
+                    );
+                }
+            }
+
+            #[test]
+            fn concat_must_preserve_order_of_elements() {
+                let mut vecs = Vec::new();
+                let mut value = 0;
+
+                for size in 0..(BRANCH_FACTOR * 4) {
+                    let mut vec = $vec::new();
+                    for _ in 0..size {
+                        vec.push(value);
+                        value += 1;
+                    }
+
+                    vecs.push(vec);
+                }
+
+                let vecs_clone = vecs.clone();
+                let vec = $vec::concat(vecs);
+
+                assert_eq!(vec.len(), value);
+                for i in 0..value {
+                    assert_eq!(vec.get(i).as_deref().cloned(), Some(i));
+                }
+
+                let vec_sum: $vec<usize> = vecs_clone.iter().sum();
+                assert_eq!(vec_sum.len(), value);
+
+                let vec_sum: $vec<usize> = vecs_clone.into_iter().sum();
+                for i in 0..value {
+                    assert_eq!(vec_sum.get(i).as_deref().cloned(), Some(i));
+                }
+
+                let empty: $vec<usize> = $vec::concat(Vec::new());
+                assert!(empty.is_empty());
+            }
+
+            #[test]
+            fn split_at_many_must_return_all_pieces() {
+                const N: usize = BRANCH_FACTOR * BRANCH_FACTOR * 3 + 7;
+
+                let mut vec = $vec::new();
+                for i in 0..N {
+                    vec.push(i);
+                }
+
+                let indices = [
+                    0,
+                    1,
+                    BRANCH_FACTOR,
+                    BRANCH_FACTOR,
+                    N / 3,
+                    N / 2 + 1,
+                    N - 1,
+                    N,
+                ];
+                let vec_clone = vec.clone();
+                let pieces = vec.split_at_many(&indices);
+
+                assert_eq!(pieces.len(), indices.len() + 1);
+
+                let mut value = 0;
+                for (i, piece) in pieces.iter().enumerate() {
+                    let end = indices.get(i).cloned().unwrap_or(N);
+                    assert_eq!(piece.len(), end - value);
+
+                    for j in 0..piece.len() {
+                        assert_eq!(piece.get(j).as_deref().cloned(), Some(value));
+                        value += 1;
+                    }
+                }
+
+                assert_eq!(value, N);
+                assert_eq!(vec_clone.len(), N);
+
+                let chunks = vec_clone.into_chunks(BRANCH_FACTOR + 1);
+                assert_eq!(chunks.len(), (N + BRANCH_FACTOR) / (BRANCH_FACTOR + 1));
+
+                let mut value = 0;
+                for chunk in chunks {
+                    assert!(chunk.len() <= BRANCH_FACTOR + 1);
+
+                    for item in chunk {
+                        assert_eq!(item, value);
+                        value += 1;
+                    }
+                }
+
+                assert_eq!(value, N);
+            }
+
+            #[test]
+            fn split_at_many_must_cut_relaxed_trees() {
+                let mut vec = $vec::new();
+                let mut value = 0;
+
+                for size in 1..(BRANCH_FACTOR * 4) {
+                    let mut another_vec = $vec::new();
+                    for _ in 0..size * 3 {
+                        another_vec.push(value);
+                        value += 1;
+                    }
+
+                    vec.append(&mut another_vec);
+                }
+
+                let len = vec.len();
+                let step = len / 23 + 1;
+
+                for offset in 0..BRANCH_FACTOR + 1 {
+                    let indices: Vec<usize> = (offset..len).step_by(step).collect();
+                    let pieces = vec.clone().split_at_many(&indices);
+
+                    let mut start = 0;
+                    let mut joined = $vec::new();
+
+                    for (i, mut piece) in pieces.into_iter().enumerate() {
+                        let end = indices.get(i).cloned().unwrap_or(len);
+                        assert_eq!(piece.len(), end - start);
+
+                        for j in 0..piece.len() {
+                            assert_eq!(piece.get(j).as_deref().cloned(), Some(start + j));
+                        }
+
+                        piece.push(end);
+                        assert_eq!(piece.pop(), Some(end));
+
+                        joined.append(&mut piece);
+                        start = end;
+                    }
+
+                    assert_eq!(joined.len(), len);
+                    for i in 0..len {
+                        assert_eq!(joined.get(i).as_deref().cloned(), Some(i));
+                    }
+                }
+            }
+
+            #[test]
+            #[should_panic]
+            fn split_at_many_must_panic_on_unsorted_indices() {
+                let mut vec = $vec::new();
+                for i in 0..BRANCH_FACTOR * 2 {
+                    vec.push(i);
+                }
+
+                vec.split_at_many(&[BRANCH_FACTOR, 1]);
+            }
+
+            #[test]
+            fn rotate_must_move_elements() {
+                const N: usize = BRANCH_FACTOR * BRANCH_FACTOR + BRANCH_FACTOR / 2;
+
+                let mut vec = $vec::new();
+                for i in 0..N {
+                    vec.push(i);
+                }
+
+                for &mid in [0, 1, BRANCH_FACTOR + 3, N / 2, N].iter() {
+                    let mut left = vec.clone();
+                    left.rotate_left(mid);
+
+                    let mut right = vec.clone();
+                    right.rotate_right(N - mid);
+
+                    assert_eq!(left.len(), N);
+                    assert_eq!(right.len(), N);
+
+                    for i in 0..N {
+                        assert_eq!(left.get(i).as_deref().cloned(), Some((i + mid) % N));
+                        assert_eq!(right.get(i).as_deref().cloned(), Some((i + mid) % N));
+                    }
+                }
+            }
+        }
+    };
+}
+
+/// Runs the disk-backed vectors with a small cache budget,
+/// so that the tests go through eviction and reloading.
+#[derive(Debug, Clone, Default)]
+struct SmallCache<S>(S);
+
+impl<S: NodeStore> NodeStore for SmallCache<S> {
+    fn write(&self, record: &[u8]) -> io::Result<RecordId> {
+        self.0.write(record)
+    }
+
+    fn read(&self, id: RecordId) -> io::Result<Vec<u8>> {
+        self.0.read(id)
+    }
+
+    fn same_store(&self, other: &Self) -> bool {
+        self.0.same_store(&other.0)
+    }
+
+    fn cache_budget(&self) -> usize {
+        8 * 1024 / BRANCH_FACTOR
+    }
+}
+
+type MemoryRrbVec<T> = DiskRrbVec<T, SmallCache<MemoryStore>>;
+type FileRrbVec<T> = DiskRrbVec<T, SmallCache<FileStore>>;
+
+make_tests!(RbVec, test_rbvec);
+make_tests!(RrbVec, test_rrbvec);
+make_tests!(PVec, test_pvec_vector);
+make_tests!(Vec, test_vec);
+make_tests!(MemoryRrbVec, test_memory_rrbvec);
+make_tests!(FileRrbVec, test_file_rrbvec);
+
+make_tree_tests!(RbVec, test_rbvec_tree);
+make_tree_tests!(RrbVec, test_rrbvec_tree);
+make_tree_tests!(MemoryRrbVec, test_memory_rrbvec_tree);
+make_tree_tests!(FileRrbVec, test_file_rrbvec_tree);
+
+mod test_pvec {
+    use super::PVec;
+
+    #[test]
+    fn interleaving_append_split_off_operations() {
+        let mut vec_one = PVec::new();
+        let mut value = 0;
+
+        for size in 1..(32 * 8 + 32) {
+            let mut vec_two = PVec::new();
+            for _ in 0..size {
+                vec_two.push(value);
+                value += 1;
+            }
+
+            vec_one.append(&mut vec_two);
+
+            let mid = vec_one.len() / 2;
+            let mut right = vec_one.split_off(mid);
+
+            vec_one.append(&mut right);
+            value = vec_one.len();
+        }
+
+        for i in 0..value {
+            assert_eq!(vec_one.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    fn split_at_many_and_rotate() {
+        const N: usize = 32 * 32 + 5;
+
+        fn check_split_at_many_and_rotate(vec: PVec<usize>) {
+            let mut rotated = vec.clone();
+            rotated.rotate_left(N / 3);
+            rotated.rotate_right(N / 3);
+
+            for i in 0..N {
+                assert_eq!(rotated.get(i).cloned(), Some(i));
+            }
+
+            let pieces = vec.split_at_many(&[10, N / 2]);
+            assert_eq!(pieces.len(), 3);
+            assert_eq!(pieces[0].len(), 10);
+            assert_eq!(pieces[1].len(), N / 2 - 10);
+            assert_eq!(pieces[2].get(0).cloned(), Some(N / 2));
+
+            let chunks = PVec::concat(pieces).into_chunks(100);
+            assert_eq!(chunks.len(), 11);
+            assert_eq!(chunks[3].get(0).cloned(), Some(300));
+            assert_eq!(chunks[10].len(), 29);
+        }
+
+        let mut flat = PVec::new();
+        for i in 0..N {
+            flat.push(i);
+        }
+
+        let tree = flat.clone();
+
+        check_split_at_many_and_rotate(flat);
+        check_split_at_many_and_rotate(tree);
+    }
+
+    #[test]
+    fn append_flat_to_tree_leaves_other_empty() {
+        let mut tree = PVec::new_with_tree();
+        let mut flat = PVec::new();
+
+        for i in 0..10 {
+            tree.push(i);
+            flat.push(i + 10);
+        }
+
+        tree.append(&mut flat);
+
+        assert!(flat.is_empty());
+        assert_eq!(tree.len(), 20);
+
+        for i in 0..20 {
+            assert_eq!(tree.get(i).cloned(), Some(i));
+        }
+    }
+}
+
+mod test_pvec_concat {
+    use super::PVec;
+    use super::BRANCH_FACTOR;
+
+    #[test]
+    fn concat_flat_and_tree_vectors() {
+        let mut vecs = Vec::new();
+        let mut value = 0;
+
+        for size in 0..(BRANCH_FACTOR * 4) {
+            let mut vec = if size % 2 == 0 {
+                PVec::new()
+            } else {
+                PVec::new_with_tree()
+            };
+
+            for _ in 0..size {
+                vec.push(value);
+                value += 1;
+            }
+
+            vecs.push(vec);
+        }
+
+        let vec: PVec<usize> = vecs.iter().sum();
+        assert_eq!(vec.len(), value);
+
+        let vec = PVec::concat(vecs);
+        for i in 0..value {
+            assert_eq!(vec.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "arc", feature = "rayon_iter"))]
+    fn collect_parallel_iterator() {
+        use rayon::prelude::*;
+
+        let vec: PVec<usize> = (0..BRANCH_FACTOR * BRANCH_FACTOR * 4)
+            .into_par_iter()
+            .collect();
+
+        for i in 0..vec.len() {
+            assert_eq!(vec.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "arc", feature = "rayon_iter"))]
+    fn parallel_collect_of_many_chunks_must_match_sequential_collect() {
+        use super::{RbVec, RrbVec};
+        use rayon::prelude::*;
+
+        const N: usize = BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + 3;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        let expected: Vec<usize> = (0..N).map(|i| i * 3).collect();
+
+        pool.install(|| {
+            let par_iter = || (0..N).into_par_iter().with_max_len(BRANCH_FACTOR / 2 + 1);
+
+            let vec: PVec<usize> = par_iter().map(|i| i * 3).collect();
+            assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
+
+            let vec: RrbVec<usize> = par_iter().map(|i| i * 3).collect();
+            assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
+
+            let vec: RbVec<usize> = par_iter().map(|i| i * 3).collect();
+            assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
+        });
+    }
+}
+
+mod test_rrbvec_compact {
+    use super::RrbVec;
+    use super::BRANCH_FACTOR;
+
+    fn create_relaxed_vec(chunks: usize, chunk_size: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+        let mut value = 0;
+
+        for _ in 0..chunks {
+            let mut chunk = RrbVec::new();
+            for _ in 0..chunk_size {
+                chunk.push(value);
+                value += 1;
+            }
+
+            vec.append(&mut chunk);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn compact_keeps_elements_and_operations_working() {
+        let chunk_size = BRANCH_FACTOR + BRANCH_FACTOR / 2 + 1;
+        let mut vec = create_relaxed_vec(BRANCH_FACTOR * 2, chunk_size);
+        let len = vec.len();
+
+        vec.compact();
+        assert_eq!(vec.len(), len);
+
+        for i in 0..len {
+            assert_eq!(vec.get(i).cloned(), Some(i));
+        }
+
+        for i in len..len + BRANCH_FACTOR * BRANCH_FACTOR {
+            vec.push(i);
+        }
+
+        let mut right = vec.split_off(len / 2);
+        vec.append(&mut right);
+
+        for i in (0..len + BRANCH_FACTOR * BRANCH_FACTOR).rev() {
+            assert_eq!(vec.pop(), Some(i));
+        }
+
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn compact_must_not_mutate_cloned_vec() {
+        let mut vec = create_relaxed_vec(BRANCH_FACTOR * 2, BRANCH_FACTOR + 1);
+        let vec_clone = vec.clone();
+
+        vec.compact();
+        *vec.get_mut(0).unwrap() += 1;
+
+        assert_eq!(vec.len(), vec_clone.len());
+        assert_eq!(vec.get(0).cloned(), Some(1));
+
+        for i in 0..vec_clone.len() {
+            assert_eq!(vec_clone.get(i).cloned(), Some(i));
+        }
+    }
+
+    #[test]
+    fn compact_small_and_empty_vec() {
+        let mut empty: RrbVec<usize> = RrbVec::new();
+        empty.compact();
+        assert!(empty.is_empty());
+
+        let mut vec = create_relaxed_vec(3, BRANCH_FACTOR / 2);
+        vec.compact();
+
+        for i in 0..vec.len() {
+            assert_eq!(vec.get(i).cloned(), Some(i));
+        }
+
+        vec.push(vec.len());
+        assert_eq!(vec.len(), 3 * (BRANCH_FACTOR / 2) + 1);
+    }
+
+    #[test]
+    fn compact_relaxed_keeps_elements() {
+        for &preserve_shared in [false, true].iter() {
+            let mut vec = create_relaxed_vec(BRANCH_FACTOR * 2, BRANCH_FACTOR * 2 + 3);
+            let vec_clone = vec.clone();
+            let len = vec.len();
+
+            vec.compact_relaxed(preserve_shared);
+
+            for i in len..len + BRANCH_FACTOR * 3 {
+                vec.push(i);
+            }
+
+            for i in 0..vec.len() {
+                assert_eq!(vec.get(i).cloned(), Some(i));
+            }
+
+            for i in 0..vec_clone.len() {
+                assert_eq!(vec_clone.get(i).cloned(), Some(i));
+            }
+        }
+    }
+}
+
+mod test_rrbvec_repeat {
+    use super::BRANCH_FACTOR;
+    use super::{PVec, RrbVec};
+    use std::rc::Rc;
+
+    #[test]
+    fn from_elem_must_hold_n_clones() {
+        let sizes = [0, 1, BRANCH_FACTOR, BRANCH_FACTOR * BRANCH_FACTOR + 3];
+
+        for &n in sizes.iter() {
+            let mut vec = RrbVec::from_elem(7, n);
+            assert_eq!(vec.len(), n);
+
+            for i in 0..n {
+                assert_eq!(vec.get(i), Some(&7));
+            }
+
+            vec.push(8);
+            assert_eq!(vec.pop(), Some(8));
+
+            for _ in 0..n {
+                assert_eq!(vec.pop(), Some(7));
+            }
+
+            assert!(vec.is_empty());
+        }
+    }
+
+    #[test]
+    fn from_elem_must_share_nodes() {
+        let value = Rc::new(0);
+        let n = BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + 1;
+
+        let vec = RrbVec::from_elem(value.clone(), n);
+        assert_eq!(Rc::strong_count(&value), BRANCH_FACTOR + 2);
+
+        let mut copy = vec.clone();
+        *copy.get_mut(n / 2).unwrap() = Rc::new(1);
+
+        // only the touched leaf and the tail of the clone are copied
+        assert_eq!(Rc::strong_count(&value), BRANCH_FACTOR * 2 + 2);
+
+        for i in 0..n {
+            assert_eq!(**vec.get(i).unwrap(), 0);
+            assert_eq!(**copy.get(i).unwrap(), if i == n / 2 { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn resize_must_truncate_and_extend() {
+        let mut vec = RrbVec::new();
+        for i in 0..BRANCH_FACTOR + 3 {
+            vec.push(i);
+        }
+
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2 + 5;
+        vec.resize(len, 0);
+        assert_eq!(vec.len(), len);
+
+        for i in 0..len {
+            let expected = if i < BRANCH_FACTOR + 3 { i } else { 0 };
+            assert_eq!(vec.get(i), Some(&expected));
+        }
+
+        vec.resize(2, 0);
+        assert_eq!(vec.len(), 2);
+        assert_eq!(vec.get(1), Some(&1));
+
+        vec.resize(0, 0);
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn pvec_repeat_and_resize() {
+        let mut flat = PVec::new();
+        let mut tree = PVec::repeat(1, BRANCH_FACTOR * 3);
+
+        flat.resize(BRANCH_FACTOR * 3, 1);
+        assert_eq!(flat.len(), tree.len());
+
+        tree.resize(BRANCH_FACTOR * 5 + 1, 2);
+        flat.resize(BRANCH_FACTOR * 5 + 1, 2);
+
+        assert_eq!(tree.len(), flat.len());
+        for i in 0..flat.len() {
+            assert_eq!(tree.get(i), flat.get(i));
+        }
+    }
+}
+
+mod test_pvec_shrink_to_fit {
+    use super::PVec;
+    use super::BRANCH_FACTOR;
+
+    #[test]
+    fn shrink_to_fit_keeps_elements() {
+        let mut flat = PVec::new();
+        let mut tree = PVec::new_with_tree();
+
+        for i in 0..BRANCH_FACTOR * 3 {
+            flat.push(i);
+
+            let mut chunk = PVec::new_with_tree();
+            chunk.push(i);
+            tree.append(&mut chunk);
+        }
+
+        flat.shrink_to_fit();
+        tree.shrink_to_fit();
+
+        for i in 0..BRANCH_FACTOR * 3 {
+            assert_eq!(flat.get(i).cloned(), Some(i));
+            assert_eq!(tree.get(i).cloned(), Some(i));
+        }
+    }
+}
+
+mod test_measured_rrbvec {
+    use super::BRANCH_FACTOR;
+    use pvec::core::measure::{Measure, MeasuredRrbVec};
+
+    #[derive(Clone, Debug)]
+    struct Sum;
+
+    impl Measure<usize> for Sum {
+        type Summary = usize;
+
+        fn identity() -> usize {
+            0
+        }
+
+        fn measure(item: &usize) -> usize {
+            *item
+        }
+
+        fn combine(left: &usize, right: &usize) -> usize {
+            left + right
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Max;
+
+    impl Measure<usize> for Max {
+        type Summary = Option<usize>;
+
+        fn identity() -> Option<usize> {
+            None
+        }
+
+        fn measure(item: &usize) -> Option<usize> {
+            Some(*item)
+        }
+
+        fn combine(left: &Option<usize>, right: &Option<usize>) -> Option<usize> {
+            std::cmp::max(*left, *right)
+        }
+    }
+
+    fn check_sums(vec: &MeasuredRrbVec<usize, Sum>, expected: &[usize]) {
+        assert_eq!(vec.len(), expected.len());
+        assert_eq!(vec.summary(), expected.iter().sum::<usize>());
+
+        let step = expected.len() / 16 + 1;
+
+        for from in (0..=expected.len()).step_by(step) {
+            for to in (from..=expected.len()).step_by(step / 2 + 1) {
+                let sum = expected[from..to].iter().sum::<usize>();
+                assert_eq!(vec.range_summary(from..to), sum);
+            }
+        }
+
+        let total = vec.summary();
+        for threshold in (0..total + 1).step_by(total / 64 + 1) {
+            let mut prefix = 0;
+            let index = expected.iter().position(|item| {
+                prefix += item;
+                prefix > threshold
+            });
+
+            assert_eq!(vec.find_by_measure(|sum| *sum > threshold), index);
+        }
+    }
+
+    fn create_relaxed_vec(chunks: usize, chunk_size: usize) -> MeasuredRrbVec<usize, Sum> {
+        let mut vec = MeasuredRrbVec::new();
+        let mut value = 0;
+
+        for _ in 0..chunks {
+            let mut chunk = MeasuredRrbVec::new();
+            for _ in 0..chunk_size {
+                chunk.push(value % 7);
+                value += 1;
+            }
+
+            vec.append(&mut chunk);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn summary_must_follow_push_and_pop() {
+        let mut vec = MeasuredRrbVec::new();
+        let mut expected = Vec::new();
+
+        for i in 0..BRANCH_FACTOR * BRANCH_FACTOR * 2 + 3 {
+            vec.push(i);
+            expected.push(i);
+
+            assert_eq!(vec.summary(), expected.iter().sum::<usize>());
+        }
+
+        check_sums(&vec, &expected);
+
+        while !expected.is_empty() {
+            assert_eq!(vec.pop(), expected.pop());
+            assert_eq!(vec.summary(), expected.iter().sum::<usize>());
+        }
+
+        assert_eq!(vec.pop(), None);
+        assert_eq!(vec.summary(), 0);
+    }
+
+    #[test]
+    fn summary_must_follow_get_mut() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR + BRANCH_FACTOR / 2;
+
+        let mut vec: MeasuredRrbVec<usize, Sum> = (0..len).collect();
+        let mut expected: Vec<usize> = (0..len).collect();
+
+        let vec_clone = vec.clone();
+
+        for i in (0..len).step_by(3) {
+            *vec.get_mut(i).unwrap() += 10;
+            expected[i] += 10;
+        }
+
+        assert!(vec.get_mut(len).is_none());
+
+        check_sums(&vec, &expected);
+        check_sums(&vec_clone, &(0..len).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn summary_must_follow_append_and_split_off() {
+        let chunk_size = BRANCH_FACTOR + BRANCH_FACTOR / 2 + 1;
+        let chunks = BRANCH_FACTOR * 2;
+
+        let vec = create_relaxed_vec(chunks, chunk_size);
+        let expected: Vec<usize> = (0..chunks * chunk_size).map(|i| i % 7).collect();
+
+        check_sums(&vec, &expected);
+
+        let step = expected.len() / 13 + 1;
+        for mid in (0..=expected.len()).step_by(step) {
+            let mut left = vec.clone();
+            let mut right = left.split_off(mid);
+
+            check_sums(&left, &expected[..mid]);
+            check_sums(&right, &expected[mid..]);
+
+            right.push(5);
+            *left.get_mut(0).unwrap_or_else(|| right.get_mut(0).unwrap()) += 1;
+
+            let mut expected = expected.clone();
+            expected.push(5);
+            expected[0] += 1;
+
+            left.append(&mut right);
+
+            assert!(right.is_empty());
+            check_sums(&left, &expected);
+        }
+    }
+
+    #[test]
+    fn summary_must_follow_split_at_many() {
+        let chunk_size = BRANCH_FACTOR + BRANCH_FACTOR / 2 + 1;
+        let chunks = BRANCH_FACTOR * 2;
+
+        let vec = create_relaxed_vec(chunks, chunk_size);
+        let expected: Vec<usize> = (0..chunks * chunk_size).map(|i| i % 7).collect();
+
+        let len = expected.len();
+        let indices = [
+            0,
+            1,
+            chunk_size,
+            chunk_size,
+            len / 3,
+            len / 2 + 1,
+            len - 1,
+            len,
+        ];
+
+        let mut start = 0;
+        for (i, mut piece) in vec.split_at_many(&indices).into_iter().enumerate() {
+            let end = indices.get(i).cloned().unwrap_or(len);
+            check_sums(&piece, &expected[start..end]);
+
+            let mut expected = expected[start..end].to_vec();
+            piece.push(5);
+            expected.push(5);
+
+            check_sums(&piece, &expected);
+            start = end;
+        }
+    }
+
+    #[test]
+    fn find_by_measure_must_return_first_index() {
+        let mut vec: MeasuredRrbVec<usize, Max> = MeasuredRrbVec::new();
+        assert_eq!(vec.find_by_measure(|max| max.is_some()), None);
+
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 3;
+        for i in 0..len {
+            vec.push((i * 31) % 1000);
+        }
+
+        for threshold in (0..1000).step_by(50) {
+            let index = (0..len).position(|i| (i * 31) % 1000 > threshold);
+            assert_eq!(vec.find_by_measure(|max| *max > Some(threshold)), index);
+        }
+
+        assert_eq!(vec.range_summary(0..0), None);
+        let max = (0..len).map(|i| (i * 31) % 1000).max();
+        assert_eq!(vec.range_summary(0..len), max);
+        assert_eq!(vec.range_summary(1..3), Some(62));
+    }
+
+    #[test]
+    fn get_must_return_none_when_out_of_bounds() {
+        for &len in [0, 3, BRANCH_FACTOR * BRANCH_FACTOR + 3].iter() {
+            let vec: MeasuredRrbVec<usize, Sum> = (0..len).collect();
+
+            for i in 0..len {
+                assert_eq!(vec.get(i), Some(&i));
+            }
+
+            assert_eq!(vec.get(len), None);
+            assert_eq!(vec.get(len + BRANCH_FACTOR), None);
+            assert_eq!(vec.get(len + 1000), None);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn index_must_panic_with_message_when_out_of_bounds() {
+        let vec: MeasuredRrbVec<usize, Sum> = (0..3).collect();
+        let _ = vec[1000];
+    }
+
+    #[test]
+    #[should_panic]
+    fn range_summary_must_panic_when_out_of_bounds() {
+        let vec: MeasuredRrbVec<usize, Sum> = (0..10).collect();
+        vec.range_summary(5..11);
+    }
+}
+
+mod test_rope {
+    use super::BRANCH_FACTOR;
+    use pvec::rope::Rope;
+
+    fn sample_text(lines: usize) -> String {
+        let mut text = String::new();
+
+        for i in 0..lines {
+            text.push_str(&format!("line {} — ünïcödé ✓ 𝄞\n", i));
+        }
+
+        text
+    }
+
+    fn check_conversions(rope: &Rope, text: &str) {
+        assert_eq!(rope.len(), text.len());
+        assert_eq!(rope.len_chars(), text.chars().count());
+        assert_eq!(rope.len_lines(), text.matches('\n').count() + 1);
+        assert_eq!(rope.to_string(), text);
+
+        for (char_idx, (byte_idx, _)) in text.char_indices().enumerate() {
+            assert_eq!(rope.char_to_byte(char_idx), byte_idx);
+            assert_eq!(rope.byte_to_char(byte_idx), char_idx);
+            assert_eq!(
+                rope.byte_to_line(byte_idx),
+                text[..byte_idx].matches('\n').count()
+            );
+        }
+
+        assert_eq!(rope.char_to_byte(rope.len_chars()), text.len());
+
+        let mut line_start = 0;
+        for line in 0..rope.len_lines() {
+            assert_eq!(rope.line_to_byte(line), line_start);
+            assert_eq!(rope.line_to_char(line), text[..line_start].chars().count());
+
+            line_start += text[line_start..].find('\n').map_or(0, |i| i + 1);
+        }
+    }
+
+    #[test]
+    fn conversions_must_match_string() {
+        let text = sample_text(BRANCH_FACTOR * 3);
+        let rope: Rope = text.parse().unwrap();
+
+        check_conversions(&rope, &text);
+        check_conversions(&Rope::new(), "");
+    }
+
+    #[test]
+    fn insert_and_remove_must_match_string() {
+        let mut text = sample_text(BRANCH_FACTOR);
+        let mut rope = Rope::from(text.as_str());
+
+        let snapshot = rope.clone();
+        let snapshot_text = text.clone();
+
+        for i in 0..BRANCH_FACTOR {
+            let char_idx = (i * 37) % text.chars().count();
+            let byte_idx = rope.char_to_byte(char_idx);
+
+            rope.insert_str(byte_idx, "«inserted»");
+            text.insert_str(byte_idx, "«inserted»");
+
+            let end = rope.char_to_byte(char_idx + i % 5 + 1);
+            rope.remove(byte_idx..end);
+            text.replace_range(byte_idx..end, "");
+        }
+
+        check_conversions(&rope, &text);
+        check_conversions(&snapshot, &snapshot_text);
+    }
+
+    #[test]
+    fn slice_and_split_must_share_text() {
+        let text = sample_text(BRANCH_FACTOR * 2);
+        let mut rope = Rope::from(text.as_str());
+
+        let start = rope.line_to_byte(3);
+        let end = rope.line_to_byte(BRANCH_FACTOR + 1);
+
+        let slice = rope.slice(start..end);
+        check_conversions(&slice, &text[start..end]);
+
+        let mut right = rope.split_off(start);
+        check_conversions(&rope, &text[..start]);
+        check_conversions(&right, &text[start..]);
+
+        rope.append(&mut right);
+        assert!(right.is_empty());
+        assert_eq!(rope, text.as_str());
+    }
+
+    #[test]
+    fn chars_and_lines_must_match_string() {
+        let text = "first\r\nsecond\n\nƒourth — 𝄞";
+        let rope = Rope::from(text);
+
+        assert!(rope.chars().eq(text.chars()));
+        assert!(rope.bytes().eq(text.bytes()));
+
+        let lines: Vec<String> = rope.lines().map(|line| line.to_string()).collect();
+        assert_eq!(lines, text.lines().collect::<Vec<_>>());
+
+        let rope = Rope::from("trailing\n");
+        assert_eq!(rope.lines().count(), 1);
+        assert_eq!(Rope::new().lines().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_must_panic_inside_of_char() {
+        let mut rope = Rope::from("ü");
+        rope.insert_str(1, "x");
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_must_panic_inside_of_char() {
+        let rope = Rope::from("a𝄞b");
+        rope.slice(0..3);
+    }
+}
+
+mod test_pbitvec {
+    use super::BRANCH_FACTOR;
+    use pvec::bitvec::PBitVec;
+
+    fn bits(len: usize, seed: usize) -> Vec<bool> {
+        (0..len).map(|i| (i * 7 + seed) % 5 < 2).collect()
+    }
+
+    fn check(vec: &PBitVec, expected: &[bool]) {
+        assert_eq!(vec.len(), expected.len());
+        assert_eq!(vec.iter().collect::<Vec<bool>>(), expected);
+
+        let mut ones = 0;
+
+        for (i, bit) in expected.iter().enumerate() {
+            assert_eq!(vec.get(i), Some(*bit));
+            assert_eq!(vec.rank(i), ones);
+
+            if *bit {
+                assert_eq!(vec.select(ones), Some(i));
+                ones += 1;
+            }
+        }
+
+        assert_eq!(vec.get(expected.len()), None);
+        assert_eq!(vec.rank(expected.len()), ones);
+        assert_eq!(vec.select(ones), None);
+        assert_eq!(vec.count_ones(), ones);
+        assert_eq!(vec.count_zeros(), expected.len() - ones);
+    }
+
+    #[test]
+    fn push_and_pop_must_keep_bits() {
+        let len = 64 * BRANCH_FACTOR * 3 + 17;
+        let mut expected = bits(len, 0);
+        let mut vec: PBitVec = expected.iter().cloned().collect();
+
+        check(&vec, &expected);
+
+        for _ in 0..64 * BRANCH_FACTOR + 3 {
+            assert_eq!(vec.pop(), expected.pop());
+        }
+
+        check(&vec, &expected);
+
+        while !expected.is_empty() {
+            assert_eq!(vec.pop(), expected.pop());
+        }
+
+        assert_eq!(vec.pop(), None);
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn set_must_not_affect_clones() {
+        let len = 64 * BRANCH_FACTOR * 2 + 5;
+        let expected = bits(len, 1);
+        let vec: PBitVec = expected.iter().cloned().collect();
+
+        let mut vec_clone = vec.clone();
+        let mut expected_clone = expected.clone();
+
+        for i in (0..len).step_by(3) {
+            vec_clone.set(i, !expected_clone[i]);
+            expected_clone[i] = !expected_clone[i];
+        }
+
+        check(&vec, &expected);
+        check(&vec_clone, &expected_clone);
+    }
+
+    #[test]
+    fn split_and_append_must_keep_bits() {
+        let len = 64 * BRANCH_FACTOR * 2 + 40;
+
+        for at in &[0, 1, 63, 64, 65, 64 * BRANCH_FACTOR + 7, len - 1, len] {
+            let mut expected = bits(len, 2);
+            let mut vec: PBitVec = expected.iter().cloned().collect();
+
+            let mut right = vec.split_off(*at);
+            let mut expected_right = expected.split_off(*at);
+
+            check(&vec, &expected);
+            check(&right, &expected_right);
+
+            right.push(true);
+            expected_right.push(true);
+
+            vec.append(&mut right);
+            expected.append(&mut expected_right);
+
+            check(&vec, &expected);
+            assert!(right.is_empty());
+        }
+    }
+
+    #[test]
+    fn bitwise_operations_must_combine_bits() {
+        let len = 64 * BRANCH_FACTOR * 2 + 9;
+        let expected_a = bits(len, 3);
+
+        let vec_a: PBitVec = expected_a.iter().cloned().collect();
+
+        let mut vec_b = vec_a.clone();
+        let mut expected_b = expected_a.clone();
+
+        for i in (0..64).chain(len - 3..len) {
+            vec_b.set(i, i % 2 == 0);
+            expected_b[i] = i % 2 == 0;
+        }
+
+        let mut vec_c = vec_a.clone();
+        let mut vec_c_right = vec_c.split_off(100);
+        vec_c_right.set(0, !expected_b[100]);
+        vec_c.append(&mut vec_c_right);
+
+        let mut expected_c = expected_a.clone();
+        expected_c[100] = !expected_c[100];
+
+        for (vec, expected) in [(&vec_b, &expected_b), (&vec_c, &expected_c)] {
+            let and: Vec<bool> = expected_a
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| a & b)
+                .collect();
+            let or: Vec<bool> = expected_a
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| a | b)
+                .collect();
+            let xor: Vec<bool> = expected_a
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| a ^ b)
+                .collect();
+
+            check(&vec_a.and(vec), &and);
+            check(&vec_a.or(vec), &or);
+            check(&vec_a.xor(vec), &xor);
+        }
+
+        assert_eq!(vec_a.and(&vec_a), vec_a);
+        assert_eq!(vec_a.xor(&vec_a).count_ones(), 0);
+    }
+}
+
+mod test_history {
+    use super::BRANCH_FACTOR;
+    use pvec::core::RrbVec;
+    use pvec::history::History;
+    use pvec::PVec;
+
+    fn to_vec(vec: &RrbVec<usize>) -> Vec<usize> {
+        vec.clone().into_iter().collect()
+    }
+
+    #[test]
+    fn undo_and_redo_must_move_between_versions() {
+        let mut vec = RrbVec::new();
+        let mut history = History::new(vec.clone());
+
+        for i in 0..5 {
+            vec.push(i);
+            history.commit(vec.clone());
+        }
+
+        assert_eq!(history.len(), 6);
+        assert_eq!(to_vec(history.current()), vec![0, 1, 2, 3, 4]);
+
+        assert_eq!(to_vec(history.undo().unwrap()), vec![0, 1, 2, 3]);
+        assert_eq!(to_vec(history.undo().unwrap()), vec![0, 1, 2]);
+        assert_eq!(to_vec(history.redo().unwrap()), vec![0, 1, 2, 3]);
+
+        for _ in 0..4 {
+            history.undo().unwrap();
+        }
+
+        assert!(history.current().is_empty());
+        assert!(history.undo().is_none());
+
+        for _ in 0..5 {
+            history.redo().unwrap();
+        }
+
+        assert_eq!(to_vec(history.current()), vec![0, 1, 2, 3, 4]);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn commit_after_undo_must_start_branch() {
+        let mut history = History::new(PVec::new());
+
+        let mut vec = history.current().clone();
+        vec.push(1);
+        let first = history.commit(vec);
+
+        history.undo();
+
+        let mut vec = history.current().clone();
+        vec.push(2);
+        let second = history.commit(vec);
+
+        let root = history.parent(second).unwrap();
+
+        assert_eq!(history.parent(first), Some(root));
+        assert_eq!(history.children(root), &[first, second]);
+
+        history.undo();
+        assert_eq!(history.redo().unwrap()[0], 2);
+
+        assert_eq!(history.checkout(first).unwrap()[0], 1);
+        history.undo();
+        assert_eq!(history.redo().unwrap()[0], 1);
+    }
+
+    #[test]
+    fn limit_must_keep_current_and_checkpoints() {
+        let mut vec = RrbVec::new();
+        let mut history = History::with_limit(vec.clone(), 3);
+
+        history.checkpoint("empty");
+
+        for i in 0..10 {
+            vec.push(i);
+            history.commit(vec.clone());
+
+            if i == 4 {
+                history.checkpoint("five");
+            }
+        }
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.restore("five").unwrap().len(), 5);
+        assert!(history.restore("empty").unwrap().is_empty());
+        assert!(history.undo().is_none());
+
+        assert!(history.restore("unknown").is_none());
+
+        history.remove_checkpoint("five");
+        assert_eq!(history.len(), 3);
+
+        let names: Vec<&str> = history.checkpoints().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["empty"]);
+
+        history.set_limit(Some(1));
+        assert_eq!(history.len(), 1);
+        assert!(history.current().is_empty());
+    }
+
+    #[test]
+    fn limit_must_count_current_and_checkpoints() {
+        let mut vec = RrbVec::new();
+        let mut history = History::with_limit(vec.clone(), 2);
+        let initial = history.current_id();
+
+        for i in 0..3 {
+            vec.push(i);
+            history.commit(vec.clone());
+            history.checkpoint(format!("{}", i));
+        }
+
+        // the checkpoints exceed the limit, as they are never discarded
+        assert_eq!(history.len(), 3);
+        assert!(history.get(initial).is_none());
+
+        for i in 3..5 {
+            vec.push(i);
+            history.commit(vec.clone());
+        }
+
+        // the current version counts towards the limit as well
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.undo().unwrap().len(), 3);
+        assert_eq!(history.redo().unwrap().len(), 5);
+
+        history.remove_checkpoint("0");
+        assert_eq!(history.len(), 3);
+
+        history.remove_checkpoint("1");
+        history.remove_checkpoint("2");
+        assert_eq!(history.len(), 2);
+
+        assert_eq!(history.current().len(), 5);
+        assert_eq!(history.undo().unwrap().len(), 3);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn memory_usage_must_report_shared_bytes() {
+        let mut vec = RrbVec::new();
+
+        for i in 0..BRANCH_FACTOR * BRANCH_FACTOR * 4 {
+            vec.push(i);
+        }
+
+        let mut history = History::new(vec.clone());
+        drop(vec);
+
+        let single = history.memory_usage();
+        assert_eq!(single.shared_bytes, 0);
+
+        let mut vec = history.current().clone();
+        *vec.get_mut(0).unwrap() = 42;
+        history.commit(vec);
+
+        let usage = history.memory_usage();
+
+        assert!(usage.shared_bytes > usage.unique_bytes);
+        assert!(usage.shared_bytes + usage.unique_bytes > single.unique_bytes);
+        assert!(usage.shared_bytes + usage.unique_bytes < single.unique_bytes * 2);
+    }
+}
+
+#[cfg(feature = "arc")]
+mod test_atomic_rrbvec {
+    use pvec::core::atomic::AtomicRrbVec;
+    use pvec::core::RrbVec;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn load_must_return_snapshot() {
+        let cell = AtomicRrbVec::new(RrbVec::new());
+
+        cell.update(|vec| vec.push(1));
+        let snapshot = cell.load();
+
+        cell.update(|vec| vec.push(2));
+        let previous = cell.swap(RrbVec::new());
+
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(previous.len(), 2);
+        assert!(cell.load().is_empty());
+
+        cell.store(snapshot);
+        assert_eq!(cell.into_inner().len(), 1);
+    }
+
+    #[test]
+    fn updates_under_contention_must_not_be_lost() {
+        let writers = 4;
+        let updates = 500;
+
+        let cell = Arc::new(AtomicRrbVec::default());
+        let mut handles = Vec::new();
+
+        for writer in 0..writers {
+            let cell = cell.clone();
+
+            handles.push(thread::spawn(move || {
+                for i in 0..updates {
+                    let len = cell.update(|vec| {
+                        vec.push(writer * updates + i);
+                        vec.len()
+                    });
+
+                    assert!(len > i);
+                }
+            }));
+        }
+
+        for _ in 0..4 {
+            let cell = cell.clone();
+
+            handles.push(thread::spawn(move || {
+                let mut last_len = 0;
+
+                while last_len < writers * updates {
+                    let snapshot = cell.load();
+
+                    assert!(snapshot.len() >= last_len);
+
+                    let mut next = vec![0; writers];
+                    for item in snapshot.clone() {
+                        assert_eq!(item % updates, next[item / updates]);
+                        next[item / updates] += 1;
+                    }
+
+                    last_len = snapshot.len();
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut items: Vec<usize> = cell.load().into_iter().collect();
+        items.sort();
+
+        assert_eq!(items, (0..writers * updates).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn stores_must_progress_while_readers_keep_loading() {
+        let cell = Arc::new(AtomicRrbVec::new(RrbVec::new()));
+        let done = Arc::new(AtomicBool::new(false));
+        let mut handles = Vec::new();
+
+        for _ in 0..8 {
+            let cell = cell.clone();
+            let done = done.clone();
+
+            handles.push(thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    cell.load();
+                }
+            }));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = {
+            let cell = cell.clone();
+
+            thread::spawn(move || {
+                for i in 0..2000 {
+                    let mut vec = RrbVec::new();
+                    vec.push(i);
+
+                    cell.store(vec);
+                }
+
+                sender.send(()).unwrap();
+            })
+        };
+
+        let result = receiver.recv_timeout(Duration::from_secs(60));
+        done.store(true, Ordering::SeqCst);
+
+        assert!(result.is_ok(), "the writer is blocked by the readers");
+
+        writer.join().unwrap();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cell.load().get(0), Some(&1999));
+    }
+}
+
+mod test_merkle {
+    use super::BRANCH_FACTOR;
+    use pvec::core::merkle::{ImportError, MerkleStore};
+    use pvec::core::RrbVec;
+    use std::collections::HashSet;
+
+    fn make_vec(len: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+
+        for i in 0..len {
+            vec.push(i);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn import_must_rebuild_exported_vec() {
+        for len in &[0, 1, BRANCH_FACTOR, BRANCH_FACTOR * BRANCH_FACTOR * 3 + 5] {
+            let vec = make_vec(*len);
+
+            let mut sender = MerkleStore::new();
+            let mut receiver = MerkleStore::new();
+
+            let bytes = sender.export(&vec, &receiver.hashes());
+            let imported = receiver.import(&bytes).unwrap();
+
+            assert_eq!(imported, vec);
+            assert_eq!(receiver.hash(&imported), sender.hash(&vec));
+            assert_eq!(receiver.hashes(), sender.hashes());
+        }
+    }
+
+    #[test]
+    fn export_must_skip_known_nodes() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 4;
+        let mut vec = make_vec(len);
+
+        let mut sender = MerkleStore::new();
+        let mut receiver = MerkleStore::new();
+
+        let full = sender.export(&vec, &receiver.hashes());
+        receiver.import(&full).unwrap();
+
+        *vec.get_mut(len / 2).unwrap() = 0;
+
+        let mut right = vec.split_off(len / 3);
+        right.push(42);
+        vec.append(&mut right);
+
+        let delta = sender.export(&vec, &receiver.hashes());
+        let imported = receiver.import(&delta).unwrap();
+
+        assert_eq!(imported, vec);
+        assert!(delta.len() * 4 < full.len());
+
+        let unchanged = sender.export(&vec, &receiver.hashes());
+        assert!(unchanged.len() < BRANCH_FACTOR * 16);
+        assert_eq!(receiver.import(&unchanged).unwrap(), vec);
+    }
+
+    #[test]
+    fn hashing_must_not_pin_nodes() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2;
+        let mut vec = make_vec(len);
+        let mut store = MerkleStore::new();
+
+        let hash = store.hash(&vec);
+        assert!(vec.is_unique());
+        assert!(!store.is_empty());
+
+        assert_eq!(vec.get_mut_cow(0).map(|(_, copied)| copied), Some(false));
+        *vec.get_mut(0).unwrap() = len;
+
+        assert_ne!(store.hash(&vec), hash);
+        assert_eq!(store.hash(&make_vec(len)), hash);
+
+        store.insert(&vec);
+        assert!(!vec.is_unique());
+
+        drop(vec);
+        assert!(!store.is_empty());
+    }
+
+    #[test]
+    fn import_must_reject_incomplete_input() {
+        let vec = make_vec(BRANCH_FACTOR * 3);
+
+        let mut sender = MerkleStore::new();
+        let mut receiver: MerkleStore<usize> = MerkleStore::new();
+
+        let mut known = HashSet::new();
+        sender.insert(&vec);
+        known.extend(sender.hashes());
+
+        let bytes = sender.export(&vec, &known);
+
+        match receiver.import(&bytes) {
+            Err(ImportError::MissingNode(hash)) => assert!(sender.contains(hash)),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let bytes = sender.export(&vec, &HashSet::new());
+
+        for len in 0..bytes.len() {
+            assert!(receiver.import(&bytes[..len]).is_err());
+        }
+
+        assert_eq!(receiver.import(&bytes).unwrap(), vec);
+    }
+}
+
+mod test_unique {
+    use super::BRANCH_FACTOR;
+    use pvec::core::RrbVec;
+    use pvec::PVec;
+
+    fn make_vec(len: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+
+        for i in 0..len {
+            vec.push(i);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn clones_must_not_be_unique() {
+        let mut vec = make_vec(BRANCH_FACTOR * BRANCH_FACTOR * 2 + 3);
+        assert!(vec.is_unique());
+
+        let copy = vec.clone();
+        assert!(!vec.is_unique());
+        assert!(vec.ptr_eq(&copy));
+
+        vec.make_unique();
+        assert!(vec.is_unique());
+        assert!(copy.is_unique());
+        assert!(!vec.ptr_eq(&copy));
+        assert_eq!(vec, copy);
+    }
+
+    #[test]
+    fn tail_only_vectors_must_not_share() {
+        assert!(make_vec(0).ptr_eq(&make_vec(0)));
+        assert!(!make_vec(3).ptr_eq(&make_vec(3)));
+        assert!(!make_vec(3).ptr_eq(&make_vec(0)));
+
+        let vec = make_vec(3);
+        assert!(!vec.ptr_eq(&vec.clone()));
+    }
+
+    #[test]
+    fn get_mut_cow_must_report_copies() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2 + 3;
+        let mut vec = make_vec(len);
+        let copy = vec.clone();
+
+        assert_eq!(vec.get_mut_cow(0).map(|(_, copied)| copied), Some(true));
+        assert_eq!(vec.get_mut_cow(1).map(|(_, copied)| copied), Some(false));
+        assert_eq!(
+            vec.get_mut_cow(len - 1).map(|(_, copied)| copied),
+            Some(false)
+        );
+        assert!(vec.get_mut_cow(len).is_none());
+
+        let (item, copied) = vec.get_mut_cow(BRANCH_FACTOR).unwrap();
+        *item += len;
+
+        assert!(copied);
+        assert_eq!(vec[BRANCH_FACTOR], BRANCH_FACTOR + len);
+        assert_eq!(copy[BRANCH_FACTOR], BRANCH_FACTOR);
+    }
+
+    #[test]
+    fn make_unique_range_must_copy_only_the_range() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2 + 3;
+        let mut vec = make_vec(len);
+        let copy = vec.clone();
+
+        vec.make_unique_range(BRANCH_FACTOR + 1..BRANCH_FACTOR * 3);
+
+        // the range touches the second and the third leaf, while the
+        // last three elements are in the tail, which is never shared
+        for i in (0..len).step_by(BRANCH_FACTOR) {
+            let copied = vec.get_mut_cow(i).unwrap().1;
+            let in_range = (BRANCH_FACTOR..BRANCH_FACTOR * 3).contains(&i);
+
+            assert_eq!(copied, !in_range && i < len - 3, "index {}", i);
+        }
+
+        assert!(vec.is_unique());
+        assert_eq!(vec, copy);
+    }
+
+    #[test]
+    fn pvec_must_expose_ownership() {
+        let mut flat = PVec::new();
+        for i in 0..BRANCH_FACTOR * 3 {
+            flat.push(i);
+        }
+
+        assert!(flat.is_unique());
+        assert!(flat.ptr_eq(&flat));
+        assert_eq!(flat.get_mut_cow(0).map(|(_, copied)| copied), Some(false));
+
+        let mut tree = flat.clone();
+        let copy = tree.clone();
+
+        assert!(!tree.is_unique());
+        assert!(tree.ptr_eq(&copy));
+        assert!(!tree.ptr_eq(&flat));
+
+        tree.make_unique_range(0..BRANCH_FACTOR);
+        assert_eq!(tree.get_mut_cow(0).map(|(_, copied)| copied), Some(false));
+        assert_eq!(
+            tree.get_mut_cow(BRANCH_FACTOR).map(|(_, copied)| copied),
+            Some(true)
+        );
+
+        tree.make_unique();
+        assert!(tree.is_unique());
+    }
+
+    #[cfg(all(feature = "arc", feature = "rayon_iter"))]
+    #[test]
+    fn par_make_unique_must_copy_all_nodes() {
+        let mut vec = make_vec(BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + 3);
+        let copy = vec.clone();
+
+        vec.par_make_unique();
+
+        assert!(vec.is_unique());
+        assert!(copy.is_unique());
+        assert_eq!(vec, copy);
+    }
+}
+
+mod test_stats {
+    use super::BRANCH_FACTOR;
+    use pvec::core::RrbVec;
+    use pvec::{Backing, PVec};
+
+    fn make_vec(len: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+
+        for i in 0..len {
+            vec.push(i);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn stats_must_count_nodes() {
+        let empty: RrbVec<usize> = RrbVec::new();
+        assert_eq!(empty.stats().heap_bytes, 0);
+        assert_eq!(empty.stats().leaves, 0);
+
+        let vec = make_vec(BRANCH_FACTOR * BRANCH_FACTOR * 2);
+        let stats = vec.stats();
+
+        assert!(stats.shift > 0);
+        assert_eq!(stats.leaves, BRANCH_FACTOR * 2);
+        assert_eq!(stats.branches, 3);
+        assert_eq!(stats.relaxed_branches, 0);
+        assert_eq!(stats.leaf_fill_ratio, 1.0);
+        assert!(stats.heap_bytes > 0);
+        assert_eq!(stats.shared_bytes, 0);
+    }
+
+    #[test]
+    fn stats_must_count_relaxed_branches() {
+        let mut vec = RrbVec::new();
+
+        for _ in 0..BRANCH_FACTOR * 2 {
+            let mut chunk = make_vec(BRANCH_FACTOR + BRANCH_FACTOR / 2 + 1);
+            vec.append(&mut chunk);
+        }
+
+        let stats = vec.stats();
+
+        assert!(stats.relaxed_branches > 0);
+        assert!(stats.leaf_fill_ratio < 1.0);
+    }
+
+    #[test]
+    fn stats_must_count_shared_nodes_once() {
+        let vec = RrbVec::from_elem(0, BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR);
+        let stats = vec.stats();
+
+        // the root is referenced once, while a branch and
+        // a leaf below it are referenced by all positions
+        assert_eq!(stats.leaves, 1);
+        assert_eq!(stats.branches, 2);
+        assert!(stats.shared_bytes > 0);
+        assert!(stats.shared_bytes < stats.heap_bytes);
+    }
+
+    #[test]
+    fn shared_with_must_measure_overlap() {
+        let vec = make_vec(BRANCH_FACTOR * BRANCH_FACTOR * 2);
+        let mut copy = vec.clone();
+
+        // the root is shared with the copy, so are all the nodes below it
+        assert_eq!(vec.stats().shared_bytes, vec.stats().heap_bytes);
+        assert_eq!(vec.shared_with(&copy), vec.stats().heap_bytes);
+
+        copy[0] += 1;
+
+        let shared = vec.shared_with(&copy);
+        assert_eq!(shared, copy.shared_with(&vec));
+        assert!(shared < vec.stats().heap_bytes);
+        assert!(shared > vec.stats().heap_bytes / 2);
+        assert_eq!(vec.stats().shared_bytes, shared);
+
+        let other = make_vec(BRANCH_FACTOR * 2);
+        assert_eq!(vec.shared_with(&other), 0);
+    }
+
+    #[test]
+    fn pvec_must_report_backing() {
+        let mut vec = PVec::new();
+        for i in 0..BRANCH_FACTOR * 2 {
+            vec.push(i);
+        }
+
+        assert_eq!(vec.backing(), Backing::Flat);
+        assert!(vec.stats().heap_bytes >= BRANCH_FACTOR * 2 * std::mem::size_of::<usize>());
+        assert_eq!(vec.stats().leaves, 0);
+
+        let mut copy = vec.clone();
+        assert_eq!(copy.backing(), Backing::Spilled);
+        assert_eq!(copy.stats().leaves, 2);
+
+        // the spill leaves the cloned vector flat
+        assert_eq!(vec.backing(), Backing::Flat);
+        assert_eq!(copy.clone().backing(), Backing::Spilled);
+        assert_eq!(copy.split_off(1).backing(), Backing::Spilled);
+
+        let mut tree = PVec::new_with_tree();
+        tree.push(0);
+        assert_eq!(tree.backing(), Backing::Tree);
+        assert_eq!(tree.clone().backing(), Backing::Tree);
+        assert_eq!(PVec::repeat(0, 3).backing(), Backing::Tree);
+
+        // flat vectors spill when a tree is appended to them
+        let mut flat = PVec::new();
+        flat.push(0);
+        flat.append(&mut PVec::new());
+        assert_eq!(flat.backing(), Backing::Flat);
+
+        flat.append(&mut tree);
+        assert_eq!(flat.backing(), Backing::Spilled);
+
+        // the comparisons do not depend on the origin of the tree
+        let mut built = PVec::new_with_tree();
+        built.push(0);
+        built.push(0);
+        assert_eq!(flat, built);
+    }
+}
+
+mod test_debug {
+    use super::BRANCH_FACTOR;
+    use pvec::core::{RbVec, RrbVec};
+    use pvec::PVec;
+
+    #[test]
+    fn debug_must_print_elements_like_vec() {
+        let elements: Vec<usize> = (0..BRANCH_FACTOR * 3 + 1).collect();
+
+        let mut rbvec = RbVec::new();
+        let mut rrbvec = RrbVec::new();
+        let mut pvec = PVec::new();
+
+        for &i in elements.iter() {
+            rbvec.push(i);
+            rrbvec.push(i);
+            pvec.push(i);
+        }
+
+        let expected = format!("{:?}", elements);
+
+        assert_eq!(format!("{:?}", rbvec), expected);
+        assert_eq!(format!("{:?}", rrbvec), expected);
+        assert_eq!(format!("{:?}", pvec), expected);
+        assert_eq!(format!("{:?}", pvec.clone()), expected);
+        assert_eq!(format!("{:#?}", rrbvec), format!("{:#?}", elements));
+        assert_eq!(format!("{:?}", RrbVec::<usize>::new()), "[]");
+    }
+
+    #[test]
+    fn debug_must_elide_huge_vecs() {
+        let vec = RrbVec::from_elem(7, 1_000_000);
+        let output = format!("{:?}", vec);
+
+        assert!(output.starts_with("[7, 7, "));
+        assert!(output.ends_with(", 7, 7]"));
+        assert!(output.contains(", .. 998976 more .., "));
+        assert!(output.len() < 8 * 1024);
+    }
+
+    #[test]
+    fn debug_structure_must_print_internals() {
+        let mut vec = RrbVec::new();
+        for i in 0..BRANCH_FACTOR + 1 {
+            vec.push(i);
+        }
+
+        let output = format!("{:?}", vec.debug_structure());
+        assert!(output.starts_with("RrbVec { tree: RrbTree { root: Some(Leaf("));
+        assert!(output.ends_with(", tail_len: 1 }"));
+
+        let mut pvec = PVec::new();
+        pvec.push(1);
+
+        assert_eq!(format!("{:?}", pvec.debug_structure()), "Flat([1])");
+
+        let output = format!("{:?}", pvec.clone().debug_structure());
+        assert!(output.starts_with("Tree(RrbVec { tree: "));
+    }
+}
+
+mod test_render {
+    use super::BRANCH_FACTOR;
+    use pvec::core::render;
+    use pvec::core::RrbVec;
+
+    fn make_vec(len: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+
+        for i in 0..len {
+            vec.push(i);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn to_dot_must_write_shared_nodes_once() {
+        let vec = make_vec(BRANCH_FACTOR * 2 + 1);
+        let mut copy = vec.clone();
+        copy[0] += 1;
+
+        let dot = render::to_dot(&[&vec, &copy]);
+
+        assert!(dot.starts_with("digraph pvec {"));
+        assert!(dot.ends_with("}\n"));
+
+        // a root and two leaves of the first vector, plus a root
+        // and a copy of the first leaf of the second one
+        assert_eq!(dot.matches("Branch").count(), 2);
+        assert_eq!(dot.matches("Leaf").count(), 3);
+        assert_eq!(dot.matches("fillcolor").count(), 1);
+        assert_eq!(dot.matches(" -> n2;").count(), 2);
+        assert!(dot.contains(&format!("Tail 1/{}", BRANCH_FACTOR)));
+        assert_eq!(vec.to_dot(), render::to_dot(&[&vec]));
+    }
+
+    #[test]
+    fn to_text_must_refer_to_seen_nodes() {
+        let mut vec = RrbVec::new();
+
+        for _ in 0..BRANCH_FACTOR * 2 {
+            let mut chunk = make_vec(BRANCH_FACTOR + BRANCH_FACTOR / 2 + 1);
+            vec.append(&mut chunk);
+        }
+
+        let copy = vec.clone();
+        let text = render::to_text(&[&vec, &copy]);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines[0].starts_with(&format!("vec 0: len {}, shift ", vec.len())));
+        assert!(lines[1].starts_with("├── #0 "));
+        assert!(lines[1].contains("refs 2"));
+        assert!(text.contains("RelaxedBranch"));
+        assert!(text.contains("sizes ["));
+        assert!(text.contains("vec 1: len"));
+        assert!(text.contains("├── #0 (see above)"));
+        assert_eq!(vec.to_text(), render::to_text(&[&vec]));
+
+        let empty: RrbVec<usize> = RrbVec::new();
+        let expected = format!("vec 0: len 0, shift 0\n└── Tail 0/{}\n", BRANCH_FACTOR);
+        assert_eq!(empty.to_text(), expected);
+    }
+}
+
+mod test_dedup {
+    use super::BRANCH_FACTOR;
+    use pvec::core::dedup::Deduplicator;
+    use pvec::core::RrbVec;
+
+    fn make_vec(len: usize, period: usize) -> RrbVec<usize> {
+        let mut vec = RrbVec::new();
+
+        for i in 0..len {
+            vec.push(i % period);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn dedup_must_keep_elements() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 3 + 5;
+
+        let mut vecs = vec![
+            make_vec(len, BRANCH_FACTOR),
+            make_vec(len, BRANCH_FACTOR * 2),
+            make_vec(len / 2, BRANCH_FACTOR),
+        ];
+        let expected = vecs.clone();
+
+        let mut dedup = Deduplicator::new();
+        let stats = dedup.dedup_all(vecs.iter_mut());
+
+        assert!(stats.replaced_nodes > 0);
+        assert!(stats.bytes_saved > 0);
+        assert_eq!(vecs, expected);
+    }
+
+    #[test]
+    fn dedup_must_be_incremental() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2;
+        let mut dedup = Deduplicator::new();
+
+        let mut first = make_vec(len, BRANCH_FACTOR);
+        dedup.dedup(&mut first);
+        let nodes = dedup.len();
+
+        // every node of the second vector is equal to a node of the first one,
+        // that is, 2 * BRANCH_FACTOR leaves, 2 branches and the root
+        let mut second = make_vec(len, BRANCH_FACTOR);
+        let stats = dedup.dedup(&mut second);
+
+        assert_eq!(dedup.len(), nodes);
+        assert_eq!(stats.replaced_nodes, BRANCH_FACTOR * 2 + 3);
+
+        // shared subtrees are not walked again
+        assert_eq!(dedup.dedup(&mut second).visited_nodes, 1);
+        assert_eq!(dedup.stats().replaced_nodes, BRANCH_FACTOR * 4 + 3);
+    }
+
+    #[test]
+    fn dedup_vecs_must_copy_on_write() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR + 1;
+
+        let mut first = make_vec(len, BRANCH_FACTOR);
+        let mut second = make_vec(len, BRANCH_FACTOR);
+
+        let mut dedup = Deduplicator::new();
+        dedup.dedup_all(vec![&mut first, &mut second]);
+
+        *first.get_mut(0).unwrap() = len;
+        *second.get_mut(BRANCH_FACTOR).unwrap() = len;
+
+        for i in 0..len {
+            assert_eq!(first[i], if i == 0 { len } else { i % BRANCH_FACTOR });
+            assert_eq!(
+                second[i],
+                if i == BRANCH_FACTOR {
+                    len
+                } else {
+                    i % BRANCH_FACTOR
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn purge_must_release_dropped_vecs() {
+        let mut dedup = Deduplicator::new();
+        let mut vec = make_vec(BRANCH_FACTOR * 5, BRANCH_FACTOR * 2);
+
+        dedup.dedup(&mut vec);
+        assert!(!dedup.is_empty());
+
+        drop(vec);
+        dedup.purge();
+        assert!(dedup.is_empty());
+    }
+}
+
+mod test_disk_rrbvec {
+    use super::BRANCH_FACTOR;
+    use pvec::core::disk::{DiskRrbVec, FileStore, MemoryStore, NodeStore, RecordId};
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::process;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn make_vec<S: NodeStore>(store: S, len: usize) -> DiskRrbVec<usize, S> {
+        let mut vec = DiskRrbVec::with_store(store);
+
+        for i in 0..len {
+            vec.push(i);
+        }
+
+        vec
+    }
+
+    fn flush_and_open<S: NodeStore>(store: S) {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR + 3;
+
+        let mut vec = make_vec(store.clone(), len);
+        let root = vec.flush().unwrap();
+
+        let reopened = DiskRrbVec::<usize, S>::open(store, root).unwrap();
+        assert_eq!(reopened.loaded_leaves(), 0);
+        assert_eq!(reopened.len(), len);
+
+        assert_eq!(reopened, vec);
+        assert_eq!(reopened.loaded_leaves(), vec.loaded_leaves());
+    }
+
+    fn writes_after_flush<S: NodeStore>(store: S) {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 2;
+
+        let mut vec = make_vec(store.clone(), len);
+        let first = vec.flush().unwrap();
+
+        *vec.get_mut(len / 2).unwrap() = 0;
+        let mut right = vec.split_off(len / 3);
+        right.push(len);
+        vec.append(&mut right);
+
+        let second = vec.flush().unwrap();
+
+        let old = DiskRrbVec::<usize, S>::open(store.clone(), first).unwrap();
+        let new = DiskRrbVec::<usize, S>::open(store, second).unwrap();
+
+        assert!(old.iter().map(|item| *item).eq(0..len));
+        assert_eq!(new, vec);
+        assert_eq!(new.get(len / 2).as_deref(), Some(&0));
+        assert_eq!(new.get(len).as_deref(), Some(&len));
+    }
+
+    fn eviction<S: NodeStore>(store: S) {
+        let budget = 4;
+        let len = BRANCH_FACTOR * budget * 8;
+
+        let mut vec = DiskRrbVec::with_store(store);
+        vec.set_cache_budget(budget);
+
+        for i in 0..len {
+            vec.push(i);
+            assert!(vec.loaded_leaves() <= budget + 1);
+        }
+
+        for i in 0..len {
+            *vec.get_mut(i).unwrap() += 1;
+            assert!(vec.loaded_leaves() <= budget + 1);
+        }
+
+        let vec_clone = vec.clone();
+        for (i, item) in vec.iter().enumerate() {
+            assert_eq!(*item, i + 1);
+            assert!(vec.loaded_leaves() <= budget + 1);
+        }
+
+        vec.push(0);
+        assert!(vec.loaded_leaves() <= budget + 1);
+
+        for i in (0..len).rev() {
+            assert_eq!(vec_clone.get(i).as_deref(), Some(&(i + 1)));
+            assert!(vec_clone.loaded_leaves() <= budget + 1);
+        }
+    }
+
+    #[test]
+    fn flush_and_open_must_restore_elements() {
+        flush_and_open(MemoryStore::new());
+        flush_and_open(FileStore::temporary());
+    }
+
+    #[test]
+    fn writes_must_not_affect_flushed_versions() {
+        writes_after_flush(MemoryStore::new());
+        writes_after_flush(FileStore::temporary());
+    }
+
+    #[test]
+    fn eviction_must_keep_loaded_leaves_within_budget() {
+        eviction(MemoryStore::new());
+        eviction(FileStore::temporary());
+    }
+
+    #[test]
+    fn unchanged_nodes_must_not_be_rewritten() {
+        let store = MemoryStore::new();
+        let len = BRANCH_FACTOR * BRANCH_FACTOR;
+
+        // The leaves, their parent and the header.
+        let mut vec = make_vec(store.clone(), len);
+        vec.flush().unwrap();
+        assert_eq!(store.len(), BRANCH_FACTOR + 2);
+
+        // Leaves which are read, evicted and read again stay in their records.
+        vec.set_cache_budget(2);
+        for i in 0..len {
+            assert_eq!(vec.get(i).as_deref(), Some(&i));
+        }
+
+        vec.flush().unwrap();
+        assert_eq!(store.len(), BRANCH_FACTOR + 3);
+
+        *vec.get_mut(0).unwrap() = len;
+
+        vec.flush().unwrap();
+        assert_eq!(store.len(), BRANCH_FACTOR + 6);
+    }
+
+    #[test]
+    fn reads_must_evict_nodes_of_reopened_vectors() {
+        let store = MemoryStore::new();
+        let budget = 4;
+        let len = BRANCH_FACTOR * BRANCH_FACTOR * 4;
+
+        let root = make_vec(store.clone(), len).flush().unwrap();
+
+        let mut vec = DiskRrbVec::<usize, MemoryStore>::open(store, root).unwrap();
+        vec.set_cache_budget(budget);
+
+        for i in (0..len).step_by(BRANCH_FACTOR / 2) {
+            assert_eq!(vec.get(i).as_deref(), Some(&i));
+            assert!(vec.loaded_leaves() <= budget);
+        }
+
+        let mut count = 0;
+        for (i, item) in vec.iter().enumerate() {
+            assert_eq!(*item, i);
+            assert!(vec.loaded_leaves() <= budget);
+            count += 1;
+        }
+
+        assert_eq!(count, len);
+    }
+
+    /// A store whose reads fail on demand.
+    #[derive(Debug, Clone, Default)]
+    struct FailingStore {
+        records: MemoryStore,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl NodeStore for FailingStore {
+        fn write(&self, record: &[u8]) -> io::Result<RecordId> {
+            self.records.write(record)
+        }
+
+        fn read(&self, id: RecordId) -> io::Result<Vec<u8>> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(io::Error::new(io::ErrorKind::Other, "store is unavailable"));
+            }
+
+            self.records.read(id)
+        }
+
+        fn same_store(&self, other: &Self) -> bool {
+            self.records.same_store(&other.records)
+        }
+    }
+
+    fn open_failing(len: usize) -> (FailingStore, DiskRrbVec<usize, FailingStore>) {
+        let store = FailingStore::default();
+        let root = make_vec(store.clone(), len).flush().unwrap();
+
+        let vec = DiskRrbVec::open(store.clone(), root).unwrap();
+        assert_eq!(vec.try_get(0).unwrap().as_deref(), Some(&0));
+
+        store.failing.store(true, Ordering::SeqCst);
+        (store, vec)
+    }
+
+    #[test]
+    fn failed_reads_must_return_errors() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR;
+        let (store, vec) = open_failing(len);
+
+        assert_eq!(vec.try_get(1).unwrap().as_deref(), Some(&1));
+        assert!(vec.try_get(len).unwrap().is_none());
+
+        let err = vec.try_get(len - 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+
+        let results: Vec<_> = vec.try_iter().collect();
+        assert_eq!(results.len(), len);
+
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.is_ok(), i < BRANCH_FACTOR);
+        }
+
+        store.failing.store(false, Ordering::SeqCst);
+        assert!(vec.try_iter().map(|item| *item.unwrap()).eq(0..len));
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to read the node")]
+    fn failed_reads_must_panic_in_get() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR;
+        let (_store, vec) = open_failing(len);
+
+        vec.get(len - 1);
+    }
+
+    #[test]
+    fn file_store_must_reopen_from_path() {
+        let path = env::temp_dir().join(format!("pvec-test-{}.store", process::id()));
+        let len = BRANCH_FACTOR * BRANCH_FACTOR;
+
+        let root = {
+            let mut vec = make_vec(FileStore::open(&path).unwrap(), len);
+            vec.flush().unwrap()
+        };
+
+        let store = FileStore::open(&path).unwrap();
+        let vec = DiskRrbVec::<usize, FileStore>::open(store, root).unwrap();
+
+        assert!(vec.iter().map(|item| *item).eq(0..len));
+
+        drop(vec);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_must_reject_malformed_records() {
+        let store = MemoryStore::new();
+
+        let vec = make_vec(store.clone(), BRANCH_FACTOR * 2);
+        let garbage = store.write(&[1, 2, 3]).unwrap();
+
+        let err = DiskRrbVec::<usize, MemoryStore>::open(store.clone(), garbage).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(vec.len(), BRANCH_FACTOR * 2);
+    }
+
+    #[test]
+    fn into_iter_must_yield_elements_from_both_ends() {
+        let len = BRANCH_FACTOR * BRANCH_FACTOR + BRANCH_FACTOR / 2;
+
+        let mut vec = make_vec(MemoryStore::new(), len);
+        vec.set_cache_budget(2);
+
+        let mut right = vec.split_off(len / 3);
+        vec.append(&mut right);
+
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.size_hint(), (len, Some(len)));
+
+        for i in 0..len / 2 {
+            assert_eq!(iter.next(), Some(i));
+            assert_eq!(iter.next_back(), Some(len - 1 - i));
+        }
+
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+}
+
+macro_rules! make_io_tests {
+    ($vec:ident, $module:ident) => {
+        mod $module {
+            use super::$vec;
+            use super::BRANCH_FACTOR;
+            use pvec::io::Cursor;
+            use std::io;
+            use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+
+            struct ChokedWriter {
+                bytes: Vec<u8>,
+                limit: usize,
+            }
+
+            impl Write for ChokedWriter {
+                fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                    let len = buf.len().min(self.limit);
+                    self.bytes.extend_from_slice(&buf[..len]);
+                    Ok(len)
+                }
+
+                fn flush(&mut self) -> io::Result<()> {
+                    Ok(())
+                }
+            }
+
+            fn bytes(len: usize) -> Vec<u8> {
+                (0..len).map(|i| (i * 7 % 251) as u8).collect()
+            }
+
+            fn make_vec(len: usize) -> $vec<u8> {
+                let mut vec = $vec::new();
+
+                for byte in bytes(len) {
+                    vec.push(byte);
+                }
+
+                vec
+            }
+
+            #[test]
+            fn read_must_return_all_bytes() {
+                for len in &[0, 1, BRANCH_FACTOR, BRANCH_FACTOR * 37 + 5] {
+                    let mut cursor = Cursor::new(make_vec(*len));
+                    let mut out = Vec::new();
+
+                    cursor.read_to_end(&mut out).unwrap();
+
+                    assert_eq!(out, bytes(*len));
+                    assert_eq!(cursor.position(), *len as u64);
+                }
             }
 
             #[test]
-            fn interleaving_push_and_append_operations_must_maintain_correct_internal_state_for_var_sizes_32() {
-                let mut vec_one = $vec::new();
+            fn fill_buf_must_not_cross_leaves() {
+                let len = BRANCH_FACTOR * 9 + 3;
+                let mut cursor = Cursor::new(make_vec(len));
+                let mut out = Vec::new();
 
-                for i in 0..32 {
-                    vec_one.push(i);
-                }
+                cursor.fill_buf().unwrap();
+                cursor.consume(1);
 
-                let mut vec_two = $vec::new();
+                loop {
+                    let buf = cursor.fill_buf().unwrap().to_vec();
 
-                for i in 0..1024 {
-                    if i % 2 == 0 {
-                        vec_two.push(i);
-                    } else {
-                        vec_two.append(&mut vec_one.clone());
+                    if buf.is_empty() {
+                        break;
                     }
 
-                    for k in 0..vec_two.len() {
-                        vec_two.get(k).unwrap();
-                    }
+                    assert!(buf.len() <= BRANCH_FACTOR);
+                    cursor.consume(buf.len());
+                    out.extend(buf);
                 }
+
+                assert_eq!(out, bytes(len)[1..].to_vec());
             }
 
             #[test]
-            fn zero_sized_values() {
-                let mut v = $vec::new();
-                assert_eq!(v.len(), 0);
-
-                v.push(());
-                assert_eq!(v.len(), 1);
-
-                v.push(());
-                assert_eq!(v.len(), 2);
-                assert_eq!(v.pop(), Some(()));
-                assert_eq!(v.pop(), Some(()));
-                assert_eq!(v.pop(), None);
+            fn seek_must_move_position() {
+                let len = BRANCH_FACTOR * 5 + 1;
+                let expected = bytes(len);
+                let mut cursor = Cursor::new(make_vec(len));
+                let mut buf = [0; 3];
+
+                assert_eq!(cursor.seek(SeekFrom::Start(10)).unwrap(), 10);
+                cursor.read_exact(&mut buf).unwrap();
+                assert_eq!(&buf, &expected[10..13]);
+
+                assert_eq!(cursor.seek(SeekFrom::Current(-5)).unwrap(), 8);
+                cursor.read_exact(&mut buf).unwrap();
+                assert_eq!(&buf, &expected[8..11]);
+
+                assert_eq!(cursor.seek(SeekFrom::End(-3)).unwrap(), len as u64 - 3);
+                cursor.read_exact(&mut buf).unwrap();
+                assert_eq!(&buf, &expected[len - 3..]);
+
+                assert!(cursor.seek(SeekFrom::End(-(len as i64) - 1)).is_err());
+                assert_eq!(cursor.read(&mut buf).unwrap(), 0);
+            }
 
-                assert_eq!(v.len(), 0);
+            #[test]
+            fn write_must_overwrite_and_extend() {
+                let len = BRANCH_FACTOR * 3 + 2;
+                let mut expected = bytes(len);
+                let mut cursor = Cursor::new(make_vec(len));
 
-                v.push(());
-                assert_eq!(v.len(), 1);
+                let data = vec![255; BRANCH_FACTOR + 4];
 
-                v.push(());
-                assert_eq!(v.len(), 2);
+                cursor.seek(SeekFrom::Start(5)).unwrap();
+                cursor.write_all(&data).unwrap();
+                expected[5..5 + data.len()].copy_from_slice(&data);
 
-                for i in 0..v.len() {
-                    v.get(i);
-                }
-                assert_eq!(v.len(), 2);
+                cursor.seek(SeekFrom::End(-1)).unwrap();
+                cursor.write_all(&data).unwrap();
+                expected.truncate(len - 1);
+                expected.extend(&data);
 
-                v.push(());
-                assert_eq!(v.len(), 3);
+                cursor.seek(SeekFrom::End(3)).unwrap();
+                cursor.write_all(&[1, 2]).unwrap();
+                expected.extend(&[0, 0, 0, 1, 2]);
 
-                v.push(());
-                assert_eq!(v.len(), 4);
+                let vec = cursor.into_inner();
 
-                for i in 0..v.len() {
-                    v.get_mut(i);
+                assert_eq!(vec.len(), expected.len());
+                for (i, byte) in expected.iter().enumerate() {
+                    assert_eq!(vec[i], *byte);
                 }
-                assert_eq!(v.len(), 4);
             }
 
             #[test]
-            fn interleaving_append_split_off_operations() {
-                let mut vec = $vec::new();
-                let mut value = 0;
+            fn write_must_fill_long_gaps() {
+                let len = BRANCH_FACTOR + 1;
+                let gap = BRANCH_FACTOR * 2 + 3;
+                let mut cursor = Cursor::new(make_vec(len));
 
-                for size in 1..(BRANCH_FACTOR * 8 + BRANCH_FACTOR) {
-                    let mut another_vec = $vec::new();
-                    for _ in 0..size {
-                        another_vec.push(value);
-                        value += 1;
-                    }
+                let data = vec![255; BRANCH_FACTOR * 2];
 
-                    vec.append(&mut another_vec);
+                cursor.seek(SeekFrom::End(gap as i64)).unwrap();
+                cursor.write_all(&data).unwrap();
 
-                    let mid = vec.len() / 2;
-                    let mut right = vec.split_off(mid);
+                let mut expected = bytes(len);
+                expected.extend(vec![0; gap]);
+                expected.extend(&data);
 
-                    vec.append(&mut right);
-                    value = vec.len();
-                }
+                let mut out = Vec::new();
+                cursor.into_inner().write_all_to(&mut out).unwrap();
 
-                for i in 0..value {
-                    assert_eq!(vec.get(i).cloned(), Some(i));
-                }
+                assert_eq!(out, expected);
             }
 
             #[test]
-            fn split_off_by_one() {
-                let mut vec = $vec::new();
+            fn write_must_not_affect_clones() {
+                let len = BRANCH_FACTOR * 4;
+                let vec = make_vec(len);
+                let mut cursor = Cursor::new(vec.clone());
 
-                for i in 0..(BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + (BRANCH_FACTOR / 2)) {
-                    vec.push(i);
-                }
+                cursor.write_all(&vec![0; len + 1]).unwrap();
 
-                for i in (0..BRANCH_FACTOR * BRANCH_FACTOR * BRANCH_FACTOR + (BRANCH_FACTOR / 2)).rev() {
-                    let mut other = vec.split_off(i);
-                    assert_eq!(other.pop(), Some(i));
-                }
+                let mut out = Vec::new();
+                vec.write_all_to(&mut out).unwrap();
 
-                assert!(vec.is_empty());
+                assert_eq!(out, bytes(len));
+                assert_eq!(cursor.get_ref().len(), len + 1);
             }
-        }
-    };
-}
-
-make_tests!(RbVec, test_rbvec);
-make_tests!(RrbVec, test_rrbvec);
-
-mod test_pvec {
-    use super::PVec;
-
-    #[test]
-    fn interleaving_append_split_off_operations() {
-        let mut vec_one = PVec::new();
-        let mut value = 0;
 
-        for size in 1..(32 * 8 + 32) {
-            let mut vec_two = PVec::new();
-            for _ in 0..size {
-                vec_two.push(value);
-                value += 1;
+            #[test]
+            fn write_all_to_must_write_all_bytes() {
+                for len in &[
+                    0,
+                    1,
+                    BRANCH_FACTOR + 1,
+                    BRANCH_FACTOR * BRANCH_FACTOR * 3 + 7,
+                ] {
+                    let vec = make_vec(*len);
+
+                    let mut out = Vec::new();
+                    vec.write_all_to(&mut out).unwrap();
+                    assert_eq!(out, bytes(*len));
+
+                    let mut writer = ChokedWriter {
+                        bytes: Vec::new(),
+                        limit: 7,
+                    };
+                    vec.write_all_to(&mut writer).unwrap();
+                    assert_eq!(writer.bytes, bytes(*len));
+                }
             }
-
-            vec_one.append(&mut vec_two);
-
-            let mid = vec_one.len() / 2;
-            let mut right = vec_one.split_off(mid);
-
-            vec_one.append(&mut right);
-            value = vec_one.len();
-        }
-
-        for i in 0..value {
-            assert_eq!(vec_one.get(i).cloned(), Some(i));
         }
-    }
+    };
 }
 
+make_io_tests!(RrbVec, test_io_rrbvec);
+make_io_tests!(PVec, test_io_pvec);
+
 macro_rules! make_iter_tests {
     ($vec:ident, $module:ident) => {
         mod $module {
@@ -722,3 +3453,481 @@ mod test_iter_pvec {
         }
     }
 }
+
+/// Tests checking that the vectors stay valid when cloning or dropping
+/// an element panics in the middle of an operation. The elements count
+/// how many of them are alive, which reveals the leaked ones.
+mod panic_suite {
+    use super::BRANCH_FACTOR;
+    use pvec::Vector;
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+
+    thread_local! {
+        static CLONES_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+        static DROPS_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+        static ALIVE: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Returns true once the countdown reaches zero, and
+    /// disarms it, so that only a single panic happens.
+    fn explodes(countdown: &'static std::thread::LocalKey<Cell<usize>>) -> bool {
+        countdown.with(|left| match left.get() {
+            0 => {
+                left.set(usize::MAX);
+                true
+            }
+            usize::MAX => false,
+            n => {
+                left.set(n - 1);
+                false
+            }
+        })
+    }
+
+    fn alive() -> usize {
+        ALIVE.with(|alive| alive.get())
+    }
+
+    #[derive(Debug)]
+    pub struct Bomb(pub usize);
+
+    impl Bomb {
+        pub fn new(value: usize) -> Self {
+            ALIVE.with(|alive| alive.set(alive.get() + 1));
+            Bomb(value)
+        }
+    }
+
+    impl Clone for Bomb {
+        fn clone(&self) -> Self {
+            if explodes(&CLONES_LEFT) {
+                panic!("cloning {}", self.0);
+            }
+
+            Bomb::new(self.0)
+        }
+    }
+
+    impl Drop for Bomb {
+        fn drop(&mut self) {
+            ALIVE.with(|alive| alive.set(alive.get() - 1));
+
+            if explodes(&DROPS_LEFT) {
+                panic!("dropping {}", self.0);
+            }
+        }
+    }
+
+    pub fn values<V: Vector<Bomb>>(vec: &V) -> Vec<usize> {
+        (0..vec.len()).map(|i| vec.get(i).unwrap().0).collect()
+    }
+
+    /// Builds a vector by appending chunks of varying sizes,
+    /// which leaves relaxed nodes in the trees supporting them.
+    pub fn build<V: Vector<Bomb>>(new: fn() -> V) -> V {
+        let mut vec = new();
+        let mut chunk = 1;
+
+        while vec.len() < BRANCH_FACTOR * BRANCH_FACTOR + BRANCH_FACTOR + 3 {
+            let mut that = new();
+
+            for _ in 0..chunk {
+                that.push(Bomb::new(vec.len() + that.len()));
+            }
+
+            vec.append(&mut that);
+            chunk = chunk % (BRANCH_FACTOR * 2) + 1;
+        }
+
+        vec
+    }
+
+    /// Panics if the length of the vector does not match its elements,
+    /// or if the vector can not be modified any further.
+    fn assert_valid<V: Vector<Bomb>>(vec: &V) {
+        let len = vec.len();
+
+        assert_eq!(values(vec).len(), len);
+        assert!(vec.get(len).is_none());
+        assert_eq!(vec.clone().into_iter().count(), len);
+
+        let mut vec = vec.clone();
+        let mut that = vec.split_off(len / 2);
+
+        that.push(Bomb::new(len));
+        assert_eq!(that.pop().map(|item| item.0), Some(len));
+
+        vec.append(&mut that);
+        assert_eq!(vec.len(), len);
+
+        while vec.pop().is_some() {}
+    }
+
+    /// Runs the operation on a vector sharing its nodes with a snapshot,
+    /// making the countdown panic at the steps the operation takes in turn,
+    /// until it completes. The steps are tried one by one at first, and then
+    /// further apart, as the operations take the same path for all elements
+    /// of a leaf. After every panic, the vector has to stay valid, the
+    /// snapshot has to be left intact, and no element may leak.
+    fn run<V, F>(countdown: &'static std::thread::LocalKey<Cell<usize>>, new: fn() -> V, op: F)
+    where
+        V: Vector<Bomb>,
+        F: Fn(&mut V),
+    {
+        let mut steps = 0;
+
+        loop {
+            let baseline = alive();
+
+            let mut vec = build(new);
+            let snapshot = vec.clone();
+            let expected = values(&snapshot);
+
+            countdown.with(|left| left.set(steps));
+            let result = panic::catch_unwind(AssertUnwindSafe(|| op(&mut vec)));
+            countdown.with(|left| left.set(usize::MAX));
+
+            assert_valid(&vec);
+            assert_eq!(values(&snapshot), expected);
+
+            drop(vec);
+            drop(snapshot);
+
+            assert_eq!(alive(), baseline);
+
+            if result.is_ok() {
+                break;
+            }
+
+            steps += 1 + steps / BRANCH_FACTOR;
+        }
+    }
+
+    pub fn with_panicking_clones<V: Vector<Bomb>, F: Fn(&mut V)>(new: fn() -> V, op: F) {
+        run(&CLONES_LEFT, new, op);
+    }
+
+    pub fn with_panicking_drops<V: Vector<Bomb>, F: Fn(&mut V)>(new: fn() -> V, op: F) {
+        run(&DROPS_LEFT, new, op);
+    }
+}
+
+macro_rules! make_panic_tests {
+    ($vec:ident, $new:expr, $module:ident) => {
+        mod $module {
+            use super::panic_suite::{
+                build, values, with_panicking_clones, with_panicking_drops, Bomb,
+            };
+            use super::$vec;
+            use super::BRANCH_FACTOR;
+
+            fn new() -> $vec<Bomb> {
+                $new()
+            }
+
+            #[test]
+            fn push_with_panicking_clones() {
+                with_panicking_clones(new, |vec| {
+                    for i in 0..BRANCH_FACTOR * 2 {
+                        vec.push(Bomb::new(i));
+                    }
+                });
+            }
+
+            #[test]
+            fn pop_with_panicking_clones() {
+                with_panicking_clones(new, |vec| {
+                    for _ in 0..BRANCH_FACTOR * 3 {
+                        vec.pop();
+                    }
+                });
+            }
+
+            #[test]
+            fn get_mut_with_panicking_clones() {
+                with_panicking_clones(new, |vec| {
+                    for i in (0..vec.len()).step_by(BRANCH_FACTOR / 2 + 1) {
+                        vec.get_mut(i).unwrap().0 += 1;
+                    }
+                });
+            }
+
+            #[test]
+            fn split_off_with_panicking_clones() {
+                let len = build(new).len();
+
+                for &mid in [1, BRANCH_FACTOR / 2, BRANCH_FACTOR + 1, len / 2, len - 1].iter() {
+                    with_panicking_clones(new, |vec| {
+                        let mut right = vec.split_off(mid);
+                        right.push(Bomb::new(0));
+                    });
+                }
+            }
+
+            #[test]
+            fn append_with_panicking_clones() {
+                let that = build(new);
+
+                with_panicking_clones(new, |vec| {
+                    vec.split_off(vec.len() - BRANCH_FACTOR / 2);
+                    vec.append(&mut that.clone());
+                    vec.append(&mut that.clone());
+                });
+
+                with_panicking_clones(new, |vec| {
+                    let mut that = that.clone();
+                    that.pop();
+
+                    that.append(vec);
+                    *vec = that;
+                });
+            }
+
+            #[test]
+            fn rotate_with_panicking_clones() {
+                with_panicking_clones(new, |vec| {
+                    let mid = vec.len() / 3;
+                    vec.rotate_left(mid);
+                });
+            }
+
+            #[test]
+            fn make_unique_with_panicking_clones() {
+                with_panicking_clones(new, |vec| vec.make_unique());
+            }
+
+            #[test]
+            fn operations_with_panicking_drops() {
+                with_panicking_drops(new, |vec| {
+                    vec.pop();
+                });
+
+                with_panicking_drops(new, |vec| {
+                    *vec.get_mut(BRANCH_FACTOR).unwrap() = Bomb::new(0);
+                });
+
+                with_panicking_drops(new, |vec| {
+                    let mid = vec.len() / 2;
+                    vec.split_off(mid);
+                });
+
+                with_panicking_drops(new, |vec| {
+                    let mut that = vec.clone();
+                    vec.append(&mut that);
+                    vec.split_off(BRANCH_FACTOR + 1);
+                });
+
+                with_panicking_drops(new, |vec| *vec = new());
+            }
+
+            #[test]
+            fn clone_with_panicking_clones_leaves_original_intact() {
+                let vec = build(new);
+                let expected = values(&vec);
+
+                super::panic_suite::with_panicking_clones(new, |that| {
+                    *that = vec.clone();
+                });
+
+                assert_eq!(values(&vec), expected);
+            }
+        }
+    };
+}
+
+make_panic_tests!(RbVec, RbVec::new, test_panic_rbvec);
+make_panic_tests!(RrbVec, RrbVec::new, test_panic_rrbvec);
+make_panic_tests!(PVec, PVec::new, test_panic_pvec_flat);
+make_panic_tests!(PVec, PVec::new_with_tree, test_panic_pvec_tree);
+
+mod test_panic_rrbvec_resize_compact {
+    use super::panic_suite::{with_panicking_clones, Bomb};
+    use super::RrbVec;
+
+    #[test]
+    fn compact_with_panicking_clones() {
+        with_panicking_clones(RrbVec::<Bomb>::new, |vec| vec.compact());
+    }
+
+    #[test]
+    fn compact_relaxed_with_panicking_clones() {
+        with_panicking_clones(RrbVec::<Bomb>::new, |vec| vec.compact_relaxed(false));
+    }
+
+    #[test]
+    fn resize_with_panicking_clones() {
+        with_panicking_clones(RrbVec::<Bomb>::new, |vec| {
+            let len = vec.len();
+
+            vec.resize(len + 7, Bomb::new(0));
+            vec.resize(len / 2, Bomb::new(0));
+        });
+    }
+}
+
+#[cfg(feature = "im_interop")]
+macro_rules! make_interop_tests {
+    ($im:ident, $module:ident) => {
+        mod $module {
+            use super::BRANCH_FACTOR;
+            use pvec::core::RrbVec;
+            use pvec::PVec;
+            use $im::Vector as IVec;
+
+            const SIZES: [usize; 7] = [0, 1, 63, 64, 65, 1000, 5000];
+
+            // Builds an RrbVec with relaxed nodes by appending vectors of uneven sizes.
+            fn relaxed_rrbvec(n: usize) -> RrbVec<usize> {
+                let mut vec = RrbVec::new();
+                let mut size = 1;
+
+                while vec.len() < n {
+                    let mut chunk = RrbVec::new();
+
+                    for i in vec.len()..n.min(vec.len() + size) {
+                        chunk.push(i);
+                    }
+
+                    vec.append(&mut chunk);
+                    size = size * 2 + BRANCH_FACTOR / 2 + 1;
+                }
+
+                vec
+            }
+
+            // Builds an im vector with relaxed nodes by appending vectors of uneven sizes.
+            fn relaxed_ivec(n: usize) -> IVec<usize> {
+                let mut vec = IVec::new();
+                let mut size = 1;
+
+                while vec.len() < n {
+                    vec.append((vec.len()..n.min(vec.len() + size)).collect());
+                    size = size * 2 + 33;
+                }
+
+                vec
+            }
+
+            fn assert_equivalent(vec: &RrbVec<usize>, ivec: &IVec<usize>) {
+                assert_eq!(vec.len(), ivec.len());
+
+                for i in 0..vec.len() {
+                    assert_eq!(vec.get(i), ivec.get(i));
+                }
+            }
+
+            #[test]
+            fn rrbvec_from_im_vector() {
+                for &n in SIZES.iter() {
+                    let ivec = (0..n).collect::<IVec<_>>();
+                    assert_equivalent(&RrbVec::from(&ivec), &ivec);
+
+                    let ivec = relaxed_ivec(n);
+                    assert_equivalent(&RrbVec::from(ivec.clone()), &ivec);
+                }
+            }
+
+            #[test]
+            fn im_vector_from_rrbvec() {
+                for &n in SIZES.iter() {
+                    let vec = (0..n).collect::<RrbVec<_>>();
+                    assert_equivalent(&vec, &IVec::from(&vec));
+
+                    let vec = relaxed_rrbvec(n);
+                    assert_equivalent(&vec, &IVec::from(vec.clone()));
+                }
+            }
+
+            #[test]
+            fn pvec_from_im_vector() {
+                for &n in SIZES.iter() {
+                    let ivec = relaxed_ivec(n);
+                    let pvec = PVec::from(&ivec);
+
+                    assert_eq!(pvec.len(), n);
+                    assert_eq!(
+                        pvec.into_iter().collect::<Vec<_>>(),
+                        (0..n).collect::<Vec<_>>()
+                    );
+                }
+            }
+
+            #[test]
+            fn im_vector_from_flat_and_tree_pvec() {
+                for &n in SIZES.iter() {
+                    let flat = (0..n).collect::<PVec<_>>();
+                    let mut tree = relaxed_rrbvec(n).into_iter().collect::<PVec<_>>();
+
+                    tree.append(&mut PVec::new_with_tree());
+
+                    for pvec in [flat, tree].iter() {
+                        let ivec = IVec::from(pvec);
+                        assert_eq!(ivec, (0..n).collect::<IVec<_>>());
+                        assert_eq!(IVec::from(pvec.clone()), ivec);
+                    }
+                }
+            }
+
+            #[test]
+            fn owning_conversions_must_move_unshared_elements() {
+                use std::rc::Rc;
+
+                let n = 1000;
+                let items = (0..n).map(Rc::new).collect::<Vec<_>>();
+                let counts =
+                    |items: &[Rc<usize>]| items.iter().map(Rc::strong_count).collect::<Vec<_>>();
+
+                let vec = items.iter().cloned().collect::<RrbVec<_>>();
+                let shared = vec.clone();
+                let ivec = IVec::from(vec);
+                assert_eq!(counts(&items), vec![3; n]);
+
+                drop(shared);
+                assert_eq!(counts(&items), vec![2; n]);
+
+                let vec = RrbVec::from(ivec);
+                assert_eq!(counts(&items), vec![2; n]);
+
+                let ivec = IVec::from(vec);
+                assert_eq!(counts(&items), vec![2; n]);
+
+                let pvec = PVec::from(ivec);
+                assert_eq!(counts(&items), vec![2; n]);
+                assert_eq!(pvec.len(), n);
+            }
+
+            #[test]
+            fn converted_vectors_stay_equivalent_after_operations() {
+                let mut ivec = relaxed_ivec(3000);
+                let mut vec = RrbVec::from(&ivec);
+
+                let ivec_right = ivec.split_off(1234);
+                let mut vec_right = vec.split_off(1234);
+
+                ivec.append(ivec_right.clone());
+                ivec.append(ivec_right);
+                vec.append(&mut vec_right.clone());
+                vec.append(&mut vec_right);
+
+                for i in 0..BRANCH_FACTOR * 3 {
+                    ivec.push_back(i);
+                    vec.push(i);
+                }
+
+                *ivec.get_mut(42).unwrap() = 0;
+                *vec.get_mut(42).unwrap() = 0;
+
+                assert_equivalent(&vec, &ivec);
+                assert_eq!(IVec::from(&vec), ivec);
+                assert_equivalent(&RrbVec::from(&ivec), &ivec);
+            }
+        }
+    };
+}
+
+#[cfg(feature = "im_interop")]
+make_interop_tests!(im, test_interop_im);
+
+#[cfg(feature = "im_interop")]
+make_interop_tests!(im_rc, test_interop_im_rc);
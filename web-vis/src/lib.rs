@@ -1,75 +1,608 @@
 extern crate pvec;
 extern crate serde_json;
 
-use pvec::core::RrbVec;
+use pvec::core::{RbVec, RrbVec};
+use pvec::{Backing, PVec};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::mem;
 use wasm_bindgen::prelude::*;
 
-// We need to keep state on the WASM side because we rely on the identity of underlying objects.
-// If we serialize and send values over to JS we will lose identity of objects, which defeats the whole point of the demo.
-static mut STATE: Vec<RrbVec<usize>> = Vec::new();
+/// A vector of any of the types the visualizer can show.
+#[derive(Clone)]
+enum Vector {
+    Rrb(RrbVec<usize>),
+    Rb(RbVec<usize>),
+    P(PVec<usize>),
+}
 
-#[wasm_bindgen]
-pub fn push_vec() {
-    unsafe { STATE.push(RrbVec::new()) }
+macro_rules! dispatch {
+    ($vector:expr, $vec:ident => $body:expr) => {
+        match $vector {
+            Vector::Rrb($vec) => $body,
+            Vector::Rb($vec) => $body,
+            Vector::P($vec) => $body,
+        }
+    };
 }
 
-#[wasm_bindgen]
-pub fn set_vec_size(vec_idx: usize, size: usize) {
-    unsafe {
-        let vec = STATE.get_mut(vec_idx).unwrap();
+impl Vector {
+    fn kind(&self) -> &'static str {
+        match self {
+            Vector::Rrb(..) => "RrbVec",
+            Vector::Rb(..) => "RbVec",
+            Vector::P(..) => "PVec",
+        }
+    }
+
+    fn len(&self) -> usize {
+        dispatch!(self, vec => vec.len())
+    }
+
+    fn push(&mut self, value: usize) {
+        dispatch!(self, vec => vec.push(value))
+    }
 
-        if vec.len() < size {
-            for i in vec.len()..size {
-                vec.push(i);
+    fn pop(&mut self) -> Option<usize> {
+        dispatch!(self, vec => vec.pop())
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut usize> {
+        dispatch!(self, vec => vec.get_mut(index))
+    }
+
+    fn split_off(&mut self, index: usize) -> Vector {
+        match self {
+            Vector::Rrb(vec) => Vector::Rrb(vec.split_off(index)),
+            Vector::Rb(vec) => Vector::Rb(vec.split_off(index)),
+            Vector::P(vec) => Vector::P(vec.split_off(index)),
+        }
+    }
+
+    fn append(&mut self, that: &mut Vector) -> Result<(), String> {
+        match (self, that) {
+            (Vector::Rrb(vec), Vector::Rrb(that)) => vec.append(that),
+            (Vector::Rb(vec), Vector::Rb(that)) => vec.append(that),
+            (Vector::P(vec), Vector::P(that)) => vec.append(that),
+            (vec, that) => return Err(format!("can not append {} to {}", that.kind(), vec.kind())),
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the vector, including the addresses of its nodes.
+    fn to_json(&self) -> Value {
+        let mut value = match self {
+            Vector::Rrb(vec) => serde_json::to_value(vec).unwrap(),
+            Vector::Rb(vec) => serde_json::to_value(vec).unwrap(),
+            Vector::P(vec) => match serde_json::to_value(vec).unwrap() {
+                Value::Object(mut object) => match object.remove("tree") {
+                    Some(tree) => tree,
+                    None => Value::Object(object),
+                },
+                value => value,
+            },
+        };
+
+        value["kind"] = json!(self.kind());
+        value["len"] = json!(self.len());
+
+        if let Vector::P(vec) = self {
+            value["backing"] = match vec.backing() {
+                Backing::Flat => json!("flat"),
+                Backing::Tree => json!("tree"),
+                Backing::Spilled => json!("spilled"),
+            };
+        }
+
+        value
+    }
+}
+
+/// Calls the function with the address of every node in the serialized vector.
+fn visit_addresses<F: FnMut(usize)>(value: &Value, f: &mut F) {
+    match value {
+        Value::Object(object) => {
+            if let Some(addr) = object.get("addr").and_then(Value::as_u64) {
+                f(addr as usize);
             }
-        } else {
-            vec.split_off(size);
+
+            object.values().for_each(|value| visit_addresses(value, f));
         }
+        Value::Array(array) => array.iter().for_each(|value| visit_addresses(value, f)),
+        _ => {}
     }
 }
 
-#[wasm_bindgen]
-pub fn get_vec_size(vec_idx: usize) -> usize {
-    unsafe { STATE.get(vec_idx).unwrap().len() }
+/// Replaces the addresses of nodes in the serialized vector with their ids.
+fn replace_addresses(value: &mut Value, ids: &HashMap<usize, u32>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(addr) = object.remove("addr").and_then(|addr| addr.as_u64()) {
+                object.insert("id".to_string(), json!(ids[&(addr as usize)]));
+            }
+
+            object
+                .values_mut()
+                .for_each(|value| replace_addresses(value, ids));
+        }
+        Value::Array(array) => array
+            .iter_mut()
+            .for_each(|value| replace_addresses(value, ids)),
+        _ => {}
+    }
 }
 
-#[wasm_bindgen]
-pub fn split_off_vec(vec_idx: usize, idx: usize) -> usize {
-    unsafe {
-        let other = STATE.get_mut(vec_idx).unwrap().split_off(idx);
-        let new_vec_idx = vec_idx + 1;
+/// Calls the function with the position and the id of every node in the
+/// snapshot of the state. Positions are paths from the vector handle,
+/// which identify where in the vectors a node is referenced from.
+fn visit_node_ids<F: FnMut(&str, u64)>(value: &Value, path: &mut String, f: &mut F) {
+    let len = path.len();
 
-        STATE.insert(new_vec_idx, other);
+    match value {
+        Value::Object(object) => {
+            if let Some(id) = object.get("id").and_then(Value::as_u64) {
+                f(path, id);
+            }
 
-        new_vec_idx
+            for (key, value) in object {
+                path.push('/');
+                path.push_str(key);
+                visit_node_ids(value, path, f);
+                path.truncate(len);
+            }
+        }
+        Value::Array(array) => {
+            for (i, value) in array.iter().enumerate() {
+                path.push('/');
+                path.push_str(&i.to_string());
+                visit_node_ids(value, path, f);
+                path.truncate(len);
+            }
+        }
+        _ => {}
     }
 }
 
-#[wasm_bindgen]
-pub fn concatenat_all() {
-    unsafe {
-        let mut first = STATE.first_mut();
-        if let Some(ref mut accumulator) = first {
-            for i in 1..STATE.len() {
-                let other = STATE.get_mut(i).unwrap();
-                accumulator.append(other);
+/// Maps positions of nodes in the snapshot of the state to their ids.
+fn node_positions(state: &Value) -> BTreeMap<String, u64> {
+    let mut positions = BTreeMap::new();
+
+    for vec in state.as_array().into_iter().flatten() {
+        let mut path = vec["handle"].to_string();
+        visit_node_ids(vec, &mut path, &mut |path, id| {
+            positions.insert(path.to_string(), id);
+        });
+    }
+
+    positions
+}
+
+/// Counts how many times every node is referenced in the snapshot of the state.
+fn reference_counts(positions: &BTreeMap<String, u64>) -> HashMap<u64, usize> {
+    let mut counts = HashMap::new();
+
+    for id in positions.values() {
+        *counts.entry(*id).or_insert(0) += 1;
+    }
+
+    counts
+}
+
+/// Lists the nodes which were created, freed, or became shared between the
+/// snapshots of the state. A new node which took the place of a shared node
+/// that is still alive is listed as copied, since that is what `make_mut` does.
+fn diff(before: &Value, after: &Value) -> Value {
+    let before_positions = node_positions(before);
+    let after_positions = node_positions(after);
+
+    let before_counts = reference_counts(&before_positions);
+    let after_counts = reference_counts(&after_positions);
+
+    let mut created = BTreeSet::new();
+    let mut copied = BTreeMap::new();
+
+    for (path, id) in &after_positions {
+        if before_counts.contains_key(id) || copied.contains_key(id) {
+            continue;
+        }
+
+        match before_positions.get(path) {
+            Some(from) if before_counts[from] > 1 && after_counts.contains_key(from) => {
+                created.remove(id);
+                copied.insert(*id, *from);
+            }
+            _ => {
+                created.insert(*id);
             }
         }
-        STATE.truncate(1);
     }
+
+    let freed = before_counts
+        .keys()
+        .filter(|id| !after_counts.contains_key(id))
+        .collect::<BTreeSet<_>>();
+
+    let shared = after_counts
+        .iter()
+        .filter(|(id, count)| **count > 1 && before_counts.get(id).map_or(0, |count| *count) <= 1)
+        .map(|(id, _)| id)
+        .collect::<BTreeSet<_>>();
+
+    let copied = copied
+        .iter()
+        .map(|(id, from)| json!({ "id": id, "from": from }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "created": created,
+        "copied": copied,
+        "freed": freed,
+        "shared": shared,
+    })
 }
 
-#[wasm_bindgen]
-pub fn clear() {
-    unsafe { STATE.clear() }
+/// An operation applied to the vectors, along with the
+/// snapshots of all vectors before and after it.
+struct Step {
+    operation: Value,
+    before: Value,
+    after: Value,
+    diff: Value,
 }
 
+/// The vectors shown by the visualizer, addressed by handles which stay
+/// the same for the lifetime of a vector. The state is kept on the WASM
+/// side, because the demo relies on the identity of the underlying nodes,
+/// which would be lost if the vectors were sent over to JS.
+///
+/// Nodes are given ids, which stay the same as long as the nodes are alive.
+/// A node that is freed and a node allocated later at the same address
+/// get different ids, as long as no operation does both at once.
+///
+/// Every operation which succeeds is recorded in the history, so that
+/// the front end can step back and forth through the operations.
 #[wasm_bindgen]
-pub fn get(index: usize) -> JsValue {
-    unsafe { JsValue::from_str(serde_json::to_string(&STATE.get(index)).unwrap().as_str()) }
+#[derive(Default)]
+pub struct VisState {
+    vecs: BTreeMap<u32, Vector>,
+    next_handle: u32,
+    node_ids: HashMap<usize, u32>,
+    next_node_id: u32,
+    history: Vec<Step>,
+}
+
+impl VisState {
+    fn vec(&self, handle: u32) -> Result<&Vector, String> {
+        self.vecs
+            .get(&handle)
+            .ok_or_else(|| format!("unknown vector handle {}", handle))
+    }
+
+    fn vec_mut(&mut self, handle: u32) -> Result<&mut Vector, String> {
+        self.vecs
+            .get_mut(&handle)
+            .ok_or_else(|| format!("unknown vector handle {}", handle))
+    }
+
+    fn insert(&mut self, vec: Vector) -> u32 {
+        let handle = self.next_handle;
+
+        self.next_handle += 1;
+        self.vecs.insert(handle, vec);
+
+        handle
+    }
+
+    /// Gives ids to the nodes allocated since the last refresh,
+    /// and forgets the ids of the nodes which have been freed.
+    fn refresh_node_ids(&mut self) {
+        let old_ids = mem::take(&mut self.node_ids);
+        let next_node_id = &mut self.next_node_id;
+        let node_ids = &mut self.node_ids;
+
+        for vec in self.vecs.values() {
+            visit_addresses(&vec.to_json(), &mut |addr| {
+                if node_ids.contains_key(&addr) {
+                    return;
+                }
+
+                let id = match old_ids.get(&addr) {
+                    Some(id) => *id,
+                    None => {
+                        *next_node_id += 1;
+                        *next_node_id - 1
+                    }
+                };
+
+                node_ids.insert(addr, id);
+            });
+        }
+    }
+
+    /// Applies the operation, and records it in the history if it succeeds.
+    fn record<R, F>(&mut self, operation: Value, f: F) -> Result<R, String>
+    where
+        F: FnOnce(&mut VisState) -> Result<R, String>,
+    {
+        let before = self.state_value();
+        let result = f(self);
+
+        if result.is_ok() {
+            self.refresh_node_ids();
+
+            let after = self.state_value();
+            let diff = diff(&before, &after);
+
+            self.history.push(Step {
+                operation,
+                before,
+                after,
+                diff,
+            });
+        }
+
+        result
+    }
+
+    fn step(&self, index: usize) -> Result<&Step, String> {
+        self.history.get(index).ok_or_else(|| {
+            format!(
+                "step {} out of bounds of history of length {}",
+                index,
+                self.history.len()
+            )
+        })
+    }
+
+    fn state_value(&self) -> Value {
+        let vecs = self
+            .vecs
+            .keys()
+            .map(|handle| self.snapshot_value(*handle).unwrap())
+            .collect();
+
+        Value::Array(vecs)
+    }
+
+    fn snapshot_value(&self, handle: u32) -> Result<Value, String> {
+        let mut value = self.vec(handle)?.to_json();
+
+        replace_addresses(&mut value, &self.node_ids);
+        value["handle"] = json!(handle);
+
+        Ok(value)
+    }
 }
 
 #[wasm_bindgen]
-pub fn len() -> usize {
-    unsafe { STATE.len() }
+impl VisState {
+    /// Constructs the state without any vectors.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> VisState {
+        VisState::default()
+    }
+
+    /// Adds an empty `RrbVec`, and returns its handle.
+    pub fn create_rrb_vec(&mut self) -> u32 {
+        self.record(json!({ "op": "create_rrb_vec" }), |state| {
+            Ok(state.insert(Vector::Rrb(RrbVec::new())))
+        })
+        .unwrap()
+    }
+
+    /// Adds an empty `RbVec`, and returns its handle.
+    pub fn create_rb_vec(&mut self) -> u32 {
+        self.record(json!({ "op": "create_rb_vec" }), |state| {
+            Ok(state.insert(Vector::Rb(RbVec::new())))
+        })
+        .unwrap()
+    }
+
+    /// Adds an empty `PVec`, which starts out flat, and returns its handle.
+    pub fn create_pvec(&mut self) -> u32 {
+        self.record(json!({ "op": "create_pvec" }), |state| {
+            Ok(state.insert(Vector::P(PVec::new())))
+        })
+        .unwrap()
+    }
+
+    /// Returns the handles of all vectors in the order they were created.
+    pub fn handles(&self) -> Vec<u32> {
+        self.vecs.keys().cloned().collect()
+    }
+
+    /// Returns the number of vectors.
+    pub fn len(&self) -> usize {
+        self.vecs.len()
+    }
+
+    /// Returns true if there are no vectors.
+    pub fn is_empty(&self) -> bool {
+        self.vecs.is_empty()
+    }
+
+    /// Returns the number of elements in the vector.
+    pub fn vec_len(&self, handle: u32) -> Result<usize, String> {
+        Ok(self.vec(handle)?.len())
+    }
+
+    /// Returns the element at the index, if it is in bounds.
+    pub fn get(&self, handle: u32, index: usize) -> Result<Option<usize>, String> {
+        Ok(dispatch!(self.vec(handle)?, vec => vec.get(index).cloned()))
+    }
+
+    pub fn push(&mut self, handle: u32, value: usize) -> Result<(), String> {
+        self.record(
+            json!({ "op": "push", "handle": handle, "value": value }),
+            |state| {
+                state.vec_mut(handle)?.push(value);
+                Ok(())
+            },
+        )
+    }
+
+    pub fn pop(&mut self, handle: u32) -> Result<Option<usize>, String> {
+        self.record(json!({ "op": "pop", "handle": handle }), |state| {
+            Ok(state.vec_mut(handle)?.pop())
+        })
+    }
+
+    /// Replaces the element at the index through `get_mut`,
+    /// which copies the shared nodes on the path to it.
+    pub fn set(&mut self, handle: u32, index: usize, value: usize) -> Result<(), String> {
+        let operation = json!({ "op": "set", "handle": handle, "index": index, "value": value });
+
+        self.record(operation, |state| {
+            let vec = state.vec_mut(handle)?;
+
+            if index >= vec.len() {
+                return Err(format!(
+                    "index {} out of bounds of length {}",
+                    index,
+                    vec.len()
+                ));
+            }
+
+            *vec.get_mut(index).unwrap() = value;
+            Ok(())
+        })
+    }
+
+    /// Pushes elements equal to their indices, or splits the
+    /// elements off, until the vector is of the given size.
+    pub fn resize(&mut self, handle: u32, size: usize) -> Result<(), String> {
+        self.record(
+            json!({ "op": "resize", "handle": handle, "size": size }),
+            |state| {
+                let vec = state.vec_mut(handle)?;
+
+                if vec.len() < size {
+                    for i in vec.len()..size {
+                        vec.push(i);
+                    }
+                } else {
+                    vec.split_off(size);
+                }
+
+                Ok(())
+            },
+        )
+    }
+
+    /// Adds a clone of the vector, and returns its handle.
+    /// A flat `PVec` is spilled into a tree in the clone.
+    pub fn clone_vec(&mut self, handle: u32) -> Result<u32, String> {
+        self.record(json!({ "op": "clone", "handle": handle }), |state| {
+            let vec = state.vec(handle)?.clone();
+            Ok(state.insert(vec))
+        })
+    }
+
+    /// Splits the vector at the index, and returns the handle
+    /// of the new vector holding the elements after the index.
+    pub fn split_off(&mut self, handle: u32, index: usize) -> Result<u32, String> {
+        self.record(
+            json!({ "op": "split_off", "handle": handle, "index": index }),
+            |state| {
+                let vec = state.vec_mut(handle)?;
+
+                if index > vec.len() {
+                    return Err(format!(
+                        "index {} out of bounds of length {}",
+                        index,
+                        vec.len()
+                    ));
+                }
+
+                let other = vec.split_off(index);
+                Ok(state.insert(other))
+            },
+        )
+    }
+
+    /// Moves all elements of the other vector into the vector, leaving
+    /// the other one empty. Both vectors must be of the same type.
+    pub fn append(&mut self, handle: u32, other: u32) -> Result<(), String> {
+        if handle == other {
+            return Err("can not append a vector to itself".to_string());
+        }
+
+        self.record(
+            json!({ "op": "append", "handle": handle, "other": other }),
+            |state| {
+                let mut that = mem::replace(state.vec_mut(other)?, Vector::Rrb(RrbVec::new()));
+                let result = state.vec_mut(handle).and_then(|vec| vec.append(&mut that));
+
+                state.vecs.insert(other, that);
+                result
+            },
+        )
+    }
+
+    /// Removes the vector.
+    pub fn remove(&mut self, handle: u32) -> Result<(), String> {
+        self.record(json!({ "op": "remove", "handle": handle }), |state| {
+            state
+                .vecs
+                .remove(&handle)
+                .map(|_| ())
+                .ok_or_else(|| format!("unknown vector handle {}", handle))
+        })
+    }
+
+    /// Removes all vectors.
+    pub fn clear(&mut self) {
+        self.record(json!({ "op": "clear" }), |state| {
+            state.vecs.clear();
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    /// Returns true if the vector is a `PVec` backed by the standard vector.
+    pub fn is_flat(&self, handle: u32) -> Result<bool, String> {
+        match self.vec(handle)? {
+            Vector::P(vec) => Ok(vec.backing() == Backing::Flat),
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns the structure of the vector as JSON, where nodes
+    /// are identified by ids, which are stable across operations.
+    pub fn snapshot(&self, handle: u32) -> Result<String, String> {
+        Ok(self.snapshot_value(handle)?.to_string())
+    }
+
+    /// Returns the number of operations recorded in the history.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the operation at the index in the history, along with the
+    /// snapshots of all vectors before and after it, as JSON.
+    pub fn snapshot_at(&self, index: usize) -> Result<String, String> {
+        let step = self.step(index)?;
+
+        let value = json!({
+            "index": index,
+            "operation": step.operation,
+            "before": step.before,
+            "after": step.after,
+        });
+
+        Ok(value.to_string())
+    }
+
+    /// Returns the ids of the nodes which were created, copied by
+    /// `make_mut`, freed or became shared by the operation at the
+    /// index in the history, as JSON.
+    pub fn diff_at(&self, index: usize) -> Result<String, String> {
+        Ok(self.step(index)?.diff.to_string())
+    }
+
+    /// Forgets all operations recorded in the history.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
 }
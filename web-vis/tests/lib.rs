@@ -1,11 +1,301 @@
 #![cfg(target_arch = "wasm32")]
 
+extern crate serde_json;
 extern crate wasm_bindgen_test;
+extern crate web_vis;
+
+use serde_json::Value;
+use std::collections::HashSet;
 use wasm_bindgen_test::*;
+use web_vis::VisState;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
+fn snapshot(state: &VisState, handle: u32) -> Value {
+    serde_json::from_str(&state.snapshot(handle).unwrap()).unwrap()
+}
+
+fn node_ids(value: &Value, ids: &mut HashSet<u64>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(id) = object.get("id").and_then(Value::as_u64) {
+                ids.insert(id);
+            }
+
+            object.values().for_each(|value| node_ids(value, ids));
+        }
+        Value::Array(array) => array.iter().for_each(|value| node_ids(value, ids)),
+        _ => {}
+    }
+}
+
+fn snapshot_node_ids(state: &VisState, handle: u32) -> HashSet<u64> {
+    let mut ids = HashSet::new();
+    node_ids(&snapshot(state, handle), &mut ids);
+    ids
+}
+
+fn contents(state: &VisState, handle: u32) -> Vec<usize> {
+    (0..state.vec_len(handle).unwrap())
+        .map(|i| state.get(handle, i).unwrap().unwrap())
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn handles_are_stable() {
+    let mut state = VisState::new();
+
+    let first = state.create_rrb_vec();
+    let second = state.create_rb_vec();
+    let third = state.create_pvec();
+
+    assert_eq!(state.handles(), vec![first, second, third]);
+
+    state.remove(second).unwrap();
+    let fourth = state.create_rrb_vec();
+
+    assert_eq!(state.handles(), vec![first, third, fourth]);
+    assert_ne!(fourth, second);
+    assert!(state.push(second, 1).is_err());
+    assert!(state.snapshot(second).is_err());
+
+    state.clear();
+    assert!(state.is_empty());
+}
+
+#[wasm_bindgen_test]
+fn push_pop_and_set() {
+    let mut state = VisState::new();
+
+    for handle in vec![
+        state.create_rrb_vec(),
+        state.create_rb_vec(),
+        state.create_pvec(),
+    ] {
+        for i in 0..100 {
+            state.push(handle, i).unwrap();
+        }
+
+        state.set(handle, 42, 1000).unwrap();
+        assert!(state.set(handle, 100, 0).is_err());
+
+        assert_eq!(state.pop(handle).unwrap(), Some(99));
+        assert_eq!(state.vec_len(handle).unwrap(), 99);
+        assert_eq!(state.get(handle, 42).unwrap(), Some(1000));
+        assert_eq!(state.get(handle, 99).unwrap(), None);
+    }
+}
+
+#[wasm_bindgen_test]
+fn snapshots_do_not_expose_addresses() {
+    let mut state = VisState::new();
+    let handle = state.create_rrb_vec();
+
+    state.resize(handle, 200).unwrap();
+
+    let snapshot = state.snapshot(handle).unwrap();
+    assert!(!snapshot.contains("addr"));
+
+    let value: Value = serde_json::from_str(&snapshot).unwrap();
+    assert_eq!(value["kind"], "RrbVec");
+    assert_eq!(value["handle"], handle);
+    assert_eq!(value["len"], 200);
+    assert!(!snapshot_node_ids(&state, handle).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn node_ids_are_stable_across_operations() {
+    let mut state = VisState::new();
+    let handle = state.create_rrb_vec();
+
+    state.resize(handle, 200).unwrap();
+    let before = snapshot_node_ids(&state, handle);
+
+    let clone = state.clone_vec(handle).unwrap();
+    assert_eq!(snapshot_node_ids(&state, clone), before);
+    assert_eq!(snapshot_node_ids(&state, handle), before);
+
+    // Only the nodes on the path to the element are copied.
+    state.set(clone, 0, 1000).unwrap();
+
+    let after = snapshot_node_ids(&state, clone);
+    assert!(!after.is_subset(&before));
+    assert!(!after.is_disjoint(&before));
+    assert_eq!(snapshot_node_ids(&state, handle), before);
+}
+
+#[wasm_bindgen_test]
+fn split_off_and_append() {
+    let mut state = VisState::new();
+    let handle = state.create_rrb_vec();
+
+    state.resize(handle, 300).unwrap();
+
+    let other = state.split_off(handle, 100).unwrap();
+    let last = state.split_off(other, 150).unwrap();
+
+    assert_eq!(state.vec_len(handle).unwrap(), 100);
+    assert_eq!(state.vec_len(other).unwrap(), 150);
+    assert_eq!(state.vec_len(last).unwrap(), 50);
+    assert!(state.split_off(last, 51).is_err());
+
+    state.append(last, handle).unwrap();
+    state.append(other, last).unwrap();
+
+    let expected = (100..250).chain(250..300).chain(0..100).collect::<Vec<_>>();
+
+    assert_eq!(contents(&state, other), expected);
+    assert_eq!(state.vec_len(handle).unwrap(), 0);
+    assert_eq!(state.vec_len(last).unwrap(), 0);
+    assert!(state.append(other, other).is_err());
+}
+
 #[wasm_bindgen_test]
-fn pass() {
-    assert_eq!(1 + 1, 2);
+fn append_of_different_types_fails() {
+    let mut state = VisState::new();
+
+    let rrb = state.create_rrb_vec();
+    let rb = state.create_rb_vec();
+
+    state.resize(rrb, 10).unwrap();
+    state.resize(rb, 10).unwrap();
+
+    assert!(state.append(rrb, rb).is_err());
+    assert_eq!(state.vec_len(rrb).unwrap(), 10);
+    assert_eq!(state.vec_len(rb).unwrap(), 10);
+}
+
+#[wasm_bindgen_test]
+fn pvec_flat_and_tree_transitions() {
+    let mut state = VisState::new();
+    let handle = state.create_pvec();
+
+    state.resize(handle, 100).unwrap();
+    assert!(state.is_flat(handle).unwrap());
+
+    let value = snapshot(&state, handle);
+    assert_eq!(value["backing"], "flat");
+    assert_eq!(value["flat"].as_array().unwrap().len(), 100);
+    assert!(snapshot_node_ids(&state, handle).is_empty());
+
+    let clone = state.clone_vec(handle).unwrap();
+    assert!(!state.is_flat(clone).unwrap());
+
+    let value = snapshot(&state, clone);
+    assert_eq!(value["backing"], "spilled");
+    assert_eq!(value["kind"], "PVec");
+    assert!(!snapshot_node_ids(&state, clone).is_empty());
+    assert_eq!(contents(&state, clone), contents(&state, handle));
+}
+
+#[wasm_bindgen_test]
+fn rb_vec_snapshot() {
+    let mut state = VisState::new();
+    let handle = state.create_rb_vec();
+
+    state.resize(handle, 100).unwrap();
+
+    let value = snapshot(&state, handle);
+    assert_eq!(value["kind"], "RbVec");
+    assert_eq!(value["len"], 100);
+    assert!(!snapshot_node_ids(&state, handle).is_empty());
+}
+
+fn diff(state: &VisState, index: usize) -> Value {
+    serde_json::from_str(&state.diff_at(index).unwrap()).unwrap()
+}
+
+fn ids(value: &Value) -> HashSet<u64> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| id.as_u64().unwrap())
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn history_records_operations() {
+    let mut state = VisState::new();
+    let handle = state.create_rrb_vec();
+
+    state.resize(handle, 100).unwrap();
+    state.push(handle, 100).unwrap();
+
+    assert!(state.set(handle, 1000, 0).is_err());
+    assert!(state.append(handle, handle).is_err());
+    assert_eq!(state.history_len(), 3);
+
+    let step: Value = serde_json::from_str(&state.snapshot_at(2).unwrap()).unwrap();
+    assert_eq!(step["index"], 2);
+    assert_eq!(step["operation"]["op"], "push");
+    assert_eq!(step["before"][0]["len"], 100);
+    assert_eq!(step["after"][0]["len"], 101);
+    assert_eq!(step["after"][0], snapshot(&state, handle));
+
+    let first: Value = serde_json::from_str(&state.snapshot_at(0).unwrap()).unwrap();
+    assert_eq!(first["operation"]["op"], "create_rrb_vec");
+    assert_eq!(first["before"], serde_json::json!([]));
+    assert!(state.snapshot_at(3).is_err());
+
+    state.clear_history();
+    assert_eq!(state.history_len(), 0);
+}
+
+#[wasm_bindgen_test]
+fn diff_of_clone_and_set() {
+    let mut state = VisState::new();
+    let handle = state.create_rrb_vec();
+
+    state.resize(handle, 200).unwrap();
+    let before = snapshot_node_ids(&state, handle);
+
+    let clone = state.clone_vec(handle).unwrap();
+    let step = diff(&state, state.history_len() - 1);
+
+    assert!(ids(&step["created"]).is_empty());
+    assert!(ids(&step["freed"]).is_empty());
+    assert_eq!(ids(&step["shared"]), before);
+
+    state.set(clone, 0, 1000).unwrap();
+    let step = diff(&state, state.history_len() - 1);
+
+    let copied = step["copied"].as_array().unwrap();
+    assert!(!copied.is_empty());
+    assert!(ids(&step["created"]).is_empty());
+    assert!(ids(&step["freed"]).is_empty());
+
+    let after = snapshot_node_ids(&state, clone);
+    for copy in copied {
+        assert!(after.contains(&copy["id"].as_u64().unwrap()));
+        assert!(before.contains(&copy["from"].as_u64().unwrap()));
+    }
+}
+
+#[wasm_bindgen_test]
+fn diff_of_append_and_remove() {
+    let mut state = VisState::new();
+
+    let first = state.create_rrb_vec();
+    let second = state.create_rrb_vec();
+
+    state.resize(first, 100).unwrap();
+    state.resize(second, 100).unwrap();
+
+    let before = snapshot_node_ids(&state, first)
+        .union(&snapshot_node_ids(&state, second))
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    state.append(first, second).unwrap();
+    let step = diff(&state, state.history_len() - 1);
+    let after = snapshot_node_ids(&state, first);
+
+    assert_eq!(ids(&step["created"]), &after - &before);
+    assert_eq!(ids(&step["freed"]), &before - &after);
+
+    state.remove(first).unwrap();
+    let step = diff(&state, state.history_len() - 1);
+
+    assert_eq!(ids(&step["freed"]), after);
 }
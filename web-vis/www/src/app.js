@@ -141,7 +141,10 @@ const concatenateVectorsButton = new ConcatenateVectorsButtonComponent();
 concatenateVectorsButton.onClick = () => {
   // If we have only one vector, there is nothing to concatenate.
   if (wasmDecorator.len() > 1) {
-    wasmDecorator.concatenatAll();
+    const vectorIds = Array.from(grid.children)
+      .filter((child) => child instanceof VectorComponent)
+      .map((child) => child.vectorVis.vec().id());
+    wasmDecorator.concatenateAll(vectorIds);
 
     // After concatenation, there will be only one vector left. Hence, we need to prune the rest.
     while (grid.children.length > 2) {
@@ -100,7 +100,7 @@ export class RrbVec {
           lenSubTree += data.len;
         }
 
-        d.id = `${data.addr}:${data.len}`;
+        d.id = `${data.id}:${data.len}`;
         d._children = children;
 
         // keep only the right-most branches expanded to save space
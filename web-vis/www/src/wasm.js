@@ -2,44 +2,64 @@ import * as wasm from "web-vis";
 
 export class WasmDecorator {
   constructor(listener) {
+    this.state = new wasm.VisState();
     this.listener = listener;
   }
 
   pushVec() {
-    const vecId = wasm.len();
-
-    wasm.push_vec();
+    const vecId = this.state.create_rrb_vec();
     this.listener();
 
     return vecId;
   }
 
   setVecSize(id, size) {
-    wasm.set_vec_size(id, size);
+    this.state.resize(id, size);
     this.listener();
   }
 
   splitOffVec(id, index) {
-    const other = wasm.split_off_vec(id, index);
+    const other = this.state.split_off(id, index);
     this.listener();
 
     return other;
   }
 
-  concatenatAll() {
-    wasm.concatenat_all();
+  // Appends the vectors to the first one in the given order, and removes the rest.
+  concatenateAll(ids) {
+    const [first, ...rest] = ids;
+
+    rest.forEach((id) => {
+      this.state.append(first, id);
+      this.state.remove(id);
+    });
+
     this.listener();
   }
 
   getVecSize(id) {
-    return wasm.get_vec_size(id);
+    return this.state.vec_len(id);
   }
 
   get(id) {
-    return wasm.get(id);
+    return this.state.snapshot(id);
   }
 
   len() {
-    return wasm.len();
+    return this.state.len();
+  }
+
+  historyLen() {
+    return this.state.history_len();
+  }
+
+  // Returns the operation at the index, and the state of all vectors before and after it.
+  snapshotAt(index) {
+    return JSON.parse(this.state.snapshot_at(index));
+  }
+
+  // Returns the ids of the nodes created, copied, freed or shared by the operation at the index.
+  diffAt(index) {
+    return JSON.parse(this.state.diff_at(index));
   }
 }